@@ -0,0 +1,39 @@
+//! Startup cost of the built-in dog: processing from scratch (on all cores and on
+//! one) versus reading the finished frames back from the disk cache
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use tapwatch::app::cache::FrameCache;
+use tapwatch::app::recolor::Variant;
+use tapwatch::app::spritesheet::SpritePack;
+
+fn startup(c: &mut Criterion) {
+    let variant = Variant::default();
+    let dir = tempfile::tempdir().unwrap();
+    let cache = FrameCache::new(dir.path());
+
+    let mut group = c.benchmark_group("embedded pack");
+    group.sample_size(10);
+
+    group.bench_function("process", |b| {
+        b.iter(|| SpritePack::embedded_with(&variant, None).unwrap())
+    });
+
+    let single = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    group.bench_function("process on one thread", |b| {
+        b.iter(|| single.install(|| SpritePack::embedded_with(&variant, None).unwrap()))
+    });
+
+    // First load fills the cache
+    SpritePack::embedded_with(&variant, Some(&cache)).unwrap();
+    group.bench_function("from disk cache", |b| {
+        b.iter(|| SpritePack::embedded_with(&variant, Some(&cache)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, startup);
+criterion_main!(benches);
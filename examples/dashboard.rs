@@ -0,0 +1,61 @@
+//! Embed the typing dog in a ratatui dashboard
+//!
+//! Run with `cargo run --example dashboard`. Keys typed into this window are fed to
+//! the dog; `Esc` quits.
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    layout::{Constraint, Layout},
+    widgets::{Block, Borders, Paragraph},
+    DefaultTerminal,
+};
+use std::{io, time::Duration};
+use tapwatch::{app::keys, App, DogWidget, GraphicsBackend};
+
+fn main() -> io::Result<()> {
+    let terminal = ratatui::init();
+    let result = run(terminal);
+    ratatui::restore();
+    result
+}
+
+fn run(mut terminal: DefaultTerminal) -> io::Result<()> {
+    let mut app = App::new();
+    let graphics = GraphicsBackend::detect();
+
+    loop {
+        let elapsed = app.get_elapsed();
+        terminal.draw(|frame| {
+            let [left, right] = Layout::horizontal([Constraint::Fill(1), Constraint::Length(44)])
+                .areas(frame.area());
+
+            let notes = Paragraph::new(
+                "Your dashboard goes here.\n\nType anything - the dog is watching.\nEsc quits.",
+            )
+            .block(Block::default().title(" dashboard ").borders(Borders::ALL));
+            frame.render_widget(notes, left);
+
+            let block = Block::default().title(" tapwatch ").borders(Borders::ALL);
+            let inner = block.inner(right);
+            frame.render_widget(block, right);
+            frame.render_stateful_widget(
+                DogWidget::new(graphics).elapsed(elapsed),
+                inner,
+                &mut app,
+            );
+        })?;
+
+        if event::poll(Duration::from_millis(33))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && key.code == KeyCode::Esc {
+                    return Ok(());
+                }
+                if let Some(key) = keys::from_crossterm(&key) {
+                    app.on_key(key.into_owned());
+                }
+            }
+        }
+
+        app.tick();
+    }
+}
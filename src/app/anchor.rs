@@ -0,0 +1,241 @@
+//! Keeping a character planted while its frames change shape
+//!
+//! Every frame gets an anchor point: the middle of its content (`center`), the
+//! frame origin (`union`), the bottom of its content (`baseline`) or a point the
+//! manifest gives. Anchors of all frames in an animation land on the same spot,
+//! and that spot is chosen so the union of everything drawn is centered.
+
+use super::manifest::Anchor;
+use image::{imageops, Rgba, RgbaImage};
+
+/// Content bounds as `(x, y, width, height)`
+pub type Bounds = (u32, u32, u32, u32);
+
+/// How an animation's frames are lined up
+#[derive(Debug, Clone, Copy)]
+pub enum Anchoring<'a> {
+    Mode(Anchor),
+    /// One `[x, y]` per frame, in the frame's own pixels
+    Points(&'a [[u32; 2]]),
+}
+
+/// Where one frame's content ends up in the output frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    /// Content bounds in the source frame (`None` if it's empty)
+    pub bounds: Option<Bounds>,
+    /// How far the frame moves
+    pub offset: (i64, i64),
+    /// Where the anchor lands in the output frame
+    pub anchor: (i64, i64),
+    /// Part of the content falls outside the output frame
+    pub clipped: bool,
+}
+
+/// Find the bounding box of non-transparent content in an image
+pub fn find_content_bounds(img: &RgbaImage) -> Option<Bounds> {
+    let (width, height) = img.dimensions();
+    let mut min_x = width;
+    let mut min_y = height;
+    let mut max_x = 0u32;
+    let mut max_y = 0u32;
+
+    for (x, y, pixel) in img.enumerate_pixels() {
+        let Rgba([_, _, _, a]) = *pixel;
+        if a > 10 {
+            // Non-transparent pixel
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+
+    if max_x >= min_x && max_y >= min_y {
+        Some((min_x, min_y, max_x - min_x + 1, max_y - min_y + 1))
+    } else {
+        None
+    }
+}
+
+/// Size every frame of an animation is drawn at (the largest input frame)
+pub fn frame_size(frames: &[RgbaImage]) -> (u32, u32) {
+    frames.iter().fold((0, 0), |(w, h), frame| {
+        (w.max(frame.width()), h.max(frame.height()))
+    })
+}
+
+/// Work out where each frame goes in a `size` output frame
+pub fn plan(frames: &[RgbaImage], anchoring: Anchoring, size: (u32, u32)) -> Vec<Placement> {
+    let bounds: Vec<Option<Bounds>> = frames.iter().map(find_content_bounds).collect();
+
+    let anchors: Vec<(i64, i64)> = bounds
+        .iter()
+        .enumerate()
+        .map(|(i, bounds)| match (anchoring, bounds) {
+            (Anchoring::Points(points), _) => {
+                let [x, y] = points[i];
+                (x as i64, y as i64)
+            }
+            (Anchoring::Mode(Anchor::Union), _) | (_, None) => (0, 0),
+            (Anchoring::Mode(Anchor::Center), Some((x, y, w, h))) => {
+                ((x + w / 2) as i64, (y + h / 2) as i64)
+            }
+            (Anchoring::Mode(Anchor::Baseline), Some((_, y, _, h))) => (0, (y + h) as i64),
+        })
+        .collect();
+
+    // Union of all content once the anchors are lined up at the origin
+    let union = bounds
+        .iter()
+        .zip(&anchors)
+        .filter_map(|(bounds, &(ax, ay))| {
+            let (x, y, w, h) = (*bounds)?;
+            let (x, y) = (x as i64 - ax, y as i64 - ay);
+            Some((x, y, x + w as i64, y + h as i64))
+        })
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)));
+
+    // Where the shared anchor lands so the union is centered
+    let (width, height) = (size.0 as i64, size.1 as i64);
+    let target = match union {
+        Some((min_x, min_y, max_x, max_y)) => (
+            (width - (max_x - min_x)).div_euclid(2) - min_x,
+            (height - (max_y - min_y)).div_euclid(2) - min_y,
+        ),
+        None => (0, 0),
+    };
+
+    bounds
+        .into_iter()
+        .zip(anchors)
+        .map(|(bounds, (ax, ay))| {
+            let offset = (target.0 - ax, target.1 - ay);
+            let clipped = bounds.is_some_and(|(x, y, w, h)| {
+                let (x, y) = (x as i64 + offset.0, y as i64 + offset.1);
+                x < 0 || y < 0 || x + w as i64 > width || y + h as i64 > height
+            });
+            Placement {
+                bounds,
+                offset,
+                anchor: target,
+                clipped,
+            }
+        })
+        .collect()
+}
+
+/// Draw a frame's content at its planned place
+pub fn apply(frame: &RgbaImage, placement: &Placement, size: (u32, u32)) -> RgbaImage {
+    let mut result = RgbaImage::from_pixel(size.0, size.1, Rgba([0, 0, 0, 0]));
+    if let Some((x, y, w, h)) = placement.bounds {
+        let content = imageops::crop_imm(frame, x, y, w, h).to_image();
+        imageops::replace(
+            &mut result,
+            &content,
+            x as i64 + placement.offset.0,
+            y as i64 + placement.offset.1,
+        );
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (u32, u32) = (20, 20);
+
+    /// A transparent 20x20 frame with an opaque box at `bounds`
+    fn frame(bounds: Bounds) -> RgbaImage {
+        let (x, y, w, h) = bounds;
+        RgbaImage::from_fn(SIZE.0, SIZE.1, |px, py| {
+            let inside = (x..x + w).contains(&px) && (y..y + h).contains(&py);
+            Rgba([200, 100, 50, if inside { 255 } else { 0 }])
+        })
+    }
+
+    /// Where each frame's content ends up once planned and drawn
+    fn placed(frames: &[RgbaImage], anchoring: Anchoring) -> Vec<Option<Bounds>> {
+        let placements = plan(frames, anchoring, SIZE);
+        assert!(placements.iter().all(|p| !p.clipped));
+        frames
+            .iter()
+            .zip(&placements)
+            .map(|(frame, placement)| find_content_bounds(&apply(frame, placement, SIZE)))
+            .collect()
+    }
+
+    const SMALL: Bounds = (2, 2, 4, 4);
+    const LARGE: Bounds = (10, 12, 6, 6);
+
+    #[test]
+    fn content_bounds() {
+        assert_eq!(find_content_bounds(&frame(LARGE)), Some(LARGE));
+        // Nearly transparent pixels don't count
+        let mut faint = frame(SMALL);
+        faint.put_pixel(0, 19, Rgba([0, 0, 0, 10]));
+        assert_eq!(find_content_bounds(&faint), Some(SMALL));
+    }
+
+    #[test]
+    fn center_lines_up_the_middles() {
+        let frames = [frame(SMALL), frame(LARGE)];
+        let placements = plan(&frames, Anchoring::Mode(Anchor::Center), SIZE);
+        assert_eq!(placements[0].anchor, (10, 10));
+        assert_eq!(placements[0].offset, (6, 6));
+        assert_eq!(placements[1].offset, (-3, -5));
+        assert_eq!(
+            placed(&frames, Anchoring::Mode(Anchor::Center)),
+            [Some((8, 8, 4, 4)), Some((7, 7, 6, 6))]
+        );
+    }
+
+    #[test]
+    fn baseline_lines_up_the_bottoms() {
+        let frames = [frame(SMALL), frame(LARGE)];
+        // Both stand on row 13, keeping their columns apart
+        assert_eq!(
+            placed(&frames, Anchoring::Mode(Anchor::Baseline)),
+            [Some((3, 9, 4, 4)), Some((11, 7, 6, 6))]
+        );
+    }
+
+    #[test]
+    fn explicit_points_line_up() {
+        let frames = [frame(SMALL), frame(LARGE)];
+        // The top-left corners, so both start at the same spot
+        let points = [[2, 2], [10, 12]];
+        assert_eq!(
+            placed(&frames, Anchoring::Points(&points)),
+            [Some((7, 7, 4, 4)), Some((7, 7, 6, 6))]
+        );
+    }
+
+    #[test]
+    fn transparent_frames_stay_put() {
+        let empty = RgbaImage::from_pixel(SIZE.0, SIZE.1, Rgba([0, 0, 0, 0]));
+        let alone = plan(
+            std::slice::from_ref(&empty),
+            Anchoring::Mode(Anchor::Center),
+            SIZE,
+        );
+        assert_eq!(
+            alone,
+            [Placement {
+                bounds: None,
+                offset: (0, 0),
+                anchor: (0, 0),
+                clipped: false,
+            }]
+        );
+        assert_eq!(apply(&empty, &alone[0], SIZE), empty);
+
+        // Nor do they move the frames around them
+        let frames = [empty.clone(), frame(SMALL)];
+        let mixed = plan(&frames, Anchoring::Mode(Anchor::Baseline), SIZE);
+        let single = plan(&frames[1..], Anchoring::Mode(Anchor::Baseline), SIZE);
+        assert_eq!(mixed[1], single[0]);
+        assert_eq!(apply(&empty, &mixed[0], SIZE), empty);
+    }
+}
@@ -0,0 +1,428 @@
+//! Aseprite and TexturePacker JSON atlases
+//!
+//! Both tools export the same `frames` structure (as a map or an array), with
+//! optional trimming and, for TexturePacker, 90° rotation. Aseprite adds per-frame
+//! `duration`s and `meta.frameTags`, which become tapwatch animations.
+
+use super::error::SpriteError;
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::time::Duration;
+
+/// One frame as stored in the atlas image
+#[derive(Debug, Clone)]
+pub struct AtlasFrame {
+    pub name: String,
+    /// Region in the atlas image (before undoing rotation)
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Stored rotated 90° clockwise (TexturePacker)
+    pub rotated: bool,
+    /// Where the trimmed region sits in the original, untrimmed frame
+    pub offset_x: u32,
+    pub offset_y: u32,
+    /// Size of the original, untrimmed frame
+    pub source_width: u32,
+    pub source_height: u32,
+    /// How long the frame is shown (Aseprite only)
+    pub duration: Option<Duration>,
+}
+
+/// A named frame range (Aseprite frame tag)
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+    /// Frame indices in playback order (direction already applied)
+    pub frames: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    /// Atlas image file, relative to the JSON file
+    pub image: Option<String>,
+    pub frames: Vec<AtlasFrame>,
+    pub tags: Vec<Tag>,
+}
+
+// Raw JSON schema shared by both tools
+
+#[derive(Deserialize)]
+struct RawAtlas {
+    frames: RawFrames,
+    #[serde(default)]
+    meta: RawMeta,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawFrames {
+    /// "Hash" export: `{ "name": { ...frame } }`
+    Map(OrderedFrames),
+    /// "Array" export: `[{ "filename": "name", ...frame }]`
+    List(Vec<RawFrame>),
+}
+
+/// Frame map entries in file order - tags refer to frames by position, so a
+/// sorted map would scramble them ("dog 10" sorts before "dog 2")
+struct OrderedFrames(Vec<(String, RawFrame)>);
+
+impl<'de> Deserialize<'de> for OrderedFrames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OrderedVisitor;
+
+        impl<'de> Visitor<'de> for OrderedVisitor {
+            type Value = OrderedFrames;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of frame names to frames")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(OrderedFrames(entries))
+            }
+        }
+
+        deserializer.deserialize_map(OrderedVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawFrame {
+    #[serde(default)]
+    filename: Option<String>,
+    frame: RawRect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    sprite_source_size: Option<RawRect>,
+    #[serde(default)]
+    source_size: Option<RawSize>,
+    #[serde(default)]
+    duration: Option<u64>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct RawRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct RawSize {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct RawMeta {
+    #[serde(default)]
+    image: Option<String>,
+    #[serde(default)]
+    frame_tags: Vec<RawTag>,
+}
+
+#[derive(Deserialize)]
+struct RawTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: Option<String>,
+}
+
+impl Atlas {
+    pub fn parse(json: &str) -> Result<Self, SpriteError> {
+        let raw: RawAtlas = serde_json::from_str(json)
+            .map_err(|err| SpriteError::Invalid(format!("invalid atlas JSON: {}", err)))?;
+
+        let named: Vec<(String, RawFrame)> = match raw.frames {
+            RawFrames::Map(OrderedFrames(entries)) => entries,
+            RawFrames::List(list) => list
+                .into_iter()
+                .enumerate()
+                .map(|(i, frame)| {
+                    (
+                        frame.filename.clone().unwrap_or_else(|| i.to_string()),
+                        frame,
+                    )
+                })
+                .collect(),
+        };
+
+        let frames: Vec<AtlasFrame> = named
+            .into_iter()
+            .map(|(name, raw)| {
+                let trimmed = raw.sprite_source_size.unwrap_or(RawRect {
+                    x: 0,
+                    y: 0,
+                    w: raw.frame.w,
+                    h: raw.frame.h,
+                });
+                let source = raw.source_size.unwrap_or(RawSize {
+                    w: raw.frame.w,
+                    h: raw.frame.h,
+                });
+                AtlasFrame {
+                    name,
+                    x: raw.frame.x,
+                    y: raw.frame.y,
+                    width: raw.frame.w,
+                    height: raw.frame.h,
+                    rotated: raw.rotated,
+                    offset_x: trimmed.x,
+                    offset_y: trimmed.y,
+                    source_width: source.w,
+                    source_height: source.h,
+                    duration: raw.duration.map(Duration::from_millis),
+                }
+            })
+            .collect();
+
+        let tags = raw
+            .meta
+            .frame_tags
+            .into_iter()
+            .map(|tag| {
+                if tag.from > tag.to || tag.to >= frames.len() {
+                    return Err(SpriteError::Invalid(format!(
+                        "atlas tag `{}` covers frames {}..={} but there are {}",
+                        tag.name,
+                        tag.from,
+                        tag.to,
+                        frames.len()
+                    )));
+                }
+                let forward: Vec<usize> = (tag.from..=tag.to).collect();
+                let frames = match tag.direction.as_deref() {
+                    Some("reverse") => forward.into_iter().rev().collect(),
+                    // Play to the end and back without repeating the end frames
+                    Some("pingpong") => {
+                        let back = forward
+                            .iter()
+                            .rev()
+                            .skip(1)
+                            .take(forward.len().saturating_sub(2));
+                        forward.iter().chain(back).copied().collect()
+                    }
+                    _ => forward,
+                };
+                Ok(Tag {
+                    name: tag.name,
+                    frames,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            image: raw.meta.image,
+            frames,
+            tags,
+        })
+    }
+
+    /// Find a tag by name (case-insensitive)
+    pub fn tag(&self, name: &str) -> Option<&Tag> {
+        self.tags.iter().find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Indices of frames whose name starts with `prefix`, in name order
+    pub fn frames_with_prefix(&self, prefix: &str) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.frames.len())
+            .filter(|&i| self.frames[i].name.starts_with(prefix))
+            .collect();
+        indices.sort_by(|&a, &b| self.frames[a].name.cmp(&self.frames[b].name));
+        indices
+    }
+
+    /// Cut a frame out of the atlas image and restore its untrimmed, unrotated form
+    pub fn frame_image(
+        &self,
+        sheet: &DynamicImage,
+        index: usize,
+    ) -> Result<RgbaImage, SpriteError> {
+        let frame = &self.frames[index];
+        // A rotated frame occupies a height x width region in the atlas
+        let (stored_w, stored_h) = if frame.rotated {
+            (frame.height, frame.width)
+        } else {
+            (frame.width, frame.height)
+        };
+        let fits = |start: u32, size: u32, limit: u32| {
+            start.checked_add(size).is_some_and(|end| end <= limit)
+        };
+        if !fits(frame.x, stored_w, sheet.width()) || !fits(frame.y, stored_h, sheet.height()) {
+            return Err(SpriteError::Invalid(format!(
+                "atlas frame `{}` is outside the {}x{} image",
+                frame.name,
+                sheet.width(),
+                sheet.height()
+            )));
+        }
+
+        let stored = sheet
+            .crop_imm(frame.x, frame.y, stored_w, stored_h)
+            .to_rgba8();
+        let trimmed = if frame.rotated {
+            imageops::rotate270(&stored)
+        } else {
+            stored
+        };
+
+        let mut full =
+            RgbaImage::from_pixel(frame.source_width, frame.source_height, Rgba([0, 0, 0, 0]));
+        imageops::replace(
+            &mut full,
+            &trimmed,
+            frame.offset_x as i64,
+            frame.offset_y as i64,
+        );
+        Ok(full)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = r#"{
+        "frames": {
+            "dog 2": { "frame": { "x": 0, "y": 0, "w": 2, "h": 2 }, "duration": 100 },
+            "dog 10": { "frame": { "x": 2, "y": 0, "w": 2, "h": 2 }, "duration": 250 },
+            "dog 3": { "frame": { "x": 4, "y": 0, "w": 2, "h": 2 } }
+        },
+        "meta": { "image": "dog.png" }
+    }"#;
+
+    /// Four frames with every tag direction
+    const ARRAY: &str = r#"{
+        "frames": [
+            { "filename": "a", "frame": { "x": 0, "y": 0, "w": 2, "h": 2 }, "duration": 10 },
+            { "filename": "b", "frame": { "x": 2, "y": 0, "w": 2, "h": 2 }, "duration": 20 },
+            { "frame": { "x": 4, "y": 0, "w": 2, "h": 2 }, "duration": 30 },
+            { "frame": { "x": 6, "y": 0, "w": 2, "h": 2 }, "duration": 40 }
+        ],
+        "meta": { "frameTags": [
+            { "name": "Walk", "from": 0, "to": 3 },
+            { "name": "back", "from": 1, "to": 3, "direction": "reverse" },
+            { "name": "bounce", "from": 0, "to": 3, "direction": "pingpong" },
+            { "name": "blink", "from": 2, "to": 2, "direction": "pingpong" }
+        ] }
+    }"#;
+
+    fn ms(ms: u64) -> Option<Duration> {
+        Some(Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn hash_frames_keep_file_order() {
+        let atlas = Atlas::parse(HASH).unwrap();
+        let names: Vec<_> = atlas.frames.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["dog 2", "dog 10", "dog 3"]);
+        let durations: Vec<_> = atlas.frames.iter().map(|f| f.duration).collect();
+        assert_eq!(durations, [ms(100), ms(250), None]);
+        assert_eq!(atlas.frames[1].x, 2);
+        assert_eq!(atlas.image.as_deref(), Some("dog.png"));
+        assert!(atlas.tags.is_empty());
+        // Prefix lookups go by name instead
+        assert_eq!(atlas.frames_with_prefix("dog "), [1, 0, 2]);
+    }
+
+    #[test]
+    fn array_frames_and_tag_directions() {
+        let atlas = Atlas::parse(ARRAY).unwrap();
+        let names: Vec<_> = atlas.frames.iter().map(|f| f.name.as_str()).collect();
+        // Unnamed frames are named by position
+        assert_eq!(names, ["a", "b", "2", "3"]);
+        let durations: Vec<_> = atlas.frames.iter().map(|f| f.duration).collect();
+        assert_eq!(durations, [ms(10), ms(20), ms(30), ms(40)]);
+        assert_eq!(atlas.image, None);
+
+        let frames = |name| atlas.tag(name).expect(name).frames.clone();
+        assert_eq!(frames("walk"), [0, 1, 2, 3]);
+        assert_eq!(frames("back"), [3, 2, 1]);
+        assert_eq!(frames("bounce"), [0, 1, 2, 3, 2, 1]);
+        assert_eq!(frames("blink"), [2]);
+        assert!(atlas.tag("run").is_none());
+    }
+
+    #[test]
+    fn tags_past_the_last_frame_are_rejected() {
+        let json = r#"{
+            "frames": [{ "frame": { "x": 0, "y": 0, "w": 1, "h": 1 } }],
+            "meta": { "frameTags": [{ "name": "run", "from": 0, "to": 1 }] }
+        }"#;
+        let err = Atlas::parse(json).unwrap_err().to_string();
+        assert!(
+            err.contains("`run` covers frames 0..=1 but there are 1"),
+            "{}",
+            err
+        );
+
+        let backwards = json.replace(r#""from": 0, "to": 1"#, r#""from": 1, "to": 0"#);
+        assert!(Atlas::parse(&backwards).is_err());
+        assert!(Atlas::parse(r#"{ "frames": 3 }"#).is_err());
+    }
+
+    /// A 3x2 frame with every pixel a different color
+    fn pattern() -> RgbaImage {
+        RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8 * 80, y as u8 * 200, 10, 255]))
+    }
+
+    /// A sheet holding `stored` at (1, 1)
+    fn sheet_with(stored: &RgbaImage) -> DynamicImage {
+        let mut sheet = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 255, 255]));
+        imageops::replace(&mut sheet, stored, 1, 1);
+        DynamicImage::ImageRgba8(sheet)
+    }
+
+    #[test]
+    fn trimmed_frames_are_padded_back_out() {
+        let json = r#"{ "frames": [{
+            "frame": { "x": 1, "y": 1, "w": 3, "h": 2 },
+            "spriteSourceSize": { "x": 1, "y": 2, "w": 3, "h": 2 },
+            "sourceSize": { "w": 5, "h": 4 }
+        }] }"#;
+        let atlas = Atlas::parse(json).unwrap();
+        let full = atlas.frame_image(&sheet_with(&pattern()), 0).unwrap();
+
+        assert_eq!(full.dimensions(), (5, 4));
+        for (x, y, pixel) in full.enumerate_pixels() {
+            let expected = match (x.checked_sub(1), y.checked_sub(2)) {
+                (Some(px), Some(py)) if px < 3 => *pattern().get_pixel(px, py),
+                _ => Rgba([0, 0, 0, 0]),
+            };
+            assert_eq!(*pixel, expected, "pixel {},{}", x, y);
+        }
+    }
+
+    #[test]
+    fn rotated_frames_are_turned_back() {
+        // TexturePacker stores the 3x2 frame turned clockwise, as a 2x3 region
+        let json = r#"{ "frames": [{
+            "frame": { "x": 1, "y": 1, "w": 3, "h": 2 },
+            "rotated": true
+        }] }"#;
+        let atlas = Atlas::parse(json).unwrap();
+        assert!(atlas.frames[0].rotated);
+        let stored = imageops::rotate90(&pattern());
+        let full = atlas.frame_image(&sheet_with(&stored), 0).unwrap();
+        assert_eq!(full, pattern());
+
+        // The stored region is checked against the sheet, not the frame's size
+        let json = json.replace(r#""y": 1"#, r#""y": 6"#);
+        let atlas = Atlas::parse(&json).unwrap();
+        assert!(atlas.frame_image(&sheet_with(&stored), 0).is_err());
+    }
+}
@@ -0,0 +1,383 @@
+//! Turning a drawing's background transparent
+//!
+//! Flood fill starts at the frame edges and only spreads through pixels close to
+//! the key color, so white fur or eye highlights enclosed by the outline survive.
+//! Chroma keying removes every close pixel instead, for green-screen style art.
+//! Either can soften the cut with alpha feathering and despill (pulling the key
+//! color out of the edge pixels, like GIMP's "color to alpha").
+
+use super::manifest::{Background, Key};
+use image::{Rgba, RgbaImage};
+use std::collections::VecDeque;
+
+/// Make a frame's background transparent as the pack asks
+pub fn remove(img: &RgbaImage, background: Background) -> RgbaImage {
+    match background {
+        Background::None => img.clone(),
+        Background::Threshold { threshold } => remove_threshold(img, threshold),
+        Background::Flood(key) => refine(img, &flood_mask(img, &key), &key),
+        Background::Key(key) => refine(img, &key_mask(img, &key), &key),
+    }
+}
+
+/// Pixels with R, G and B all above `threshold` become transparent
+fn remove_threshold(img: &RgbaImage, threshold: u8) -> RgbaImage {
+    let mut result = img.clone();
+
+    for pixel in result.pixels_mut() {
+        let Rgba([r, g, b, _a]) = *pixel;
+
+        // If pixel is very light (near white), make it transparent
+        if r > threshold && g > threshold && b > threshold {
+            *pixel = Rgba([r, g, b, 0]); // Fully transparent
+        }
+    }
+
+    result
+}
+
+/// Whether a pixel counts as background color
+fn matches(pixel: &Rgba<u8>, key: &Key) -> bool {
+    // Already transparent pixels are background whatever their color
+    pixel[3] == 0 || (0..3).all(|c| pixel[c].abs_diff(key.color.0[c]) <= key.tolerance)
+}
+
+/// Background = key-colored pixels reachable from the frame edges
+fn flood_mask(img: &RgbaImage, key: &Key) -> Vec<bool> {
+    let (width, height) = img.dimensions();
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+    let mut mask = vec![false; (width * height) as usize];
+    let mut queue = VecDeque::new();
+
+    let visit = |x: u32, y: u32, mask: &mut Vec<bool>, queue: &mut VecDeque<(u32, u32)>| {
+        if !mask[index(x, y)] && matches(img.get_pixel(x, y), key) {
+            mask[index(x, y)] = true;
+            queue.push_back((x, y));
+        }
+    };
+
+    for x in 0..width {
+        visit(x, 0, &mut mask, &mut queue);
+        visit(x, height.saturating_sub(1), &mut mask, &mut queue);
+    }
+    for y in 0..height {
+        visit(0, y, &mut mask, &mut queue);
+        visit(width.saturating_sub(1), y, &mut mask, &mut queue);
+    }
+
+    // 4-connected, so the fill can't leak through a diagonal gap in the outline
+    while let Some((x, y)) = queue.pop_front() {
+        if x > 0 {
+            visit(x - 1, y, &mut mask, &mut queue);
+        }
+        if x + 1 < width {
+            visit(x + 1, y, &mut mask, &mut queue);
+        }
+        if y > 0 {
+            visit(x, y - 1, &mut mask, &mut queue);
+        }
+        if y + 1 < height {
+            visit(x, y + 1, &mut mask, &mut queue);
+        }
+    }
+
+    mask
+}
+
+/// Background = every key-colored pixel
+fn key_mask(img: &RgbaImage, key: &Key) -> Vec<bool> {
+    img.pixels().map(|pixel| matches(pixel, key)).collect()
+}
+
+/// Clear the masked pixels, then feather and despill the edge around them
+fn refine(img: &RgbaImage, mask: &[bool], key: &Key) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    let mut result = img.clone();
+
+    // Only pixels this close to the background get softened
+    let band = if key.despill {
+        key.feather.max(1)
+    } else {
+        key.feather
+    };
+    let distances = edge_distances(width, height, mask, band);
+
+    for (i, pixel) in result.pixels_mut().enumerate() {
+        if mask[i] {
+            pixel[3] = 0;
+            continue;
+        }
+        let Some(distance) = distances[i] else {
+            continue;
+        };
+
+        if key.despill {
+            *pixel = color_to_alpha(*pixel, key.color.0);
+        }
+        if distance <= key.feather {
+            // Fade in linearly over `feather` pixels
+            let factor = distance as f32 / (key.feather as f32 + 1.0);
+            pixel[3] = (pixel[3] as f32 * factor).round() as u8;
+        }
+    }
+
+    result
+}
+
+/// Chessboard distance from each pixel to the nearest background pixel, for
+/// foreground pixels within `limit` of it
+fn edge_distances(width: u32, height: u32, mask: &[bool], limit: u8) -> Vec<Option<u8>> {
+    let mut distances: Vec<Option<u8>> = mask.iter().map(|&bg| bg.then_some(0)).collect();
+    if limit == 0 {
+        return vec![None; mask.len()];
+    }
+
+    let mut frontier: Vec<(u32, u32)> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| mask[(y * width + x) as usize])
+        .collect();
+
+    for distance in 1..=limit {
+        let mut next = Vec::new();
+        for (x, y) in frontier {
+            for dy in -1i64..=1 {
+                for dx in -1i64..=1 {
+                    let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                    if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                        continue;
+                    }
+                    let i = (ny as u32 * width + nx as u32) as usize;
+                    if distances[i].is_none() {
+                        distances[i] = Some(distance);
+                        next.push((nx as u32, ny as u32));
+                    }
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    // Background pixels themselves aren't part of the edge band
+    for (distance, &bg) in distances.iter_mut().zip(mask) {
+        if bg {
+            *distance = None;
+        }
+    }
+    distances
+}
+
+/// Treat a pixel as the foreground blended over the key color and undo the blend:
+/// the lowest alpha that explains the pixel, with the key color taken out
+fn color_to_alpha(pixel: Rgba<u8>, key: [u8; 3]) -> Rgba<u8> {
+    let channel_alpha = |c: usize| {
+        let (value, key) = (pixel[c] as f32, key[c] as f32);
+        if value > key {
+            (value - key) / (255.0 - key)
+        } else if value < key {
+            (key - value) / key
+        } else {
+            0.0
+        }
+    };
+    let alpha = (0..3).map(channel_alpha).fold(0.0f32, f32::max);
+    if alpha <= 0.0 {
+        return Rgba([pixel[0], pixel[1], pixel[2], 0]);
+    }
+
+    let unblend = |c: usize| {
+        let key = key[c] as f32;
+        (key + (pixel[c] as f32 - key) / alpha)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    };
+    Rgba([
+        unblend(0),
+        unblend(1),
+        unblend(2),
+        (pixel[3] as f32 * alpha).round() as u8,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    //! Golden-image tests: each sample frame goes through a background setting and
+    //! must match `tests/golden/<name>.png` exactly. Run with `UPDATE_GOLDEN=1` to
+    //! rewrite the goldens after an intended change, then review them by eye.
+
+    use super::*;
+    use crate::app::manifest::Color;
+    use std::path::PathBuf;
+
+    const SHEET: &[u8] = include_bytes!("../assets/dog_sprites.png");
+
+    fn golden_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{}.png", name))
+    }
+
+    fn assert_golden(name: &str, actual: &RgbaImage) {
+        let path = golden_path(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            actual.save(&path).unwrap();
+            return;
+        }
+        let expected = image::open(&path)
+            .unwrap_or_else(|err| panic!("{}: {} (run with UPDATE_GOLDEN=1)", path.display(), err))
+            .to_rgba8();
+        assert_eq!(
+            expected.dimensions(),
+            actual.dimensions(),
+            "{} size changed",
+            name
+        );
+        let differing = expected
+            .pixels()
+            .zip(actual.pixels())
+            .filter(|(a, b)| a != b)
+            .count();
+        assert_eq!(
+            differing, 0,
+            "{}: {} pixels differ from the golden",
+            name, differing
+        );
+    }
+
+    /// A 256x256 frame from the built-in sheet, by grid cell
+    fn dog_frame(cell: u32) -> RgbaImage {
+        let sheet = image::load_from_memory(SHEET).unwrap();
+        sheet
+            .crop_imm((cell % 4) * 256, (cell / 4) * 256, 256, 256)
+            .to_rgba8()
+    }
+
+    /// The first sample frame with white eye highlights painted inside its dark fur,
+    /// which flood fill must keep and thresholding removes
+    fn highlighted_dog_frame() -> RgbaImage {
+        let mut frame = dog_frame(0);
+        for &(x, y) in &HIGHLIGHTS {
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                frame.put_pixel(x + dx, y + dy, Rgba([255, 255, 255, 255]));
+            }
+        }
+        frame
+    }
+
+    /// Top-left corners of the 2x2 highlights, each well inside the fur
+    const HIGHLIGHTS: [(u32, u32); 2] = [(155, 113), (60, 140)];
+
+    /// A white blob inside a dark outline with a one-pixel fringe blended toward
+    /// the background, and a background-colored "eye" in the middle
+    fn blob_frame(background: [u8; 3]) -> RgbaImage {
+        let [r, g, b] = background;
+        let bg = Rgba([r, g, b, 255]);
+        let blend = |outline: u8, bg: u8| ((outline as u16 + bg as u16) / 2) as u8;
+        let fringe = Rgba([blend(30, r), blend(20, g), blend(10, b), 255]);
+        let mut img = RgbaImage::from_pixel(32, 32, bg);
+        for y in 0..32i32 {
+            for x in 0..32i32 {
+                let d = ((x - 16).pow(2) + (y - 16).pow(2)) as f32;
+                let pixel = match d.sqrt() {
+                    r if r < 10.0 => Rgba([255, 255, 255, 255]),
+                    r if r < 12.0 => Rgba([30, 20, 10, 255]),
+                    r if r < 13.0 => fringe,
+                    _ => continue,
+                };
+                img.put_pixel(x as u32, y as u32, pixel);
+            }
+        }
+        img.put_pixel(16, 16, bg);
+        img
+    }
+
+    fn key(color: [u8; 3], tolerance: u8, feather: u8, despill: bool) -> Key {
+        Key {
+            color: Color(color),
+            tolerance,
+            feather,
+            despill,
+        }
+    }
+
+    const WHITE: [u8; 3] = [255, 255, 255];
+    const GREEN: [u8; 3] = [0, 200, 0];
+
+    #[test]
+    fn threshold_matches_legacy_behavior() {
+        let frame = dog_frame(0);
+        let result = remove(&frame, Background::Threshold { threshold: 240 });
+        assert_golden("dog_threshold", &result);
+    }
+
+    #[test]
+    fn flood_keeps_enclosed_highlights() {
+        let frame = blob_frame(WHITE);
+        let flood = remove(&frame, Background::Flood(key(WHITE, 15, 0, false)));
+        assert_golden("white_flood", &flood);
+        assert_eq!(flood.get_pixel(16, 16)[3], 255);
+        assert_eq!(flood.get_pixel(16, 20)[3], 255);
+        assert_eq!(flood.get_pixel(0, 0)[3], 0);
+
+        // Thresholding punches the white body out
+        let threshold = remove(&frame, Background::Threshold { threshold: 240 });
+        assert_eq!(threshold.get_pixel(16, 20)[3], 0);
+    }
+
+    #[test]
+    fn flood_on_sample_frame() {
+        let frame = highlighted_dog_frame();
+        let flood = remove(&frame, Background::Flood(key(WHITE, 15, 0, false)));
+        assert_golden("dog_flood", &flood);
+
+        // Flood fill keeps the highlights that thresholding punches out
+        let threshold = remove(&frame, Background::Threshold { threshold: 240 });
+        for &(x, y) in &HIGHLIGHTS {
+            assert_eq!(flood.get_pixel(x, y)[3], 255, "highlight at {},{}", x, y);
+            assert_eq!(threshold.get_pixel(x, y)[3], 0, "highlight at {},{}", x, y);
+        }
+        assert_ne!(flood, threshold);
+
+        // Flood fill never removes more than thresholding the same color
+        let opaque = |img: &RgbaImage| img.pixels().filter(|p| p[3] > 0).count();
+        assert!(opaque(&flood) > opaque(&threshold));
+    }
+
+    #[test]
+    fn flood_with_feather_and_despill() {
+        let frame = dog_frame(9);
+        let result = remove(&frame, Background::Flood(key(WHITE, 15, 2, true)));
+        assert_golden("dog_flood_soft", &result);
+    }
+
+    #[test]
+    fn chroma_key_removes_enclosed_key_color() {
+        let frame = blob_frame(GREEN);
+        let keyed = remove(&frame, Background::Key(key(GREEN, 40, 0, false)));
+        assert_eq!(keyed.get_pixel(16, 16)[3], 0);
+        assert_golden("green_key", &keyed);
+
+        // Flood fill can't reach the enclosed pixel
+        let flooded = remove(&frame, Background::Flood(key(GREEN, 40, 0, false)));
+        assert_eq!(flooded.get_pixel(16, 16)[3], 255);
+    }
+
+    #[test]
+    fn despill_pulls_key_color_out_of_fringe() {
+        let frame = blob_frame(GREEN);
+        let result = remove(&frame, Background::Key(key(GREEN, 40, 1, true)));
+        assert_golden("green_key_soft", &result);
+
+        // The fringe pixel at radius 12.x loses most of its green
+        let fringe = result.get_pixel(16, 28);
+        let original = frame.get_pixel(16, 28);
+        assert!(fringe[1] < original[1], "{:?} -> {:?}", original, fringe);
+        assert!(fringe[3] < 255);
+    }
+
+    #[test]
+    fn none_keeps_the_image() {
+        let frame = blob_frame(GREEN);
+        assert_eq!(remove(&frame, Background::None), frame);
+    }
+}
@@ -0,0 +1,492 @@
+//! On-disk cache of processed sprite packs
+//!
+//! Cutting, cleaning up and anchoring frames is the slow part of startup, so the
+//! finished frames are kept as PNGs under `$XDG_CACHE_HOME/tapwatch/frames`, one
+//! directory per key. The key hashes everything the result depends on: the pack's
+//! files (or the embedded sheet and manifest), the color variant and the version of
+//! the processing itself. Any change gives a new key, so stale entries are never read;
+//! they're dropped once enough newer ones are stored.
+
+use super::error::SpriteError;
+use super::frame::Frame;
+use super::machine::{self, Machine};
+use super::manifest::{Facing, Playback};
+use super::recolor::Variant;
+use super::spritesheet::{self, Animation, SpritePack};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Bump whenever processing changes what comes out for the same input
+const CACHE_FORMAT: u32 = 4;
+/// Bump whenever what goes into a key, or how it's fed to the hash, changes
+const KEY_FORMAT: u32 = 1;
+const INDEX_FILE_NAME: &str = "index.json";
+/// Entries kept; storing another drops the least recently used
+const MAX_ENTRIES: usize = 8;
+
+/// Identifies one processed version of a pack
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    /// Key for a pack directory: its name and every file its manifest reads, by the
+    /// name the manifest gives
+    pub fn for_dir(dir: &Path, variant: &Variant) -> Result<Self, SpriteError> {
+        let manifest = spritesheet::read_manifest(dir)?;
+        let mut files = Vec::new();
+        for name in spritesheet::pack_files(dir, &manifest)? {
+            let path = dir.join(&name);
+            let bytes = fs::read(&path).map_err(|err| SpriteError::Io(path, err))?;
+            files.push((name, bytes));
+        }
+        Ok(Self::for_files(
+            manifest.name.as_deref().unwrap_or(""),
+            files
+                .iter()
+                .map(|(name, bytes)| (name.as_str(), bytes.as_slice())),
+            variant,
+        ))
+    }
+
+    /// Key for a pack given as named file contents
+    pub fn for_files<'a>(
+        pack_name: &str,
+        files: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+        variant: &Variant,
+    ) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        // Length prefixes keep ("ab", "c") and ("a", "bc") apart
+        let mut field = |bytes: &[u8]| {
+            hasher.update(&(bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        };
+        field(&KEY_FORMAT.to_le_bytes());
+        field(&CACHE_FORMAT.to_le_bytes());
+        field(env!("CARGO_PKG_VERSION").as_bytes());
+        field(machine::DEFAULT_STATES.as_bytes());
+        field(pack_name.as_bytes());
+        for (name, bytes) in files {
+            field(name.as_bytes());
+            field(bytes);
+        }
+        // Field by field: `Debug` output isn't a stable format
+        let Variant { name, recolor } = variant;
+        match name {
+            Some(name) => {
+                field(&[1]);
+                field(name.as_bytes());
+            }
+            None => field(&[0]),
+        }
+        field(&recolor.hue.to_le_bytes());
+        field(&recolor.saturation.to_le_bytes());
+        field(&recolor.brightness.to_le_bytes());
+        field(&(recolor.palette.len() as u64).to_le_bytes());
+        for (from, to) in &recolor.palette {
+            field(&from.0);
+            field(&to.0);
+        }
+        field(&[recolor.tolerance]);
+        Self(hasher.finalize().to_hex().to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// What's in a cache entry besides the frames
+#[derive(Serialize, Deserialize)]
+struct Index {
+    format: u32,
+    name: String,
+    facing: Facing,
+    machine: Machine,
+    animations: Vec<IndexAnimation>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexAnimation {
+    name: String,
+    frames: usize,
+    frame_durations: Vec<Duration>,
+    playback: Playback,
+    weights: Vec<u32>,
+}
+
+/// A directory of processed packs
+#[derive(Debug, Clone)]
+pub struct FrameCache {
+    dir: PathBuf,
+}
+
+impl FrameCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The per-user cache (`None` without a cache directory)
+    pub fn user() -> Option<Self> {
+        Some(Self::new(
+            dirs::cache_dir()?.join("tapwatch").join("frames"),
+        ))
+    }
+
+    fn entry_dir(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(key.as_str())
+    }
+
+    /// A previously stored pack; `None` if there is none or it can't be read
+    pub fn load(&self, key: &CacheKey) -> Option<SpritePack> {
+        let dir = self.entry_dir(key);
+        let index: Index =
+            serde_json::from_slice(&fs::read(dir.join(INDEX_FILE_NAME)).ok()?).ok()?;
+        if index.format != CACHE_FORMAT {
+            return None;
+        }
+
+        let mut animations = HashMap::new();
+        for (i, entry) in index.animations.into_iter().enumerate() {
+            let frames = (0..entry.frames)
+                .into_par_iter()
+                .map(|frame| {
+                    let png = fs::read(dir.join(frame_file_name(i, frame))).ok()?;
+                    let image = image::load_from_memory(&png).ok()?.to_rgba8();
+                    Some(Frame::with_png(image, png))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            animations.insert(
+                entry.name,
+                Animation {
+                    frames,
+                    frame_durations: entry.frame_durations,
+                    playback: entry.playback,
+                    weights: entry.weights,
+                },
+            );
+        }
+        // Mark the entry as used, so pruning keeps it
+        let _ = fs::File::options()
+            .write(true)
+            .open(dir.join(INDEX_FILE_NAME))
+            .and_then(|index| index.set_modified(SystemTime::now()));
+        Some(SpritePack::from_parts(
+            index.name,
+            index.facing,
+            index.machine,
+            animations,
+        ))
+    }
+
+    /// Keep a processed pack for next time
+    /// The entry is written aside and renamed into place, so a reader never sees
+    /// half of it
+    pub fn store(&self, key: &CacheKey, pack: &SpritePack) -> io::Result<()> {
+        let target = self.entry_dir(key);
+        let staging = self
+            .dir
+            .join(format!("{}.tmp-{}", key.as_str(), std::process::id()));
+        let _ = fs::remove_dir_all(&staging);
+        fs::create_dir_all(&staging)?;
+
+        let result = self.write_entry(&staging, pack).and_then(|()| {
+            // An entry that couldn't be loaded may be in the way
+            let _ = fs::remove_dir_all(&target);
+            fs::rename(&staging, &target)
+        });
+        if result.is_err() {
+            let _ = fs::remove_dir_all(&staging);
+        } else {
+            // Old entries only cost disk space, so failing to remove them is no error
+            let _ = self.prune(key);
+        }
+        result
+    }
+
+    /// Remove the least recently used entries beyond `MAX_ENTRIES`, sparing `keep`
+    fn prune(&self, keep: &CacheKey) -> io::Result<()> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            // Staging directories belong to whoever is writing them
+            if name == keep.as_str() || name.to_string_lossy().contains(".tmp-") {
+                continue;
+            }
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let used = fs::metadata(entry.path().join(INDEX_FILE_NAME))
+                .and_then(|index| index.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((used, entry.path()));
+        }
+        entries.sort_by_key(|(used, _)| std::cmp::Reverse(*used));
+        for (_, dir) in entries.into_iter().skip(MAX_ENTRIES - 1) {
+            fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    fn write_entry(&self, dir: &Path, pack: &SpritePack) -> io::Result<()> {
+        let mut animations: Vec<(&str, &Animation)> = pack.animations().collect();
+        animations.sort_by_key(|(name, _)| *name);
+
+        let mut index = Index {
+            format: CACHE_FORMAT,
+            name: pack.name.clone(),
+            facing: pack.facing,
+            machine: pack.machine.clone(),
+            animations: Vec::new(),
+        };
+        for (i, (name, animation)) in animations.into_iter().enumerate() {
+            animation
+                .frames
+                .par_iter()
+                .enumerate()
+                .try_for_each(|(frame, image)| {
+                    let png = image.png().map_err(io::Error::other)?;
+                    fs::write(dir.join(frame_file_name(i, frame)), png)
+                })?;
+            index.animations.push(IndexAnimation {
+                name: name.to_string(),
+                frames: animation.frames.len(),
+                frame_durations: animation.frame_durations.clone(),
+                playback: animation.playback,
+                weights: animation.weights.clone(),
+            });
+        }
+        let json = serde_json::to_vec(&index).map_err(io::Error::other)?;
+        fs::write(dir.join(INDEX_FILE_NAME), json)
+    }
+}
+
+/// Animations are numbered rather than named so any name makes a valid file name
+fn frame_file_name(animation: usize, frame: usize) -> String {
+    format!("{}-{}.png", animation, frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::manifest::{Color, Recolor};
+    use crate::app::spritesheet::{IDLE, TYPING};
+    use crate::app::testing::write_pack;
+    use tempfile::tempdir;
+
+    #[test]
+    fn key_follows_every_input() {
+        let plain = Variant::default();
+        let key = |sheet: &[u8], manifest: &[u8], variant: &Variant| {
+            CacheKey::for_files(
+                "dog",
+                [("sheet.png", sheet), ("manifest.toml", manifest)],
+                variant,
+            )
+        };
+        let base = key(b"pixels", b"frame_ms = 1", &plain);
+
+        assert_eq!(base, key(b"pixels", b"frame_ms = 1", &plain));
+        assert_ne!(base, key(b"pixelz", b"frame_ms = 1", &plain));
+        assert_ne!(base, key(b"pixels", b"frame_ms = 2", &plain));
+        let mint = Variant {
+            name: Some("mint".to_string()),
+            recolor: Recolor::default(),
+        };
+        assert_ne!(base, key(b"pixels", b"frame_ms = 1", &mint));
+        let recolored = |recolor: Recolor| Variant {
+            name: None,
+            recolor,
+        };
+        let tolerant = recolored(Recolor {
+            tolerance: 40,
+            ..Recolor::default()
+        });
+        assert_ne!(base, key(b"pixels", b"frame_ms = 1", &tolerant));
+        let swapped = recolored(Recolor {
+            palette: [(Color([1, 2, 3]), Color([4, 5, 6]))].into(),
+            ..Recolor::default()
+        });
+        assert_ne!(base, key(b"pixels", b"frame_ms = 1", &swapped));
+        // Moving bytes between files is a different pack too
+        assert_ne!(base, key(b"pixelsf", b"rame_ms = 1", &plain));
+    }
+
+    #[test]
+    fn dir_key_covers_the_files_the_manifest_reads() {
+        let root = tempdir().unwrap();
+        let dir = root.path().join("pack");
+        fs::create_dir(&dir).unwrap();
+        let extra = "[animations.sleep]\nfiles = [\"../shared.png\"]\nframe_ms = 100";
+        write_pack(&dir, [200, 100, 50], extra);
+        fs::copy(dir.join("frame.png"), root.path().join("shared.png")).unwrap();
+        let variant = Variant::default();
+        let before = CacheKey::for_dir(&dir, &variant).unwrap();
+
+        // Files the manifest doesn't name don't matter
+        fs::write(dir.join(".manifest.toml.swp"), "editor noise").unwrap();
+        fs::write(dir.join("notes.txt"), "todo").unwrap();
+        assert_eq!(before, CacheKey::for_dir(&dir, &variant).unwrap());
+
+        // Files it names outside the pack directory do
+        image::RgbaImage::new(2, 2)
+            .save(root.path().join("shared.png"))
+            .unwrap();
+        let shared = CacheKey::for_dir(&dir, &variant).unwrap();
+        assert_ne!(before, shared);
+
+        write_pack(&dir, [10, 100, 50], extra);
+        assert_ne!(shared, CacheKey::for_dir(&dir, &variant).unwrap());
+
+        fs::remove_file(root.path().join("shared.png")).unwrap();
+        assert!(matches!(
+            CacheKey::for_dir(&dir, &variant),
+            Err(SpriteError::Io(..))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_pack_dir_is_cached() {
+        let root = tempdir().unwrap();
+        let cache = FrameCache::new(root.path().join("cache"));
+        let dir = root.path().join("pack");
+        fs::create_dir(&dir).unwrap();
+        write_pack(&dir, [200, 100, 50], "");
+        let link = root.path().join("link");
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+
+        let variant = Variant::default();
+        let pack = SpritePack::load_with(&link, &variant, Some(&cache)).unwrap();
+        let key = CacheKey::for_dir(&link, &variant).unwrap();
+        assert!(cache.load(&key).is_some());
+        let direct = SpritePack::load_with(&dir, &variant, None).unwrap();
+        assert_eq!(pack.frame(IDLE, 0), direct.frame(IDLE, 0));
+    }
+
+    #[test]
+    fn stored_pack_comes_back_unchanged() {
+        let dir = tempdir().unwrap();
+        let cache = FrameCache::new(dir.path());
+        let variant = Variant::default();
+        let pack = SpritePack::embedded_with(&variant, None).unwrap();
+        let key = CacheKey::for_files("dog", [("a", &b"b"[..])], &variant);
+
+        assert!(cache.load(&key).is_none());
+        cache.store(&key, &pack).unwrap();
+        let cached = cache.load(&key).expect("entry was just stored");
+
+        assert_eq!(cached.name, pack.name);
+        assert_eq!(cached.facing, pack.facing);
+        assert_eq!(cached.machine, pack.machine);
+        for name in [IDLE, TYPING] {
+            let (a, b) = (
+                pack.animation(name).unwrap(),
+                cached.animation(name).unwrap(),
+            );
+            assert_eq!(a.frames, b.frames);
+            assert_eq!(a.frame_durations, b.frame_durations);
+            assert_eq!(a.playback, b.playback);
+        }
+    }
+
+    #[test]
+    fn changed_pack_is_processed_again() {
+        let dir = tempdir().unwrap();
+        let entries = tempdir().unwrap();
+        let cache = FrameCache::new(entries.path());
+        let variant = Variant::default();
+        write_pack(dir.path(), [200, 100, 50], "");
+
+        let first = SpritePack::load_with(dir.path(), &variant, Some(&cache)).unwrap();
+        let key = CacheKey::for_dir(dir.path(), &variant).unwrap();
+        assert!(cache.entry_dir(&key).join(INDEX_FILE_NAME).exists());
+
+        // Unchanged: served from the cache (proven by tampering with the entry)
+        image::RgbaImage::new(1, 1)
+            .save(cache.entry_dir(&key).join(frame_file_name(0, 0)))
+            .unwrap();
+        let again = SpritePack::load_with(dir.path(), &variant, Some(&cache)).unwrap();
+        assert_eq!(again.frame(IDLE, 0).unwrap().dimensions(), (1, 1));
+        assert_eq!(
+            again.animation(IDLE).unwrap().frame_durations,
+            first.animation(IDLE).unwrap().frame_durations
+        );
+
+        // Changed image: new key, fresh frames
+        write_pack(dir.path(), [10, 100, 50], "");
+        let changed = SpritePack::load_with(dir.path(), &variant, Some(&cache)).unwrap();
+        let uncached = SpritePack::load_with(dir.path(), &variant, None).unwrap();
+        assert_eq!(changed.frame(IDLE, 0), uncached.frame(IDLE, 0));
+        assert_ne!(changed.frame(IDLE, 0), first.frame(IDLE, 0));
+
+        // Another variant of the same files gets its own entry
+        let ghost = Variant {
+            recolor: Recolor {
+                saturation: 0.0,
+                ..Recolor::default()
+            },
+            ..Variant::default()
+        };
+        let gray = SpritePack::load_with(dir.path(), &ghost, Some(&cache)).unwrap();
+        assert_ne!(gray.frame(IDLE, 0), changed.frame(IDLE, 0));
+    }
+
+    #[test]
+    fn broken_entry_is_ignored() {
+        let dir = tempdir().unwrap();
+        let entries = tempdir().unwrap();
+        let cache = FrameCache::new(entries.path());
+        let variant = Variant::default();
+        write_pack(dir.path(), [200, 100, 50], "");
+        let fresh = SpritePack::load_with(dir.path(), &variant, Some(&cache)).unwrap();
+        let key = CacheKey::for_dir(dir.path(), &variant).unwrap();
+
+        fs::remove_file(cache.entry_dir(&key).join(frame_file_name(0, 0))).unwrap();
+        assert!(cache.load(&key).is_none());
+        let reloaded = SpritePack::load_with(dir.path(), &variant, Some(&cache)).unwrap();
+        assert_eq!(reloaded.frame(IDLE, 0), fresh.frame(IDLE, 0));
+
+        fs::write(cache.entry_dir(&key).join(INDEX_FILE_NAME), "{ not json").unwrap();
+        assert!(cache.load(&key).is_none());
+    }
+
+    #[test]
+    fn least_recently_used_entries_are_pruned() {
+        let dir = tempdir().unwrap();
+        let cache = FrameCache::new(dir.path());
+        let variant = Variant::default();
+        let pack = SpritePack::empty();
+        let keys: Vec<CacheKey> = (0..MAX_ENTRIES + 2)
+            .map(|i| CacheKey::for_files("", [("n", &i.to_le_bytes()[..])], &variant))
+            .collect();
+
+        // Each used an hour after the one before, long ago (mtimes written back to
+        // back could tie)
+        for (i, key) in keys[..MAX_ENTRIES].iter().enumerate() {
+            cache.store(key, &pack).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(cache.entry_dir(key).join(INDEX_FILE_NAME))
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(3600 * (i as u64 + 1)))
+                .unwrap();
+        }
+        // Using the oldest entry keeps it around
+        assert!(cache.load(&keys[0]).is_some());
+        for key in &keys[MAX_ENTRIES..] {
+            cache.store(key, &pack).unwrap();
+        }
+
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), MAX_ENTRIES);
+        assert!(cache.load(&keys[0]).is_some());
+        assert!(cache.load(&keys[1]).is_none());
+        assert!(cache.load(&keys[2]).is_none());
+        assert!(cache.load(&keys[MAX_ENTRIES + 1]).is_some());
+    }
+}
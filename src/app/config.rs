@@ -0,0 +1,149 @@
+//! User configuration, read from `$XDG_CONFIG_HOME/tapwatch/config.toml`
+//!
+//! Every field is optional; a missing file means all defaults.
+
+use super::error::ConfigError;
+use super::graphics::GraphicsBackend;
+use super::manifest::Recolor;
+use super::mistakes::Sensitivity;
+use super::mood::MoodRates;
+use super::recolor::Variant;
+use super::state::DEFAULT_SLEEP_AFTER;
+use super::tempo::Tempo;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Which graphics backend to draw the sprite with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphicsSetting {
+    /// Pick based on the terminal we're running in
+    Auto,
+    /// Always use iTerm2 inline images
+    #[default]
+    Iterm2,
+    /// Always use the kitty graphics protocol
+    Kitty,
+    /// Always use Sixel graphics
+    Sixel,
+    /// Draw with colored half-block characters
+    Cells,
+    /// Never draw the sprite
+    None,
+}
+
+impl GraphicsSetting {
+    pub fn resolve(self) -> GraphicsBackend {
+        match self {
+            GraphicsSetting::Auto => GraphicsBackend::detect(),
+            GraphicsSetting::Iterm2 => GraphicsBackend::Iterm2,
+            GraphicsSetting::Kitty => GraphicsBackend::Kitty,
+            GraphicsSetting::Sixel => GraphicsBackend::Sixel,
+            GraphicsSetting::Cells => GraphicsBackend::Cells,
+            GraphicsSetting::None => GraphicsBackend::Disabled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Graphics backend for the sprite
+    pub graphics: GraphicsSetting,
+    /// Capture keys system-wide (off = only keys typed into the TapWatch window)
+    pub global_capture: bool,
+    /// Sprite pack directory (the built-in dog when unset)
+    pub sprite_pack: Option<PathBuf>,
+    /// Named color variant of the sprite pack
+    pub variant: Option<String>,
+    /// Color changes on top of the variant
+    pub recolor: Recolor,
+    /// Turn the dog toward the mouse pointer (captures the mouse, so text
+    /// selection in the TapWatch window needs Shift or Option)
+    pub follow_mouse: bool,
+    /// Seconds without keys before the dog falls asleep (0 = never)
+    pub sleep_after_secs: u64,
+    /// How far typing speed can speed up or slow down the typing animation
+    pub tempo: Tempo,
+    /// How readily error-heavy typing makes the dog confused ("off" to never)
+    pub mistake_sensitivity: Sensitivity,
+    /// How fast the dog's mood changes, per hour
+    pub mood: MoodRates,
+    /// Follow a cursor in the typed text: arrows, Home, End and deletes edit it like
+    /// an editor would instead of showing the key's name
+    pub edit_cursor: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            graphics: GraphicsSetting::default(),
+            global_capture: true,
+            sprite_pack: None,
+            variant: None,
+            recolor: Recolor::default(),
+            follow_mouse: false,
+            sleep_after_secs: DEFAULT_SLEEP_AFTER.as_secs(),
+            tempo: Tempo::default(),
+            mistake_sensitivity: Sensitivity::default(),
+            mood: MoodRates::default(),
+            edit_cursor: false,
+        }
+    }
+}
+
+impl Config {
+    /// Default location of the config file
+    pub fn path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("tapwatch").join(CONFIG_FILE_NAME))
+    }
+
+    /// How to recolor the sprite pack
+    pub fn variant(&self) -> Variant {
+        Variant {
+            name: self.variant.clone(),
+            recolor: self.recolor.clone(),
+        }
+    }
+
+    /// How long without keys before falling asleep (`None`: never)
+    pub fn sleep_after(&self) -> Option<Duration> {
+        (self.sleep_after_secs > 0).then(|| Duration::from_secs(self.sleep_after_secs))
+    }
+
+    /// Load the config from the default location (defaults if there is no file)
+    pub fn load() -> Result<Self, ConfigError> {
+        match Self::path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load the config from `path` (defaults if the file doesn't exist)
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(ConfigError::Read(path.to_path_buf(), err)),
+        };
+        let config: Self =
+            toml::from_str(&text).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))?;
+        let invalid = |section: &str, reason: String| {
+            ConfigError::Invalid(path.to_path_buf(), format!("{}: {}", section, reason))
+        };
+        config
+            .tempo
+            .validate()
+            .map_err(|reason| invalid("tempo", reason))?;
+        config
+            .mood
+            .validate()
+            .map_err(|reason| invalid("mood", reason))?;
+        Ok(config)
+    }
+}
@@ -0,0 +1,520 @@
+//! `tapwatch doctor` - checks the environment and suggests fixes
+//!
+//! Each check is independent and never fails hard; the report lists what works,
+//! what might not, and what definitely won't.
+
+use super::config::{Config, GraphicsSetting};
+use super::error::ConfigError;
+use super::graphics::GraphicsBackend;
+use super::spritesheet::{self, SpritePack};
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Outcome of one check
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What the user can do about a warning or failure
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// All check results, printable as a report
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn has_failures(&self) -> bool {
+        self.checks.iter().any(|c| c.status == Status::Fail)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "TapWatch doctor\n")?;
+        for check in &self.checks {
+            let mark = match check.status {
+                Status::Ok => "✓",
+                Status::Warn => "!",
+                Status::Fail => "✗",
+            };
+            writeln!(f, "{} {}: {}", mark, check.name, check.detail)?;
+            if let Some(fix) = &check.fix {
+                writeln!(f, "    → {}", fix)?;
+            }
+        }
+        let warnings = self
+            .checks
+            .iter()
+            .filter(|c| c.status == Status::Warn)
+            .count();
+        let failures = self
+            .checks
+            .iter()
+            .filter(|c| c.status == Status::Fail)
+            .count();
+        write!(f, "\n{} warning(s), {} failure(s)", warnings, failures)
+    }
+}
+
+/// Run every check; `sprite_pack` and `variant` come from the command line and
+/// override the config, as they do for the dog
+pub fn run(sprite_pack: Option<&Path>, variant: Option<&str>) -> Report {
+    // Loaded once; if it's broken, the checks that read it are skipped rather
+    // than run against defaults the dog wouldn't use
+    let path = Config::path();
+    let config = match &path {
+        Some(path) => Config::load_from(path),
+        None => Ok(Config::default()),
+    };
+    let mut checks = Vec::new();
+    checks.extend(check_global_input());
+    checks.push(match &config {
+        Ok(config) => check_graphics(config.graphics),
+        Err(_) => unchecked("terminal graphics"),
+    });
+    checks.push(check_cell_size());
+    if let Some(check) = check_tmux() {
+        checks.push(check);
+    }
+    checks.push(check_window_positioning());
+    checks.extend(check_sprites(sprite_pack, variant, config.as_ref().ok()));
+    checks.push(check_config(path.as_deref(), &config));
+    Report { checks }
+}
+
+/// Stands in for a check that needs the config when the config didn't load
+fn unchecked(name: &'static str) -> Check {
+    Check::warn(
+        name,
+        "not checked, the config didn't load",
+        "fix the config file first",
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn check_global_input() -> Vec<Check> {
+    // There's no way to query the permission without prompting; a missing grant
+    // makes rdev silently receive nothing rather than fail
+    vec![Check::warn(
+        "global input",
+        "needs Accessibility permission (cannot be verified without prompting)",
+        "System Settings → Privacy & Security → Accessibility → enable your terminal app",
+    )]
+}
+
+#[cfg(target_os = "linux")]
+fn check_global_input() -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    // rdev listens through the X11 RECORD extension
+    let display = env::var("DISPLAY").unwrap_or_default();
+    let wayland = env::var("WAYLAND_DISPLAY").unwrap_or_default();
+    checks.push(match (display.is_empty(), wayland.is_empty()) {
+        (true, true) => Check::fail(
+            "global input",
+            "no X11 display (DISPLAY is unset)",
+            "run inside an X11 session, or set global_capture = false in the config",
+        ),
+        (true, false) => Check::fail(
+            "global input",
+            "Wayland session without XWayland (DISPLAY is unset)",
+            "enable XWayland, or set global_capture = false in the config",
+        ),
+        (false, false) => Check::warn(
+            "global input",
+            format!(
+                "X11 via XWayland (DISPLAY={}) - only keys typed into X11 apps are seen",
+                display
+            ),
+            "run an X11 session for system-wide capture",
+        ),
+        (false, true) => Check::ok("global input", format!("X11 (DISPLAY={})", display)),
+    });
+
+    // Raw evdev access, needed by any non-X11 capture path
+    let devices = std::fs::read_dir("/dev/input")
+        .map(|dir| {
+            dir.flatten()
+                .map(|entry| entry.path())
+                .filter(|p| {
+                    p.file_name()
+                        .is_some_and(|n| n.to_string_lossy().starts_with("event"))
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let readable = devices
+        .iter()
+        .filter(|p| std::fs::File::open(p).is_ok())
+        .count();
+    let in_input_group = command_output("id", &["-Gn"])
+        .is_some_and(|groups| groups.split_whitespace().any(|g| g == "input"));
+    checks.push(if devices.is_empty() {
+        Check::warn(
+            "evdev",
+            "no /dev/input/event* devices found",
+            "check that udev is running",
+        )
+    } else if readable == 0 {
+        Check::warn(
+            "evdev",
+            format!(
+                "permission denied on /dev/input ({} devices, input group: {})",
+                devices.len(),
+                if in_input_group { "yes" } else { "no" }
+            ),
+            "sudo usermod -aG input $USER, then log out and back in",
+        )
+    } else {
+        Check::ok(
+            "evdev",
+            format!("{} of {} input devices readable", readable, devices.len()),
+        )
+    });
+
+    checks
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn check_global_input() -> Vec<Check> {
+    vec![Check::ok("global input", "low-level keyboard hook")]
+}
+
+fn check_graphics(setting: GraphicsSetting) -> Check {
+    let term_program = env::var("TERM_PROGRAM")
+        .or_else(|_| env::var("TERM"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    graphics_check(setting, GraphicsBackend::detect(), &term_program)
+}
+
+/// What the dog draws with under `setting`, measured against what the terminal
+/// looks like it speaks
+fn graphics_check(setting: GraphicsSetting, detected: GraphicsBackend, term: &str) -> Check {
+    let resolved = match setting {
+        GraphicsSetting::Auto => detected,
+        setting => setting.resolve(),
+    };
+    if resolved != detected && setting != GraphicsSetting::None {
+        return Check::warn(
+            "terminal graphics",
+            format!(
+                "the config draws with {}, but {} looks like it wants {}",
+                describe(resolved),
+                term,
+                describe(detected)
+            ),
+            "set graphics = \"auto\" in the config",
+        );
+    }
+    match resolved {
+        GraphicsBackend::Iterm2 => Check::ok(
+            "terminal graphics",
+            format!("inline images supported ({})", term),
+        ),
+        GraphicsBackend::Kitty => Check::ok(
+            "terminal graphics",
+            format!("kitty graphics protocol supported ({})", term),
+        ),
+        GraphicsBackend::Sixel => Check::ok(
+            "terminal graphics",
+            format!("Sixel graphics supported ({})", term),
+        ),
+        GraphicsBackend::Cells => Check::warn(
+            "terminal graphics",
+            format!(
+                "{} has no known image protocol; drawing with colored cells",
+                term
+            ),
+            "use kitty, Ghostty, iTerm2, WezTerm or a Sixel terminal for a sharper dog",
+        ),
+        GraphicsBackend::Disabled if setting == GraphicsSetting::None => Check::ok(
+            "terminal graphics",
+            "the sprite is turned off in the config (graphics = \"none\")",
+        ),
+        GraphicsBackend::Disabled => Check::warn(
+            "terminal graphics",
+            format!("{} supports neither an image protocol nor true color", term),
+            "use kitty, Ghostty, iTerm2 or WezTerm to see the dog; text still works everywhere",
+        ),
+    }
+}
+
+fn describe(backend: GraphicsBackend) -> &'static str {
+    match backend {
+        GraphicsBackend::Iterm2 => "iTerm2 inline images",
+        GraphicsBackend::Kitty => "the kitty graphics protocol",
+        GraphicsBackend::Sixel => "Sixel graphics",
+        GraphicsBackend::Cells => "colored cells",
+        GraphicsBackend::Disabled => "no sprite",
+    }
+}
+
+fn check_cell_size() -> Check {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.columns > 0 && size.rows > 0 => Check::ok(
+            "cell size",
+            format!(
+                "{}x{} px per cell ({}x{} cells)",
+                size.width / size.columns,
+                size.height / size.rows,
+                size.columns,
+                size.rows
+            ),
+        ),
+        Ok(size) => Check::warn(
+            "cell size",
+            format!(
+                "terminal doesn't report pixel size ({}x{} cells)",
+                size.columns, size.rows
+            ),
+            "sprite scaling falls back to cell units; this is usually fine",
+        ),
+        Err(err) => Check::warn(
+            "cell size",
+            format!("cannot query terminal size: {}", err),
+            "run doctor from an interactive terminal",
+        ),
+    }
+}
+
+fn check_tmux() -> Option<Check> {
+    env::var_os("TMUX")?;
+    let passthrough = command_output("tmux", &["show", "-gv", "allow-passthrough"]);
+    Some(match passthrough.as_deref().map(str::trim) {
+        Some("on") | Some("all") => Check::ok("tmux passthrough", "allow-passthrough is on"),
+        Some(value) => Check::fail(
+            "tmux passthrough",
+            format!(
+                "allow-passthrough is {:?} - images won't reach the terminal",
+                value
+            ),
+            "add `set -g allow-passthrough on` to ~/.tmux.conf",
+        ),
+        None => Check::warn(
+            "tmux passthrough",
+            "cannot read tmux options",
+            "make sure `tmux` is on PATH",
+        ),
+    })
+}
+
+fn check_window_positioning() -> Check {
+    if !cfg!(target_os = "macos") {
+        return Check::warn(
+            "window positioning",
+            "only supported on macOS",
+            "arrow keys won't move the window on this platform",
+        );
+    }
+    if !on_path("osascript") {
+        return Check::fail(
+            "window positioning",
+            "osascript not found",
+            "restore /usr/bin/osascript",
+        );
+    }
+    match env::var("TERM_PROGRAM").as_deref() {
+        Ok("iTerm.app") => Check::ok("window positioning", "osascript + iTerm2"),
+        _ => Check::warn(
+            "window positioning",
+            "the AppleScript targets iTerm2, which isn't the current terminal",
+            "use iTerm2 to snap the window with the arrow keys",
+        ),
+    }
+}
+
+fn check_sprites(
+    sprite_pack: Option<&Path>,
+    variant: Option<&str>,
+    config: Option<&Config>,
+) -> Vec<Check> {
+    let mut checks = vec![if spritesheet::is_loaded() {
+        Check::ok("sprite sheet", "the built-in dog loads")
+    } else {
+        Check::fail(
+            "sprite sheet",
+            "the built-in dog failed to load",
+            "reinstall tapwatch",
+        )
+    }];
+
+    let Some(config) = config else {
+        checks.push(unchecked("sprite pack"));
+        return checks;
+    };
+    let mut pack_variant = config.variant();
+    if let Some(name) = variant {
+        pack_variant.name = Some(name.to_string());
+    }
+    let fix = if sprite_pack.is_some() {
+        "fix the pack or drop --sprite-pack"
+    } else {
+        "fix the pack or remove sprite_pack from the config"
+    };
+    if let Some(dir) = sprite_pack.or(config.sprite_pack.as_deref()) {
+        checks.push(match SpritePack::load(dir, &pack_variant) {
+            Ok(pack) => Check::ok("sprite pack", format!("{} ({})", pack.name, dir.display())),
+            Err(err) => Check::fail("sprite pack", err.to_string(), fix),
+        });
+    }
+    checks
+}
+
+fn check_config(path: Option<&Path>, config: &Result<Config, ConfigError>) -> Check {
+    let Some(path) = path else {
+        return Check::ok("config", "no config directory, using defaults");
+    };
+    match config {
+        Err(err) => Check::fail("config", err.to_string(), "fix or remove the config file"),
+        Ok(_) if !path.exists() => Check::ok(
+            "config",
+            format!("{} not present, using defaults", path.display()),
+        ),
+        Ok(_) => Check::ok("config", path.display().to_string()),
+    }
+}
+
+/// Run a command and return its stdout if it succeeded
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|dir| Path::new(&dir).join(program).is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::testing::write_pack;
+    use tempfile::tempdir;
+
+    /// How the sprite pack check went, if there was a pack to check
+    fn pack_status(
+        sprite_pack: Option<&Path>,
+        variant: Option<&str>,
+        config: Option<&Config>,
+    ) -> Option<Status> {
+        check_sprites(sprite_pack, variant, config)
+            .into_iter()
+            .find(|check| check.name == "sprite pack")
+            .map(|check| check.status)
+    }
+
+    #[test]
+    fn graphics_are_checked_as_the_config_resolves_them() {
+        let check = |setting, detected| graphics_check(setting, detected, "kitty").status;
+
+        assert_eq!(
+            check(GraphicsSetting::Auto, GraphicsBackend::Kitty),
+            Status::Ok
+        );
+        assert_eq!(
+            check(GraphicsSetting::Kitty, GraphicsBackend::Kitty),
+            Status::Ok
+        );
+        // The default sends iTerm2 images, which kitty doesn't show
+        let mismatch = graphics_check(GraphicsSetting::Iterm2, GraphicsBackend::Kitty, "kitty");
+        assert_eq!(mismatch.status, Status::Warn);
+        assert!(mismatch.detail.contains("iTerm2"), "{}", mismatch.detail);
+        assert!(mismatch.fix.unwrap().contains("auto"));
+        // Turning the sprite off is a choice, not a mistake
+        assert_eq!(
+            check(GraphicsSetting::None, GraphicsBackend::Kitty),
+            Status::Ok
+        );
+    }
+
+    #[test]
+    fn the_pack_the_dog_would_use_is_checked() {
+        let dir = tempdir().unwrap();
+        write_pack(dir.path(), [200, 0, 0], "[variants.blue]\nhue = 120\n");
+        let config = Config {
+            sprite_pack: Some(dir.path().join("gone")),
+            ..Config::default()
+        };
+
+        assert_eq!(pack_status(None, None, Some(&Config::default())), None);
+        assert_eq!(pack_status(None, None, Some(&config)), Some(Status::Fail));
+        // --sprite-pack and --variant win over the config
+        assert_eq!(
+            pack_status(Some(dir.path()), None, Some(&config)),
+            Some(Status::Ok)
+        );
+        assert_eq!(
+            pack_status(Some(dir.path()), Some("blue"), Some(&config)),
+            Some(Status::Ok)
+        );
+        assert_eq!(
+            pack_status(Some(dir.path()), Some("red"), Some(&config)),
+            Some(Status::Fail)
+        );
+        // A config that didn't load leaves the pack unchecked
+        assert_eq!(
+            pack_status(Some(dir.path()), None, None),
+            Some(Status::Warn)
+        );
+    }
+
+    #[test]
+    fn broken_config_fails_the_config_check() {
+        let path = Path::new("/tmp/tapwatch/config.toml");
+        let err = ConfigError::Invalid(path.to_path_buf(), "tempo: bpm is 0".to_string());
+
+        let check = check_config(Some(path), &Err(err));
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.detail.contains("tempo"), "{}", check.detail);
+        assert_eq!(
+            check_config(None, &Ok(Config::default())).status,
+            Status::Ok
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! Short one-shot reactions played over whatever the dog is doing
+//!
+//! Anything can queue an emote with a priority: keys like Enter or a save, or another
+//! program sending a signal when a build passes. One plays at a time and takes over
+//! from the current state's animation until it's done, then the state carries on.
+//! A higher priority cuts a running emote short, and a busy queue drops low-priority
+//! emotes rather than letting reactions pile up behind it.
+//!
+//! A pack draws an emote with the animation of the same name. Without one the dog's
+//! own frames are moved around instead (a hop, a spin, a wiggle), or a bubble is
+//! drawn beside them.
+
+use super::machine::Overlay;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Emotes that can wait behind the one playing
+const CAPACITY: usize = 3;
+/// How long an emote lasts when the pack has no frames for it
+pub const FALLBACK_DURATION: Duration = Duration::from_millis(1200);
+/// How often a spin looks the other way
+const SPIN_STEP: Duration = Duration::from_millis(150);
+/// How often a wag shifts the sprite
+const WAG_STEP: Duration = Duration::from_millis(100);
+/// Rows above the ground through a jump, in equal steps
+const JUMP_ARC: [i16; 6] = [0, 1, 2, 2, 1, 0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emote {
+    Jump,
+    Spin,
+    Bark,
+    TailWag,
+    Hearts,
+}
+
+/// How the sprite is moved for an emote the pack has no frames for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Motion {
+    /// Columns right and rows down
+    pub shift: (i16, i16),
+    /// Looking the other way
+    pub turned: bool,
+}
+
+impl Emote {
+    /// The pack animation it's drawn with
+    pub fn animation(self) -> &'static str {
+        match self {
+            Emote::Jump => "jump",
+            Emote::Spin => "spin",
+            Emote::Bark => "bark",
+            Emote::TailWag => "tail-wag",
+            Emote::Hearts => "hearts",
+        }
+    }
+
+    /// Without the pack's frames: how the sprite moves `elapsed` into an emote of
+    /// `length`
+    pub fn motion(self, elapsed: Duration, length: Duration) -> Motion {
+        let steps = |step: Duration| (elapsed.as_millis() / step.as_millis()) as usize;
+        match self {
+            Emote::Jump => {
+                let part = elapsed.as_secs_f32() / length.as_secs_f32().max(f32::EPSILON);
+                let step = ((part * JUMP_ARC.len() as f32) as usize).min(JUMP_ARC.len() - 1);
+                Motion {
+                    shift: (0, -JUMP_ARC[step]),
+                    turned: false,
+                }
+            }
+            Emote::Spin => Motion {
+                shift: (0, 0),
+                turned: steps(SPIN_STEP) % 2 == 1,
+            },
+            Emote::TailWag => Motion {
+                shift: (if steps(WAG_STEP) % 2 == 1 { 1 } else { 0 }, 0),
+                turned: false,
+            },
+            Emote::Bark | Emote::Hearts => Motion::default(),
+        }
+    }
+
+    /// Without the pack's frames: what's drawn beside the sprite
+    pub fn overlay(self) -> Option<Overlay> {
+        match self {
+            Emote::Bark => Some(Overlay::Woof),
+            Emote::Hearts => Some(Overlay::Hearts),
+            Emote::Jump | Emote::Spin | Emote::TailWag => None,
+        }
+    }
+}
+
+/// How much an emote matters next to others
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Only played when nothing else is (Enter)
+    Low,
+    Normal,
+    /// Asked for from outside (a build finishing)
+    High,
+}
+
+/// The emote on screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Playing {
+    pub emote: Emote,
+    pub priority: Priority,
+    pub since: Instant,
+    pub until: Instant,
+}
+
+/// What became of a queued emote
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Queued {
+    /// Playing straight away
+    Playing,
+    /// Waiting its turn
+    Waiting,
+    /// Too busy for it
+    Dropped,
+}
+
+/// Emotes in the order they'll play, highest priority first
+#[derive(Debug, Clone, Default)]
+pub struct EmoteQueue {
+    playing: Option<Playing>,
+    waiting: VecDeque<(Emote, Priority, Duration)>,
+}
+
+impl EmoteQueue {
+    /// The emote on screen, as of the last `update`
+    pub fn playing(&self) -> Option<&Playing> {
+        self.playing.as_ref()
+    }
+
+    /// Finish the emote playing if its time is up, starting the next one
+    /// Returns whether a new emote started
+    pub fn update(&mut self, now: Instant) -> bool {
+        let mut started = false;
+        while self.playing.is_some_and(|playing| now >= playing.until) {
+            self.playing = self
+                .waiting
+                .pop_front()
+                .map(|(emote, priority, length)| Playing {
+                    emote,
+                    priority,
+                    since: now,
+                    until: now + length,
+                });
+            started |= self.playing.is_some();
+        }
+        started
+    }
+
+    /// Queue an emote lasting `length` (call `update` first)
+    pub fn push(
+        &mut self,
+        emote: Emote,
+        priority: Priority,
+        length: Duration,
+        now: Instant,
+    ) -> Queued {
+        let start = Playing {
+            emote,
+            priority,
+            since: now,
+            until: now + length,
+        };
+        // Higher priority cuts the emote playing short
+        if self
+            .playing
+            .is_none_or(|playing| priority > playing.priority)
+        {
+            self.playing = Some(start);
+            return Queued::Playing;
+        }
+        if priority == Priority::Low {
+            return Queued::Dropped;
+        }
+        if self.waiting.len() >= CAPACITY {
+            // Make room by dropping the least important, if it's less important
+            if self
+                .waiting
+                .back()
+                .is_some_and(|&(_, last, _)| last < priority)
+            {
+                self.waiting.pop_back();
+            } else {
+                return Queued::Dropped;
+            }
+        }
+        let at = self
+            .waiting
+            .iter()
+            .position(|&(_, waiting, _)| waiting < priority)
+            .unwrap_or(self.waiting.len());
+        self.waiting.insert(at, (emote, priority, length));
+        Queued::Waiting
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn emotes_play_one_after_another_by_priority() {
+        let start = Instant::now();
+        let mut queue = EmoteQueue::default();
+        assert_eq!(
+            queue.push(Emote::Bark, Priority::Normal, SECOND, start),
+            Queued::Playing
+        );
+        assert_eq!(
+            queue.push(Emote::Spin, Priority::Normal, SECOND, start),
+            Queued::Waiting
+        );
+        assert_eq!(
+            queue.push(Emote::Jump, Priority::High, SECOND, start),
+            Queued::Playing
+        );
+        assert_eq!(
+            queue.push(Emote::Hearts, Priority::High, SECOND, start),
+            Queued::Waiting
+        );
+
+        // The jump cut the bark short; the hearts go ahead of the spin
+        let order: Vec<_> = (0..5)
+            .map(|i| {
+                queue.update(start + SECOND * i);
+                queue.playing().map(|p| p.emote)
+            })
+            .collect();
+        assert_eq!(
+            order,
+            [
+                Some(Emote::Jump),
+                Some(Emote::Hearts),
+                Some(Emote::Spin),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn a_busy_queue_drops_the_least_important() {
+        let start = Instant::now();
+        let mut queue = EmoteQueue::default();
+        assert_eq!(
+            queue.push(Emote::TailWag, Priority::Low, SECOND, start),
+            Queued::Playing
+        );
+        assert_eq!(
+            queue.push(Emote::TailWag, Priority::Low, SECOND, start),
+            Queued::Dropped
+        );
+        assert_eq!(
+            queue.push(Emote::Jump, Priority::High, SECOND, start),
+            Queued::Playing
+        );
+        for _ in 0..CAPACITY {
+            assert_eq!(
+                queue.push(Emote::Bark, Priority::Normal, SECOND, start),
+                Queued::Waiting
+            );
+        }
+        assert_eq!(
+            queue.push(Emote::Spin, Priority::Normal, SECOND, start),
+            Queued::Dropped
+        );
+        assert_eq!(
+            queue.push(Emote::Hearts, Priority::High, SECOND, start),
+            Queued::Waiting
+        );
+
+        assert!(queue.update(start + SECOND));
+        assert_eq!(queue.playing().unwrap().emote, Emote::Hearts);
+        assert!(!queue.update(start + SECOND + SECOND / 2));
+        // Once everything has played, even a low-priority emote gets a turn
+        for i in 2..=10 {
+            queue.update(start + SECOND * i);
+        }
+        assert!(queue.playing().is_none());
+        assert_eq!(
+            queue.push(Emote::TailWag, Priority::Low, SECOND, start + SECOND * 10),
+            Queued::Playing
+        );
+    }
+
+    #[test]
+    fn frameless_emotes_move_the_sprite() {
+        let at = |ms| Duration::from_millis(ms);
+        let length = FALLBACK_DURATION;
+        assert_eq!(Emote::Jump.motion(at(0), length).shift, (0, 0));
+        assert_eq!(Emote::Jump.motion(at(600), length).shift, (0, -2));
+        assert_eq!(Emote::Jump.motion(at(1199), length).shift, (0, 0));
+        assert!(!Emote::Spin.motion(at(100), length).turned);
+        assert!(Emote::Spin.motion(at(200), length).turned);
+        assert_eq!(Emote::TailWag.motion(at(150), length).shift, (1, 0));
+        assert_eq!(Emote::Hearts.overlay(), Some(Overlay::Hearts));
+        assert_eq!(Emote::Spin.overlay(), None);
+    }
+}
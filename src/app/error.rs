@@ -0,0 +1,218 @@
+//! Error types for each subsystem that can fail at runtime
+//!
+//! None of these are fatal: the app keeps running and reports them on the status line
+//! and in the log file (see `log`).
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Global key capture (rdev) could not be started
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The OS refused to let us observe input (accessibility / input permissions)
+    PermissionDenied(String),
+    /// No display server to attach to
+    NoDisplay,
+    /// Any other platform error
+    Platform(String),
+}
+
+impl From<rdev::ListenError> for CaptureError {
+    fn from(err: rdev::ListenError) -> Self {
+        use rdev::ListenError;
+        match err {
+            ListenError::EventTapError | ListenError::LoopSourceError => {
+                CaptureError::PermissionDenied(
+                    "grant Accessibility access to your terminal in System Settings".to_string(),
+                )
+            }
+            ListenError::MissingDisplayError => CaptureError::NoDisplay,
+            ListenError::KeyboardError => {
+                CaptureError::PermissionDenied("cannot open the keyboard device".to_string())
+            }
+            other => CaptureError::Platform(format!("{:?}", other)),
+        }
+    }
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "global capture unavailable: ")?;
+        match self {
+            CaptureError::PermissionDenied(reason) => write!(f, "permission denied ({})", reason),
+            CaptureError::NoDisplay => write!(f, "no display (is DISPLAY set?)"),
+            CaptureError::Platform(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+/// Moving or resizing the terminal window failed
+#[derive(Debug)]
+pub enum WindowError {
+    /// The helper (osascript) could not be started
+    Spawn(io::Error),
+    /// The helper ran but reported a failure
+    Script { status: Option<i32>, stderr: String },
+}
+
+impl fmt::Display for WindowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "window positioning failed: ")?;
+        match self {
+            WindowError::Spawn(err) => write!(f, "cannot run osascript: {}", err),
+            WindowError::Script { status, stderr } => {
+                match status {
+                    Some(code) => write!(f, "osascript exited with {}", code)?,
+                    None => write!(f, "osascript was killed")?,
+                }
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for WindowError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WindowError::Spawn(err) => Some(err),
+            WindowError::Script { .. } => None,
+        }
+    }
+}
+
+/// Drawing the sprite to the terminal failed
+#[derive(Debug)]
+pub enum GraphicsError {
+    /// Writing the image escape sequence to the terminal failed
+    Write(io::Error),
+    /// The frame couldn't be encoded for the terminal
+    Encode(image::ImageError),
+}
+
+impl From<io::Error> for GraphicsError {
+    fn from(err: io::Error) -> Self {
+        GraphicsError::Write(err)
+    }
+}
+
+impl fmt::Display for GraphicsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphicsError::Write(err) => write!(f, "sprite display failed: {}", err),
+            GraphicsError::Encode(err) => write!(f, "cannot encode sprite frame: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for GraphicsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GraphicsError::Write(err) => Some(err),
+            GraphicsError::Encode(err) => Some(err),
+        }
+    }
+}
+
+/// The config file exists but couldn't be used
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    /// Parsed, but a value is out of range
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            ConfigError::Parse(path, err) => {
+                write!(f, "invalid config {}: {}", path.display(), err.message())
+            }
+            ConfigError::Invalid(path, reason) => {
+                write!(f, "invalid config {}: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Read(_, err) => Some(err),
+            ConfigError::Parse(_, err) => Some(err),
+            ConfigError::Invalid(..) => None,
+        }
+    }
+}
+
+/// A sprite pack couldn't be loaded
+#[derive(Debug)]
+pub enum SpriteError {
+    /// A pack file couldn't be read
+    Io(PathBuf, io::Error),
+    /// The manifest isn't valid TOML or doesn't match the schema
+    Manifest(toml::de::Error),
+    /// An image couldn't be decoded
+    Image(String, image::ImageError),
+    /// The manifest is well-formed but describes something impossible
+    Invalid(String),
+}
+
+impl fmt::Display for SpriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sprite pack: ")?;
+        match self {
+            SpriteError::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            SpriteError::Manifest(err) => write!(f, "invalid manifest: {}", err.message()),
+            SpriteError::Image(name, err) => write!(f, "cannot decode {}: {}", name, err),
+            SpriteError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SpriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SpriteError::Io(_, err) => Some(err),
+            SpriteError::Manifest(err) => Some(err),
+            SpriteError::Image(_, err) => Some(err),
+            SpriteError::Invalid(_) => None,
+        }
+    }
+}
+
+/// The saved mood couldn't be read back or written
+#[derive(Debug)]
+pub enum MoodError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, serde_json::Error),
+    Write(PathBuf, io::Error),
+}
+
+impl fmt::Display for MoodError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoodError::Read(path, err) => write!(f, "cannot read mood {}: {}", path.display(), err),
+            MoodError::Parse(path, err) => write!(f, "invalid mood {}: {}", path.display(), err),
+            MoodError::Write(path, err) => {
+                write!(f, "cannot save mood {}: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoodError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MoodError::Read(_, err) | MoodError::Write(_, err) => Some(err),
+            MoodError::Parse(_, err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Sprite frames as raw pixels, encoded for the terminal on demand
+//!
+//! Packs keep every frame as RGBA. Each graphics backend asks only for what it
+//! draws with: iTerm2 a PNG, kitty the pixels themselves, Sixel and text cells a
+//! copy scaled to the space they cover. Encodings and scaled copies are made the
+//! first time they are asked for and kept with the frame (scaled ones only for
+//! the latest size, so resizing the terminal doesn't pile them up).
+
+use super::manifest::Recolor;
+use super::recolor;
+use super::sixel;
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, ImageResult, RgbaImage};
+use std::fmt;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Kitty refers to transmitted images by number; 0 means "none"
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Brightness of dimmed frames (a state showing another state's frames)
+const DIM_BRIGHTNESS: f32 = 0.55;
+/// Color left in dimmed frames
+const DIM_SATURATION: f32 = 0.7;
+
+/// A result made for a pixel size, kept until another size is asked for
+type BySize<T> = Mutex<Option<((u32, u32), Arc<T>)>>;
+
+/// One frame of an animation
+pub struct Frame {
+    id: u32,
+    image: RgbaImage,
+    png: OnceLock<Vec<u8>>,
+    scaled: BySize<RgbaImage>,
+    sixel: BySize<[u8]>,
+    dimmed: OnceLock<Box<Frame>>,
+    mirrored: OnceLock<Box<Frame>>,
+}
+
+impl Frame {
+    pub fn new(image: RgbaImage) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            image,
+            png: OnceLock::new(),
+            scaled: Mutex::new(None),
+            sixel: Mutex::new(None),
+            dimmed: OnceLock::new(),
+            mirrored: OnceLock::new(),
+        }
+    }
+
+    /// A frame whose PNG encoding is already known (read back from the disk cache)
+    pub fn with_png(image: RgbaImage, png: Vec<u8>) -> Self {
+        let frame = Self::new(image);
+        let _ = frame.png.set(png);
+        frame
+    }
+
+    /// Unique for the life of the process; names the frame to terminals that keep images
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    pub fn rgba(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    /// The frame flipped horizontally, made once and kept
+    pub fn mirrored(&self) -> &Frame {
+        self.mirrored
+            .get_or_init(|| Box::new(Frame::new(imageops::flip_horizontal(&self.image))))
+    }
+
+    /// The frame darkened and faded, made once and kept
+    pub fn dimmed(&self) -> &Frame {
+        self.dimmed.get_or_init(|| {
+            let mut image = self.image.clone();
+            let dim = Recolor {
+                brightness: DIM_BRIGHTNESS,
+                saturation: DIM_SATURATION,
+                ..Recolor::default()
+            };
+            recolor::apply(&mut image, &dim);
+            Box::new(Frame::new(image))
+        })
+    }
+
+    /// The frame as PNG bytes
+    pub fn png(&self) -> ImageResult<&[u8]> {
+        if let Some(png) = self.png.get() {
+            return Ok(png);
+        }
+        let png = encode_png(&self.image)?;
+        Ok(self.png.get_or_init(|| png))
+    }
+
+    /// The frame resized to exactly `width` x `height` pixels
+    pub fn scaled(&self, width: u32, height: u32) -> Arc<RgbaImage> {
+        latest(&self.scaled, (width, height), || {
+            if (width, height) == self.dimensions() {
+                self.image.clone().into()
+            } else {
+                imageops::resize(&self.image, width, height, FilterType::Triangle).into()
+            }
+        })
+    }
+
+    /// The frame resized to `width` x `height` pixels, as a Sixel image
+    pub fn sixel(&self, width: u32, height: u32) -> Arc<[u8]> {
+        latest(&self.sixel, (width, height), || {
+            sixel::encode(&self.scaled(width, height)).into()
+        })
+    }
+}
+
+/// What `kept` holds for `size`, made with `make` (replacing any other size) if need be
+fn latest<T: ?Sized>(kept: &BySize<T>, size: (u32, u32), make: impl FnOnce() -> Arc<T>) -> Arc<T> {
+    if let Some((_, made)) = kept
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .filter(|(at, _)| *at == size)
+    {
+        return made.clone();
+    }
+    // Made without the lock held: Sixel encoding asks for the scaled copy
+    let made = make();
+    *kept.lock().unwrap_or_else(|e| e.into_inner()) = Some((size, made.clone()));
+    made
+}
+
+/// Frames are equal when they show the same pixels
+impl PartialEq for Frame {
+    fn eq(&self, other: &Self) -> bool {
+        self.image == other.image
+    }
+}
+
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (width, height) = self.dimensions();
+        write!(f, "Frame#{}({}x{})", self.id, width, height)
+    }
+}
+
+pub fn encode_png(image: &RgbaImage) -> ImageResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+    Ok(bytes)
+}
+
+/// The largest size with the frame's aspect ratio that fits in `max_width` x `max_height`
+pub fn fit((width, height): (u32, u32), max_width: u32, max_height: u32) -> (u32, u32) {
+    let (width, height) = (width.max(1) as u64, height.max(1) as u64);
+    let (max_width, max_height) = (max_width as u64, max_height as u64);
+    let (w, h) = if width * max_height > height * max_width {
+        (max_width, height * max_width / width)
+    } else {
+        (width * max_height / height, max_height)
+    };
+    (w.max(1) as u32, h.max(1) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn checkerboard() -> RgbaImage {
+        RgbaImage::from_fn(8, 4, |x, y| {
+            if (x + y) % 2 == 0 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 255, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn encodings_are_made_once() {
+        let frame = Frame::new(checkerboard());
+        let png = frame.png().unwrap();
+        assert_eq!(
+            image::load_from_memory(png).unwrap().to_rgba8(),
+            checkerboard()
+        );
+        assert!(std::ptr::eq(png, frame.png().unwrap()));
+
+        let half = frame.scaled(4, 2);
+        assert_eq!(half.dimensions(), (4, 2));
+        assert!(Arc::ptr_eq(&half, &frame.scaled(4, 2)));
+
+        let sixel = frame.sixel(4, 2);
+        assert!(Arc::ptr_eq(&sixel, &frame.sixel(4, 2)));
+    }
+
+    #[test]
+    fn only_the_latest_size_is_kept() {
+        let frame = Frame::new(checkerboard());
+        let half = frame.scaled(4, 2);
+        let quarter = frame.scaled(2, 1);
+        assert_eq!(quarter.dimensions(), (2, 1));
+        assert!(Arc::ptr_eq(&quarter, &frame.scaled(2, 1)));
+        assert!(!Arc::ptr_eq(&half, &frame.scaled(4, 2)));
+
+        let sixel = frame.sixel(4, 2);
+        frame.sixel(2, 1);
+        assert!(!Arc::ptr_eq(&sixel, &frame.sixel(4, 2)));
+    }
+
+    #[test]
+    fn known_png_is_kept() {
+        let frame = Frame::with_png(checkerboard(), b"from the cache".to_vec());
+        assert_eq!(frame.png().unwrap(), b"from the cache");
+    }
+
+    #[test]
+    fn mirrored_frames_are_new_frames() {
+        let frame = Frame::new(checkerboard());
+        let mirrored = frame.mirrored();
+        assert!(std::ptr::eq(mirrored, frame.mirrored()));
+        assert_ne!(frame.id(), mirrored.id());
+        assert_eq!(mirrored.rgba(), &imageops::flip_horizontal(&checkerboard()));
+        assert_eq!(*mirrored.mirrored(), frame);
+    }
+
+    #[test]
+    fn dimmed_frames_are_darker_and_kept() {
+        let frame = Frame::new(checkerboard());
+        let dimmed = frame.dimmed();
+        assert!(std::ptr::eq(dimmed, frame.dimmed()));
+        assert_ne!(dimmed.id(), frame.id());
+        let (bright, dim) = (frame.rgba().get_pixel(0, 0), dimmed.rgba().get_pixel(0, 0));
+        assert!(dim[0] < bright[0]);
+        assert_eq!(dim[3], bright[3]);
+    }
+
+    #[test]
+    fn fit_keeps_the_aspect_ratio() {
+        assert_eq!(fit((256, 256), 200, 100), (100, 100));
+        assert_eq!(fit((256, 128), 100, 100), (100, 50));
+        assert_eq!(fit((10, 10), 40, 40), (40, 40));
+        assert_eq!(fit((1000, 1), 10, 10), (10, 1));
+    }
+}
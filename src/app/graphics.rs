@@ -0,0 +1,170 @@
+use super::error::GraphicsError;
+use super::frame::{self, Frame};
+use super::terminal;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+
+/// Cell size in pixels when the terminal doesn't report one
+const DEFAULT_CELL_PIXELS: (u32, u32) = (10, 20);
+/// Pixels less opaque than this are left out of text-cell drawings
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// How sprite frames get onto the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsBackend {
+    /// iTerm2 inline images (OSC 1337), also understood by WezTerm
+    Iterm2,
+    /// kitty graphics protocol (kitty, Ghostty), sent as raw RGBA
+    Kitty,
+    /// DEC Sixel graphics (foot, mlterm, xterm with Sixel enabled)
+    Sixel,
+    /// Half-block characters in true color; works without any image protocol
+    Cells,
+    /// Don't draw the sprite at all (text only)
+    Disabled,
+}
+
+impl GraphicsBackend {
+    /// Guess the backend from the environment the terminal advertises
+    pub fn detect() -> Self {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        let term = var("TERM");
+        let term_program = var("TERM_PROGRAM");
+        if term == "xterm-kitty" || !var("KITTY_WINDOW_ID").is_empty() || term_program == "ghostty"
+        {
+            GraphicsBackend::Kitty
+        } else if matches!(term_program.as_str(), "iTerm.app" | "WezTerm")
+            || var("LC_TERMINAL") == "iTerm2"
+        {
+            GraphicsBackend::Iterm2
+        } else if term.starts_with("foot") || term.starts_with("mlterm") || term.contains("sixel") {
+            GraphicsBackend::Sixel
+        } else if matches!(var("COLORTERM").as_str(), "truecolor" | "24bit") {
+            GraphicsBackend::Cells
+        } else {
+            GraphicsBackend::Disabled
+        }
+    }
+
+    /// Whether frames are drawn as characters into the buffer (see `draw_cells`)
+    /// rather than as images written straight to the terminal
+    pub fn uses_cells(&self) -> bool {
+        *self == GraphicsBackend::Cells
+    }
+
+    /// Draw a frame scaled into `area` (terminal cell coordinates)
+    /// Does nothing for `Cells`, whose frames are part of the rendered buffer
+    pub fn display(&self, frame: &Frame, area: Rect) -> Result<(), GraphicsError> {
+        match self {
+            GraphicsBackend::Iterm2 => terminal::display_image_at_position(
+                frame.png().map_err(GraphicsError::Encode)?,
+                area.y,
+                area.x,
+                Some(area.width as u32),
+                Some(area.height as u32),
+            )?,
+            GraphicsBackend::Kitty => terminal::display_kitty_image(
+                frame.id(),
+                frame.rgba(),
+                area.y,
+                area.x,
+                area.width,
+                area.height,
+            )?,
+            GraphicsBackend::Sixel => {
+                let (cell_width, cell_height) = cell_pixels();
+                let (width, height) = frame::fit(
+                    frame.dimensions(),
+                    area.width as u32 * cell_width,
+                    area.height as u32 * cell_height,
+                );
+                terminal::display_sixel_at_position(
+                    &frame.sixel(width, height),
+                    area.y,
+                    area.x,
+                    area.width,
+                    area.height,
+                )?
+            }
+            GraphicsBackend::Cells | GraphicsBackend::Disabled => {}
+        }
+        Ok(())
+    }
+}
+
+/// Draw a frame centered in `area` with half blocks: two pixels per cell, the top
+/// one in the foreground color and the bottom one in the background color
+pub fn draw_cells(frame: &Frame, area: Rect, buf: &mut Buffer) {
+    if area.is_empty() {
+        return;
+    }
+    let (width, height) = frame::fit(
+        frame.dimensions(),
+        area.width as u32,
+        area.height as u32 * 2,
+    );
+    let image = frame.scaled(width, height);
+    let rows = height.div_ceil(2);
+    let left = area.x + (area.width - width as u16) / 2;
+    let top = area.y + (area.height - rows as u16) / 2;
+
+    let color = |x: u32, y: u32| {
+        let pixel = image.get_pixel_checked(x, y)?;
+        (pixel[3] >= ALPHA_THRESHOLD).then_some(Color::Rgb(pixel[0], pixel[1], pixel[2]))
+    };
+    for row in 0..rows {
+        for col in 0..width {
+            let Some(cell) = buf.cell_mut((left + col as u16, top + row as u16)) else {
+                continue;
+            };
+            match (color(col, row * 2), color(col, row * 2 + 1)) {
+                (Some(upper), Some(lower)) => cell.set_char('▀').set_fg(upper).set_bg(lower),
+                (Some(upper), None) => cell.set_char('▀').set_fg(upper).set_bg(Color::Reset),
+                (None, Some(lower)) => cell.set_char('▄').set_fg(lower).set_bg(Color::Reset),
+                (None, None) => continue,
+            };
+        }
+    }
+}
+
+/// Pixels per terminal cell
+fn cell_pixels() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns) as u32,
+            (size.height / size.rows) as u32,
+        ),
+        _ => DEFAULT_CELL_PIXELS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn cells_pair_up_pixels() {
+        // Left half: red over blue; right half: transparent over green
+        let image = RgbaImage::from_fn(4, 4, |x, y| match (x < 2, y % 2) {
+            (true, 0) => Rgba([255, 0, 0, 255]),
+            (true, _) => Rgba([0, 0, 255, 255]),
+            (false, 0) => Rgba([0, 0, 0, 0]),
+            (false, _) => Rgba([0, 255, 0, 255]),
+        });
+        let area = Rect::new(0, 0, 4, 4);
+        let mut buf = Buffer::empty(area);
+        draw_cells(&Frame::new(image), area, &mut buf);
+
+        // Four pixel rows make two cell rows, centered in four
+        let upper = &buf[(0, 0)];
+        assert_eq!(upper.symbol(), " ");
+        let (red_over_blue, green) = (&buf[(0, 1)], &buf[(3, 1)]);
+        assert_eq!(red_over_blue.symbol(), "▀");
+        assert_eq!(red_over_blue.fg, Color::Rgb(255, 0, 0));
+        assert_eq!(red_over_blue.bg, Color::Rgb(0, 0, 255));
+        assert_eq!(green.symbol(), "▄");
+        assert_eq!(green.fg, Color::Rgb(0, 255, 0));
+    }
+}
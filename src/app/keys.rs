@@ -1,3 +1,13 @@
+//! Key event model shared by every input source
+//!
+//! A key press is represented as a display string: printable keys are the text they
+//! produce, special keys are a single glyph (`⏎`, `⌫`, `←`, ...) or a name like `F5`.
+//! Modifiers aren't keys of their own: a source tracks which are held and sends the
+//! chords the dog reacts to as one string (`⌃w`, see `chord`), dropping other shortcuts.
+//! `App::on_key` consumes these strings, so any source that can produce them -
+//! global capture via rdev or a host app's crossterm events - can drive the dog.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use rdev::Key;
 use std::borrow::Cow;
 
@@ -52,6 +62,8 @@ pub fn key_to_string(key: Key, shifted: bool) -> Cow<'static, str> {
         Key::Backspace => Cow::Borrowed("⌫"),
         Key::Escape => Cow::Borrowed("⎋"),
         Key::Delete => Cow::Borrowed("⌦"),
+        Key::Home => Cow::Borrowed("⇱"),
+        Key::End => Cow::Borrowed("⇲"),
 
         // Arrow keys
         Key::UpArrow => Cow::Borrowed("↑"),
@@ -97,3 +109,96 @@ pub fn key_to_string(key: Key, shifted: bool) -> Cow<'static, str> {
         _ => Cow::Borrowed("?"),
     }
 }
+
+/// Convert a crossterm key event (e.g. from a host ratatui app) to a display string
+/// Returns None for releases, shortcuts and keys the dog doesn't react to
+/// The chords it does react to come as the modifier's glyph and the key, like `⌃w`
+pub fn from_crossterm(event: &KeyEvent) -> Option<Cow<'static, str>> {
+    if event.kind == KeyEventKind::Release {
+        return None;
+    }
+
+    let modifiers = Modifiers {
+        ctrl: event.modifiers.contains(KeyModifiers::CONTROL),
+        alt: event.modifiers.contains(KeyModifiers::ALT),
+        meta: event.modifiers.contains(KeyModifiers::SUPER),
+    };
+    let key = match event.code {
+        KeyCode::Char(c) => Cow::Owned(c.to_string()),
+        KeyCode::Enter => Cow::Borrowed("⏎"),
+        KeyCode::Tab => Cow::Borrowed("⇥"),
+        KeyCode::Backspace => Cow::Borrowed("⌫"),
+        KeyCode::Esc => Cow::Borrowed("⎋"),
+        KeyCode::Delete => Cow::Borrowed("⌦"),
+        KeyCode::Home => Cow::Borrowed("⇱"),
+        KeyCode::End => Cow::Borrowed("⇲"),
+        KeyCode::Up => Cow::Borrowed("↑"),
+        KeyCode::Down => Cow::Borrowed("↓"),
+        KeyCode::Left => Cow::Borrowed("←"),
+        KeyCode::Right => Cow::Borrowed("→"),
+        KeyCode::F(n) => Cow::Owned(format!("F{}", n)),
+        _ => return None,
+    };
+    chord(key, modifiers)
+}
+
+/// Modifier keys held down with a key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    /// Cmd or the Windows key
+    pub meta: bool,
+}
+
+/// A key pressed with `modifiers` held, as `App::on_key` takes it
+/// The chords the dog reacts to (saving, deleting a word) come as one string like `⌃w`.
+/// Other chords on a printable key are shortcuts rather than text, so they're None;
+/// on special keys the modifiers are ignored
+pub fn chord(key: Cow<'static, str>, modifiers: Modifiers) -> Option<Cow<'static, str>> {
+    let Modifiers { ctrl, alt, meta } = modifiers;
+    let chord = match (ctrl, alt, meta, key.as_ref()) {
+        (false, false, false, _) => return Some(key),
+        (true, false, false, "s") => "⌃s",
+        (false, false, true, "s") => "⌘s",
+        (true, false, false, "w") => "⌃w",
+        (false, true, false, "⌫") => "⌥⌫",
+        (_, _, _, key) if is_text(key) => return None,
+        _ => return Some(key),
+    };
+    Some(Cow::Borrowed(chord))
+}
+
+/// Whether a key types a character, rather than being a special key like `⏎` or `F5`
+fn is_text(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c.is_ascii_punctuation() || c == ' ')
+        && chars.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_known_chords_get_through() {
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Modifiers::default()
+        };
+        let alt = Modifiers {
+            alt: true,
+            ..Modifiers::default()
+        };
+        let key = |key: &'static str, modifiers| chord(Cow::Borrowed(key), modifiers);
+        assert_eq!(key("w", Modifiers::default()).as_deref(), Some("w"));
+        assert_eq!(key("w", ctrl).as_deref(), Some("⌃w"));
+        assert_eq!(key("⌫", alt).as_deref(), Some("⌥⌫"));
+        // A shortcut isn't text, but held modifiers don't stop a special key
+        assert_eq!(key("c", ctrl), None);
+        assert_eq!(key("w", alt), None);
+        assert_eq!(key("←", ctrl).as_deref(), Some("←"));
+    }
+}
@@ -0,0 +1,33 @@
+//! Append-only log file for errors that the UI only shows briefly
+//!
+//! Lives at `$XDG_STATE_HOME/tapwatch/tapwatch.log` (or the platform cache dir when
+//! there is no state dir, e.g. on macOS). Logging never fails loudly - a broken log
+//! must not take the app down with it.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LOG_FILE_NAME: &str = "tapwatch.log";
+
+/// Where the app writes its log lines by default
+pub fn path() -> Option<PathBuf> {
+    let base = dirs::state_dir().or_else(dirs::cache_dir)?;
+    Some(base.join("tapwatch").join(LOG_FILE_NAME))
+}
+
+/// Append one timestamped line to the log file at `path`
+pub fn write(path: &Path, message: &str) {
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) else {
+        return;
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let _ = writeln!(file, "[{}] {}", timestamp, message);
+}
@@ -0,0 +1,839 @@
+//! Which animation the character plays, as a state machine declared in data
+//!
+//! Every state names the animation it plays and lists transitions out of it. A
+//! transition fires when all of its conditions hold: an event (`on`), time spent in
+//! the state (`after_ms`), time since the last key (`idle_ms`, or `sleepy` for the
+//! configured time before falling asleep), the state's one-shot animation having
+//! finished (`finished`) and gauges such as the typing rate (`when`). Transitions
+//! are tried in order and the first that holds wins; leaving a state runs its
+//! `exit` actions, entering one its `enter` actions.
+//!
+//! A state whose animation the pack lacks plays its `fallback` instead, possibly
+//! dimmed, or is passed straight through if it's `optional`. The built-in
+//! `excited`, `confused` and sleep states use these, so packs without their
+//! frames still get excited, puzzled and doze off. States marked `tempo` play
+//! faster the faster you type.
+//!
+//! A pack's manifest can add states and replace built-in ones (entirely,
+//! transitions included); every machine starts in `idle`:
+//!
+//! ```toml
+//! [states.typing]
+//! animation = "typing"
+//! tempo = true
+//! transitions = [
+//!   { to = "excited", when = [{ gauge = "typing_rate", above = 6.0 }] },
+//!   { to = "idle", idle_ms = 3000 },
+//! ]
+//!
+//! [states.excited]
+//! animation = "wag"
+//! transitions = [{ to = "typing", when = [{ gauge = "typing_rate", below = 4.0 }] }]
+//! ```
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// The state every machine starts in
+pub const IDLE: &str = "idle";
+/// The built-in state while keys are being pressed
+pub const TYPING: &str = "typing";
+/// The built-in state after a long time without keys
+pub const SLEEPING: &str = "sleeping";
+
+/// Any key press
+pub const KEY: &str = "key";
+/// Backspace (also a `key`)
+pub const BACKSPACE: &str = "backspace";
+/// Enter, Tab, Esc, Delete, arrows and function keys (also a `key`)
+pub const SPECIAL: &str = "special";
+/// Typing turned error-heavy (see `mistakes`; comes with the key that showed it)
+pub const MISTAKES: &str = "mistakes";
+/// The app just started (time to say hello)
+pub const START: &str = "start";
+const EVENTS: &[&str] = &[KEY, BACKSPACE, SPECIAL, MISTAKES, START];
+
+/// Keys per second, smoothed (see `tempo`)
+pub const TYPING_RATE: &str = "typing_rate";
+/// Mood levels, 0 to 1 (see `mood`)
+pub const HAPPINESS: &str = "happiness";
+pub const ENERGY: &str = "energy";
+pub const BOREDOM: &str = "boredom";
+const GAUGES: &[&str] = &[TYPING_RATE, HAPPINESS, ENERGY, BOREDOM];
+
+/// Merged into every pack's machine (part of the disk cache key)
+pub(crate) const DEFAULT_STATES: &str = r#"
+[idle]
+animation = "idle"
+start = "random"
+enter = ["dissolve-text"]
+
+[[idle.variants]]
+animation = "idle-tired"
+when = [{ gauge = "energy", below = 0.25 }]
+
+[[idle.variants]]
+animation = "idle-bored"
+when = [{ gauge = "boredom", above = 0.6 }]
+
+[[idle.variants]]
+animation = "idle-happy"
+when = [{ gauge = "happiness", above = 0.75 }]
+
+[[idle.transitions]]
+to = "greeting-excited"
+on = "start"
+when = [{ gauge = "boredom", above = 0.6 }]
+
+[[idle.transitions]]
+to = "greeting"
+on = "start"
+
+[[idle.transitions]]
+to = "confused"
+on = "mistakes"
+
+[[idle.transitions]]
+to = "typing"
+on = "key"
+
+[[idle.transitions]]
+to = "lying-down"
+sleepy = true
+
+[typing]
+animation = "typing"
+tempo = true
+
+[[typing.transitions]]
+to = "confused"
+on = "mistakes"
+
+[[typing.transitions]]
+to = "excited"
+when = [{ gauge = "typing_rate", above = 9.0 }]
+
+[[typing.transitions]]
+to = "idle"
+idle_ms = 3000
+
+[excited]
+animation = "excited"
+fallback = { animation = "typing" }
+tempo = true
+
+[[excited.transitions]]
+to = "confused"
+on = "mistakes"
+
+[[excited.transitions]]
+to = "typing"
+when = [{ gauge = "typing_rate", below = 6.0 }]
+
+[greeting]
+animation = "greet"
+fallback = { animation = "idle" }
+enter = ["greet"]
+
+[[greeting.transitions]]
+to = "typing"
+on = "key"
+
+[[greeting.transitions]]
+to = "idle"
+finished = true
+
+[[greeting.transitions]]
+to = "idle"
+after_ms = 2500
+
+[greeting-excited]
+animation = "greet-excited"
+fallback = { animation = "typing" }
+enter = ["greet"]
+
+[[greeting-excited.transitions]]
+to = "typing"
+on = "key"
+
+[[greeting-excited.transitions]]
+to = "idle"
+finished = true
+
+[[greeting-excited.transitions]]
+to = "idle"
+after_ms = 2500
+
+[confused]
+animation = "confused"
+fallback = { animation = "idle" }
+overlay = "question"
+
+[[confused.transitions]]
+to = "typing"
+finished = true
+
+[[confused.transitions]]
+to = "typing"
+after_ms = 2000
+
+[lying-down]
+animation = "lie-down"
+optional = true
+
+[[lying-down.transitions]]
+to = "waking"
+on = "key"
+
+[[lying-down.transitions]]
+to = "sleeping"
+finished = true
+
+[[lying-down.transitions]]
+to = "sleeping"
+after_ms = 3000
+
+[sleeping]
+animation = "sleep"
+fallback = { animation = "idle", dim = true }
+overlay = "zzz"
+
+[[sleeping.transitions]]
+to = "waking"
+on = "key"
+
+[waking]
+animation = "wake"
+optional = true
+
+[[waking.transitions]]
+to = "typing"
+finished = true
+
+[[waking.transitions]]
+to = "typing"
+after_ms = 2000
+"#;
+
+static DEFAULT_MACHINE: Lazy<Machine> =
+    Lazy::new(|| toml::from_str(DEFAULT_STATES).expect("built-in states are valid TOML"));
+
+/// All states, by name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Machine {
+    states: BTreeMap<String, State>,
+}
+
+/// One state: what it plays and where it can go from there
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct State {
+    pub animation: String,
+    /// Which frame the animation starts on when the state is entered
+    #[serde(default)]
+    pub start: StartFrame,
+    /// Played when the pack has no `animation`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<Fallback>,
+    /// Without `animation` or `fallback`, the state is passed straight through
+    /// instead of being an error
+    #[serde(default)]
+    pub optional: bool,
+    /// Drawn next to the character while in the state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlay: Option<Overlay>,
+    /// Frames play faster or slower with the typing rate
+    #[serde(default)]
+    pub tempo: bool,
+    #[serde(default)]
+    pub enter: Vec<Action>,
+    #[serde(default)]
+    pub exit: Vec<Action>,
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+    /// Played instead of `animation` when entering the state while their gauges hold
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<AnimationVariant>,
+}
+
+/// An alternative animation for a state, such as a tired idle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationVariant {
+    pub animation: String,
+    pub when: Vec<GaugeCondition>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StartFrame {
+    #[default]
+    First,
+    /// Any frame, so the same state doesn't always look the same
+    Random,
+}
+
+/// Another animation to play in place of a state's own
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fallback {
+    pub animation: String,
+    /// Darken the frames, so it looks different from where they come from
+    #[serde(default)]
+    pub dim: bool,
+}
+
+/// Decoration drawn beside the sprite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Overlay {
+    /// "zZz" floating up
+    Zzz,
+    /// A bobbing question mark
+    Question,
+    /// "woof!" beside the head
+    Woof,
+    /// Hearts floating up
+    Hearts,
+}
+
+/// What a state shows with the animations a pack has
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shown<'a> {
+    Animation {
+        name: &'a str,
+        dim: bool,
+    },
+    /// Nothing: an optional state the pack has no frames for
+    Skipped,
+}
+
+/// A way out of a state; every condition given must hold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transition {
+    pub to: String,
+    /// An event that must have just happened
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on: Option<String>,
+    /// Minimum time in the state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_ms: Option<u64>,
+    /// Minimum time since the last key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_ms: Option<u64>,
+    /// No key for as long as the user set before the character falls asleep
+    #[serde(default)]
+    pub sleepy: bool,
+    /// The state's animation has played through (`once-hold` or `once-return`)
+    #[serde(default)]
+    pub finished: bool,
+    #[serde(default)]
+    pub when: Vec<GaugeCondition>,
+}
+
+/// A gauge reading above and/or below a limit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GaugeCondition {
+    pub gauge: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub above: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below: Option<f32>,
+}
+
+/// Something done when entering or leaving a state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Let the typed text dissolve away
+    DissolveText,
+    /// Remove the typed text at once
+    ClearText,
+    /// Say hello, in words that suit the mood
+    Greet,
+}
+
+/// What the machine looks at when deciding whether to move on
+pub struct Inputs<'a> {
+    pub now: Instant,
+    /// Events that just happened (none on a plain tick)
+    pub events: &'a [&'a str],
+    pub since_key: Duration,
+    /// How long without keys counts as sleepy (`None`: never)
+    pub sleep_after: Option<Duration>,
+    /// Whether the current animation has played through
+    pub finished: bool,
+    pub gauges: &'a [(&'a str, f32)],
+}
+
+/// Where a machine is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationState {
+    pub name: String,
+    /// When the state was entered
+    pub since: Instant,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        DEFAULT_MACHINE.clone()
+    }
+}
+
+impl Machine {
+    /// The built-in states with a manifest's states added or replacing them
+    pub fn with_states(states: &BTreeMap<String, State>) -> Self {
+        let mut machine = Self::default();
+        machine.states.extend(
+            states
+                .iter()
+                .map(|(name, state)| (name.clone(), state.clone())),
+        );
+        machine
+    }
+
+    pub fn state(&self, name: &str) -> Option<&State> {
+        self.states.get(name)
+    }
+
+    /// Check that every transition leads somewhere and every state can be played
+    pub fn validate(&self, has_animation: impl Fn(&str) -> bool) -> Result<(), String> {
+        if !self.states.contains_key(IDLE) {
+            return Err(format!("no `{}` state", IDLE));
+        }
+        for (name, state) in &self.states {
+            let invalid = |reason: String| Err(format!("state `{}`: {}", name, reason));
+            if state.shown(&has_animation).is_none() {
+                return invalid(match &state.fallback {
+                    Some(fallback) => format!(
+                        "no `{}` animation, nor `{}` to fall back to",
+                        state.animation, fallback.animation
+                    ),
+                    None => format!("no `{}` animation", state.animation),
+                });
+            }
+            for variant in &state.variants {
+                check_gauges(&variant.when).or_else(invalid)?;
+                if variant.when.is_empty() {
+                    return invalid(format!("variant `{}` has no conditions", variant.animation));
+                }
+            }
+            for transition in &state.transitions {
+                if !self.states.contains_key(&transition.to) {
+                    return invalid(format!("transition to unknown state `{}`", transition.to));
+                }
+                if let Some(event) = transition.on.as_deref().filter(|e| !EVENTS.contains(e)) {
+                    return invalid(format!(
+                        "unknown event `{}` (expected one of {})",
+                        event,
+                        EVENTS.join(", ")
+                    ));
+                }
+                check_gauges(&transition.when).or_else(invalid)?;
+                if transition.is_unconditional() {
+                    return invalid(format!(
+                        "transition to `{}` has no conditions",
+                        transition.to
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every gauge is known and has a limit
+fn check_gauges(when: &[GaugeCondition]) -> Result<(), String> {
+    for condition in when {
+        if !GAUGES.contains(&condition.gauge.as_str()) {
+            return Err(format!(
+                "unknown gauge `{}` (expected one of {})",
+                condition.gauge,
+                GAUGES.join(", ")
+            ));
+        }
+        if condition.above.is_none() && condition.below.is_none() {
+            return Err(format!(
+                "gauge `{}` needs `above` or `below`",
+                condition.gauge
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl State {
+    /// What the state shows; `None` if it can't be shown at all
+    pub fn shown(&self, has_animation: impl Fn(&str) -> bool) -> Option<Shown<'_>> {
+        if has_animation(&self.animation) {
+            return Some(Shown::Animation {
+                name: &self.animation,
+                dim: false,
+            });
+        }
+        match &self.fallback {
+            Some(fallback) if has_animation(&fallback.animation) => Some(Shown::Animation {
+                name: &fallback.animation,
+                dim: fallback.dim,
+            }),
+            _ if self.optional => Some(Shown::Skipped),
+            _ => None,
+        }
+    }
+
+    /// The first variant the pack has whose gauges all hold
+    pub fn variant(
+        &self,
+        has_animation: impl Fn(&str) -> bool,
+        gauges: &[(&str, f32)],
+    ) -> Option<&str> {
+        self.variants
+            .iter()
+            .find(|v| has_animation(&v.animation) && v.when.iter().all(|c| c.holds(gauges)))
+            .map(|v| v.animation.as_str())
+    }
+}
+
+impl Transition {
+    fn is_unconditional(&self) -> bool {
+        self.on.is_none()
+            && self.after_ms.is_none()
+            && self.idle_ms.is_none()
+            && !self.sleepy
+            && !self.finished
+            && self.when.is_empty()
+    }
+
+    fn holds(&self, in_state: Duration, inputs: &Inputs) -> bool {
+        let at_least = |ms: Option<u64>, elapsed: Duration| {
+            ms.is_none_or(|ms| elapsed >= Duration::from_millis(ms))
+        };
+        self.on
+            .as_deref()
+            .is_none_or(|event| inputs.events.contains(&event))
+            && at_least(self.after_ms, in_state)
+            && at_least(self.idle_ms, inputs.since_key)
+            && (!self.sleepy
+                || inputs
+                    .sleep_after
+                    .is_some_and(|after| inputs.since_key >= after))
+            && (!self.finished || inputs.finished)
+            && self
+                .when
+                .iter()
+                .all(|condition| condition.holds(inputs.gauges))
+    }
+}
+
+impl GaugeCondition {
+    fn holds(&self, gauges: &[(&str, f32)]) -> bool {
+        let Some(&(_, value)) = gauges.iter().find(|(name, _)| *name == self.gauge) else {
+            return false;
+        };
+        self.above.is_none_or(|above| value > above) && self.below.is_none_or(|below| value < below)
+    }
+}
+
+impl AnimationState {
+    /// The starting state
+    pub fn new(now: Instant) -> Self {
+        Self {
+            name: IDLE.to_string(),
+            since: now,
+        }
+    }
+
+    /// Take the first transition that holds, if any, returning the actions to run
+    /// (the old state's `exit`, then the new state's `enter`)
+    pub fn update(&mut self, machine: &Machine, inputs: &Inputs) -> Option<Vec<Action>> {
+        let current = machine.state(&self.name)?;
+        let in_state = inputs.now.duration_since(self.since);
+        let transition = current
+            .transitions
+            .iter()
+            .find(|transition| transition.holds(in_state, inputs))?;
+        let next = machine.state(&transition.to)?;
+
+        let actions = current.exit.iter().chain(&next.enter).copied().collect();
+        self.name = transition.to.clone();
+        self.since = inputs.now;
+        Some(actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    /// The built-in states plus some a pack might declare
+    fn machine() -> Machine {
+        let states: BTreeMap<String, State> = toml::from_str(
+            r#"
+            [typing]
+            animation = "typing"
+            exit = ["dissolve-text"]
+
+            [[typing.transitions]]
+            to = "confused"
+            on = "backspace"
+
+            [[typing.transitions]]
+            to = "excited"
+            when = [{ gauge = "typing_rate", above = 6.0 }]
+
+            [[typing.transitions]]
+            to = "idle"
+            idle_ms = 3000
+
+            [excited]
+            animation = "wag"
+            enter = ["clear-text"]
+
+            [[excited.transitions]]
+            to = "typing"
+            when = [{ gauge = "typing_rate", below = 4.0 }]
+
+            [confused]
+            animation = "tilt"
+            start = "random"
+
+            [[confused.transitions]]
+            to = "typing"
+            finished = true
+            after_ms = 500
+            "#,
+        )
+        .unwrap();
+        Machine::with_states(&states)
+    }
+
+    /// Feed the machine one set of inputs at `at` seconds after the start
+    fn step(
+        state: &mut AnimationState,
+        start: Instant,
+        at: f32,
+        events: &[&str],
+        since_key: f32,
+        rate: f32,
+        finished: bool,
+    ) -> Option<Vec<Action>> {
+        let inputs = Inputs {
+            now: start + SECOND.mul_f32(at),
+            events,
+            since_key: SECOND.mul_f32(since_key),
+            sleep_after: None,
+            finished,
+            gauges: &[(TYPING_RATE, rate)],
+        };
+        state.update(&machine(), &inputs)
+    }
+
+    #[test]
+    fn built_in_states_follow_the_keyboard() {
+        let machine = Machine::default();
+        assert!(machine
+            .validate(|name| ["idle", "typing"].contains(&name))
+            .is_ok());
+
+        let start = Instant::now();
+        let mut state = AnimationState::new(start);
+        let inputs = |at: u64, events, since_key: u64| Inputs {
+            now: start + Duration::from_millis(at),
+            events,
+            since_key: Duration::from_millis(since_key),
+            sleep_after: None,
+            finished: false,
+            gauges: &[],
+        };
+
+        assert_eq!(state.update(&machine, &inputs(10_000, &[], 10_000)), None);
+        assert_eq!(
+            state.update(&machine, &inputs(10_000, &[KEY], 0)),
+            Some(vec![])
+        );
+        assert_eq!(state.name, TYPING);
+        assert_eq!(state.update(&machine, &inputs(12_000, &[KEY], 0)), None);
+        assert_eq!(state.update(&machine, &inputs(14_999, &[], 2_999)), None);
+        assert_eq!(
+            state.update(&machine, &inputs(15_000, &[], 3_000)),
+            Some(vec![Action::DissolveText])
+        );
+        assert_eq!(state.name, IDLE);
+        assert_eq!(state.since, start + Duration::from_millis(15_000));
+    }
+
+    #[test]
+    fn events_timers_and_gauges_pick_the_transition() {
+        let start = Instant::now();
+        let mut state = AnimationState::new(start);
+        step(&mut state, start, 0.0, &[KEY], 0.0, 1.0, false);
+        assert_eq!(state.name, TYPING);
+
+        // Fast typing: exit actions of typing, then enter actions of excited
+        assert_eq!(
+            step(&mut state, start, 1.0, &[], 0.1, 8.0, false),
+            Some(vec![Action::DissolveText, Action::ClearText])
+        );
+        assert_eq!(state.name, "excited");
+        // In between the limits nothing changes
+        assert_eq!(step(&mut state, start, 2.0, &[], 0.1, 5.0, false), None);
+        step(&mut state, start, 3.0, &[], 0.1, 3.0, false);
+        assert_eq!(state.name, TYPING);
+
+        // Earlier transitions win: backspace beats the idle timer
+        step(&mut state, start, 7.0, &[KEY, BACKSPACE], 5.0, 1.0, false);
+        assert_eq!(state.name, "confused");
+        // Both the one-shot and the minimum time have to be done
+        step(&mut state, start, 7.2, &[], 0.2, 1.0, true);
+        assert_eq!(state.name, "confused");
+        step(&mut state, start, 7.6, &[], 0.6, 1.0, false);
+        assert_eq!(state.name, "confused");
+        step(&mut state, start, 7.6, &[], 0.6, 1.0, true);
+        assert_eq!(state.name, TYPING);
+    }
+
+    #[test]
+    fn long_idle_falls_asleep_and_a_key_wakes_up() {
+        let machine = Machine::default();
+        let start = Instant::now();
+        let mut state = AnimationState::new(start);
+        let inputs = |at: u64, events, sleep_after: Option<u64>, finished| Inputs {
+            now: start + SECOND * at as u32,
+            events,
+            since_key: SECOND * at as u32,
+            sleep_after: sleep_after.map(Duration::from_secs),
+            finished,
+            gauges: &[],
+        };
+
+        assert_eq!(
+            state.update(&machine, &inputs(3600, &[], None, false)),
+            None
+        );
+        assert_eq!(
+            state.update(&machine, &inputs(299, &[], Some(300), false)),
+            None
+        );
+        state.update(&machine, &inputs(300, &[], Some(300), false));
+        assert_eq!(state.name, "lying-down");
+        state.update(&machine, &inputs(301, &[], Some(300), true));
+        assert_eq!(state.name, SLEEPING);
+        state.update(&machine, &inputs(900, &[KEY], Some(300), false));
+        assert_eq!(state.name, "waking");
+        assert_eq!(
+            state.update(&machine, &inputs(900, &[KEY], Some(300), false)),
+            None
+        );
+        state.update(&machine, &inputs(901, &[], Some(300), true));
+        assert_eq!(state.name, TYPING);
+    }
+
+    #[test]
+    fn missing_sleep_frames_fall_back_or_are_skipped() {
+        let machine = Machine::default();
+        let idle_only = |name: &str| name == "idle";
+        let shown = |name| machine.state(name).unwrap().shown(idle_only);
+        assert_eq!(
+            shown(SLEEPING),
+            Some(Shown::Animation {
+                name: "idle",
+                dim: true
+            })
+        );
+        assert_eq!(shown("lying-down"), Some(Shown::Skipped));
+        assert_eq!(shown("waking"), Some(Shown::Skipped));
+        assert_eq!(shown(TYPING), None);
+
+        let all = |_: &str| true;
+        assert_eq!(
+            machine.state(SLEEPING).unwrap().shown(all),
+            Some(Shown::Animation {
+                name: "sleep",
+                dim: false
+            })
+        );
+    }
+
+    #[test]
+    fn variants_follow_the_gauges_and_the_pack() {
+        let machine = Machine::default();
+        let idle = machine.state(IDLE).unwrap();
+        let all = |_: &str| true;
+        let mood = |happiness, energy, boredom| {
+            [(HAPPINESS, happiness), (ENERGY, energy), (BOREDOM, boredom)]
+        };
+
+        assert_eq!(idle.variant(all, &mood(0.5, 1.0, 0.0)), None);
+        assert_eq!(idle.variant(all, &mood(0.9, 1.0, 0.0)), Some("idle-happy"));
+        // Earlier variants win
+        assert_eq!(idle.variant(all, &mood(0.9, 0.1, 0.9)), Some("idle-tired"));
+        // Only ones the pack has frames for
+        let no_tired = |name: &str| name != "idle-tired";
+        assert_eq!(
+            idle.variant(no_tired, &mood(0.9, 0.1, 0.9)),
+            Some("idle-bored")
+        );
+    }
+
+    #[test]
+    fn manifest_states_replace_and_extend_the_built_in_ones() {
+        let machine = machine();
+        assert_eq!(machine.state(IDLE), Machine::default().state(IDLE));
+        assert_eq!(machine.state(TYPING).unwrap().transitions.len(), 3);
+        assert_eq!(machine.state("confused").unwrap().start, StartFrame::Random);
+        assert!(machine
+            .validate(|name| ["idle", "typing", "wag", "tilt"].contains(&name))
+            .is_ok());
+    }
+
+    #[test]
+    fn broken_machines_are_rejected() {
+        let all = |_: &str| true;
+        let error = |states: &str| {
+            let states: BTreeMap<String, State> = toml::from_str(states).unwrap();
+            Machine::with_states(&states).validate(all).unwrap_err()
+        };
+
+        assert_eq!(
+            machine().validate(|name| name != "tilt").unwrap_err(),
+            "state `confused`: no `tilt` animation"
+        );
+        assert_eq!(
+            error("[idle]\nanimation = \"idle\"\ntransitions = [{ to = \"nap\", on = \"key\" }]"),
+            "state `idle`: transition to unknown state `nap`"
+        );
+        assert!(error(
+            "[idle]\nanimation = \"idle\"\ntransitions = [{ to = \"typing\", on = \"keys\" }]"
+        )
+        .contains("unknown event `keys`"));
+        assert!(error("[idle]\nanimation = \"idle\"\ntransitions = [{ to = \"typing\", when = [{ gauge = \"speed\", above = 1.0 }] }]")
+            .contains("unknown gauge `speed`"));
+        assert!(error("[idle]\nanimation = \"idle\"\nvariants = [{ animation = \"nap\", when = [{ gauge = \"mood\", above = 0.5 }] }]")
+            .contains("unknown gauge `mood`"));
+        assert_eq!(
+            error("[idle]\nanimation = \"idle\"\nvariants = [{ animation = \"nap\", when = [] }]"),
+            "state `idle`: variant `nap` has no conditions"
+        );
+        assert_eq!(
+            error("[idle]\nanimation = \"idle\"\ntransitions = [{ to = \"typing\" }]"),
+            "state `idle`: transition to `typing` has no conditions"
+        );
+        assert_eq!(
+            Machine::with_states(&toml::from_str("[idle]\nanimation = \"sit\"").unwrap())
+                .validate(|name| name == "sit" || name == "typing")
+                .unwrap_err(),
+            "state `confused`: no `confused` animation, nor `idle` to fall back to"
+        );
+        assert!(toml::from_str::<BTreeMap<String, State>>(
+            "[idle]\nanimation = \"idle\"\nstart = \"middle\""
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,758 @@
+//! Sprite pack manifest (`manifest.toml`)
+//!
+//! A sprite pack is a directory with one or more images and a manifest describing
+//! where the frames are and how to play them:
+//!
+//! ```toml
+//! name = "dog"
+//! image = "sheet.png"
+//!
+//! [grid]
+//! columns = 4
+//! rows = 4
+//!
+//! [background]
+//! mode = "flood"      # or "key", "threshold", "none"
+//! color = "#ffffff"
+//! tolerance = 16
+//!
+//! [animations.idle]
+//! frames = [0, 1, 2, 3]      # grid cells, row-major
+//! frame_ms = 10000
+//!
+//! [animations.typing]
+//! rects = [[0, 512, 256, 256], [256, 512, 256, 256]]
+//! frame_ms = 250
+//! ```
+//!
+//! Each frame can have its own duration with `durations = [ms, ...]`, and
+//! `playback` picks the order: `"loop"` (default), `"ping-pong"`, `"once-hold"`,
+//! `"once-return"` or `"random"` (optionally with per-frame `weights`).
+//!
+//! Frames are lined up with `anchor`, either for the whole pack or per animation:
+//! `"union"` (the default) keeps frames where they were drawn and centers them
+//! together, `"baseline"` stands each frame on a shared baseline and `"center"`
+//! centers every frame on its own. `anchors = [[x, y], ...]` instead gives one
+//! point per frame that stays put.
+//!
+//! An animation takes its frames from exactly one of `frames` (grid cells),
+//! `rects` (`[x, y, width, height]` in pixels), `files` (one image per frame) or
+//! `animated` (an animated GIF, APNG or WebP). Animated files carry their own frame
+//! delays, so `frame_ms` is optional there and overrides them when given.
+//!
+//! `facing` says which way the character looks in the drawing (`"right"` by
+//! default). Mirrored frames are made the first time they're needed so it can look
+//! either way, and an optional `turn` animation, drawn turning away from that side,
+//! plays when it turns around.
+//!
+//! Named color variants recolor the whole pack at load time. Each one can shift
+//! the hue (degrees), scale saturation and brightness, and map palette colors;
+//! pixels near a source color move with it, so shading survives:
+//!
+//! ```toml
+//! [variants.midnight]
+//! hue = 200
+//! saturation = 0.6
+//! brightness = 0.8
+//!
+//! [variants.golden.palette]
+//! "#8b5a2b" = "#d4a017"
+//! ```
+//!
+//! `[states.<name>]` declares when each animation plays; see the `machine` module.
+//!
+//! Packs exported from Aseprite or TexturePacker point `atlas` at the JSON file
+//! instead. Their animations use `tag` (an Aseprite frame tag), `prefix` (frames
+//! whose name starts with it) or `frames` (atlas frame indices), and `frame_ms`
+//! becomes optional because Aseprite stores per-frame durations. Aseprite tags
+//! that match an animation name (ignoring case) are picked up without any entry:
+//!
+//! ```toml
+//! atlas = "dog.json"
+//!
+//! [animations.typing]
+//! tag = "Type"
+//! ```
+
+use super::error::SpriteError;
+use super::machine::{Machine, State};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Display name of the pack
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Sheet image, relative to the pack directory
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Grid layout of `image`, used by `frames`
+    #[serde(default)]
+    pub grid: Option<Grid>,
+    /// Aseprite or TexturePacker JSON, relative to the pack directory
+    #[serde(default)]
+    pub atlas: Option<String>,
+    #[serde(default)]
+    pub background: Background,
+    /// How frames are lined up, unless an animation says otherwise
+    #[serde(default)]
+    pub anchor: Anchor,
+    /// Which way the character looks in the drawing
+    #[serde(default)]
+    pub facing: Facing,
+    #[serde(default)]
+    pub animations: BTreeMap<String, AnimationSpec>,
+    /// Named recolorings of the pack
+    #[serde(default)]
+    pub variants: BTreeMap<String, Recolor>,
+    /// States added to or replacing the built-in ones (see `machine`)
+    #[serde(default)]
+    pub states: BTreeMap<String, State>,
+}
+
+/// Uniform grid of equally sized cells
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Grid {
+    pub columns: u32,
+    pub rows: u32,
+    /// Cell size in pixels (defaults to image size divided by columns/rows)
+    #[serde(default)]
+    pub frame_width: Option<u32>,
+    #[serde(default)]
+    pub frame_height: Option<u32>,
+}
+
+/// How to turn the drawing's background transparent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Background {
+    /// Keep the image as is (for images that already have alpha)
+    None,
+    /// Pixels with R, G and B all above `threshold` become transparent
+    Threshold {
+        #[serde(default = "default_threshold")]
+        threshold: u8,
+    },
+    /// Flood-fill from the frame edges through pixels close to the key color;
+    /// key-colored areas inside the outline are kept
+    Flood(Key),
+    /// Remove every pixel close to the key color (chroma key)
+    Key(Key),
+}
+
+fn default_threshold() -> u8 {
+    240
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Flood(Key::default())
+    }
+}
+
+/// Background color to remove and how softly to cut it out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Key {
+    /// `"#rrggbb"`, white by default
+    #[serde(default)]
+    pub color: Color,
+    /// How far each channel may be from `color` and still count as background
+    #[serde(default = "default_tolerance")]
+    pub tolerance: u8,
+    /// Fade the alpha in over this many pixels from the background
+    #[serde(default)]
+    pub feather: u8,
+    /// Pull the key color out of the edge pixels
+    #[serde(default)]
+    pub despill: bool,
+}
+
+fn default_tolerance() -> u8 {
+    16
+}
+
+impl Default for Key {
+    fn default() -> Self {
+        Self {
+            color: Color::default(),
+            tolerance: default_tolerance(),
+            feather: 0,
+            despill: false,
+        }
+    }
+}
+
+/// Color changes applied to every frame, in order: palette, hue, saturation, brightness
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Recolor {
+    /// Hue rotation in degrees
+    pub hue: f32,
+    /// Saturation multiplier (0 is grayscale)
+    pub saturation: f32,
+    /// Brightness multiplier
+    pub brightness: f32,
+    /// Source color to target color
+    pub palette: BTreeMap<Color, Color>,
+    /// How far (per channel) a pixel can be from a palette color and still follow it
+    pub tolerance: u8,
+}
+
+impl Default for Recolor {
+    fn default() -> Self {
+        Self {
+            hue: 0.0,
+            saturation: 1.0,
+            brightness: 1.0,
+            palette: BTreeMap::new(),
+            tolerance: 24,
+        }
+    }
+}
+
+impl Recolor {
+    /// Leaves every pixel as it is
+    pub fn is_identity(&self) -> bool {
+        self.hue.rem_euclid(360.0) == 0.0
+            && self.saturation == 1.0
+            && self.brightness == 1.0
+            && self.palette.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.hue.is_finite() {
+            return Err("`hue` must be a number of degrees".to_string());
+        }
+        for (field, value) in [
+            ("saturation", self.saturation),
+            ("brightness", self.brightness),
+        ] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(format!("`{}` must be zero or more", field));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// RGB color, written `"#rrggbb"` in the manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color(pub [u8; 3]);
+
+impl Default for Color {
+    fn default() -> Self {
+        Color([255, 255, 255])
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let hex = text.strip_prefix('#').unwrap_or(&text);
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+        };
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color([r, g, b])),
+            _ => Err(format!("invalid color {:?}, expected \"#rrggbb\"", text)),
+        }
+    }
+}
+
+/// Which way the character looks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Facing {
+    Left,
+    #[default]
+    Right,
+}
+
+impl Facing {
+    pub fn opposite(self) -> Self {
+        match self {
+            Facing::Left => Facing::Right,
+            Facing::Right => Facing::Left,
+        }
+    }
+}
+
+/// Which point of each frame stays put while an animation plays
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Anchor {
+    /// Center each frame's content on its own (the body jumps when a pose
+    /// changes the content's extent)
+    Center,
+    /// Keep frames where they were drawn and center them all as one
+    #[default]
+    Union,
+    /// Stand every frame's content on a shared baseline
+    Baseline,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationSpec {
+    /// Image to cut frames from (defaults to the pack's `image`)
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Grid cell indices, row-major (atlas frame indices for atlas packs)
+    #[serde(default)]
+    pub frames: Option<Vec<u32>>,
+    /// Explicit `[x, y, width, height]` rectangles
+    #[serde(default)]
+    pub rects: Option<Vec<[u32; 4]>>,
+    /// One image file per frame
+    #[serde(default)]
+    pub files: Option<Vec<String>>,
+    /// Animated GIF, APNG or WebP file
+    #[serde(default)]
+    pub animated: Option<String>,
+    /// Atlas frame tag (Aseprite)
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Atlas frames whose name starts with this (TexturePacker)
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// How long each frame is shown (overrides atlas durations)
+    #[serde(default)]
+    pub frame_ms: Option<u64>,
+    /// How frames are lined up (defaults to the pack's `anchor`)
+    #[serde(default)]
+    pub anchor: Option<Anchor>,
+    /// One `[x, y]` anchor point per frame, in the frame's own pixels
+    #[serde(default)]
+    pub anchors: Option<Vec<[u32; 2]>>,
+    /// How long each frame is shown, one entry per frame (overrides `frame_ms`)
+    #[serde(default)]
+    pub durations: Option<Vec<u64>>,
+    #[serde(default)]
+    pub playback: Playback,
+    /// Relative chance of each frame coming up next (`random` playback only)
+    #[serde(default)]
+    pub weights: Option<Vec<u32>>,
+}
+
+/// Order in which an animation's frames are played
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Playback {
+    /// 0, 1, 2, 0, 1, 2, ...
+    #[default]
+    Loop,
+    /// 0, 1, 2, 1, 0, 1, ...
+    PingPong,
+    /// Play through once and stay on the last frame
+    OnceHold,
+    /// Play through once, then go back to the first frame and stay there
+    OnceReturn,
+    /// Pick each next frame at random, by `weights`
+    Random,
+}
+
+/// Where an animation's frames come from, once the manifest is validated
+#[derive(Debug, Clone)]
+pub enum FrameSource<'a> {
+    Grid {
+        image: &'a str,
+        grid: Grid,
+        cells: &'a [u32],
+    },
+    Rects {
+        image: &'a str,
+        rects: &'a [[u32; 4]],
+    },
+    Files(&'a [String]),
+    /// Every frame of an animated image
+    Animated(&'a str),
+    /// Frames resolved from the pack's atlas
+    Atlas(AtlasSelector<'a>),
+}
+
+/// Which atlas frames an animation uses
+#[derive(Debug, Clone, Copy)]
+pub enum AtlasSelector<'a> {
+    Indices(&'a [u32]),
+    Tag(&'a str),
+    Prefix(&'a str),
+}
+
+impl<'a> FrameSource<'a> {
+    /// Every still image this source reads from (animated and atlas images are
+    /// decoded separately)
+    pub fn images(&self) -> Vec<&'a str> {
+        match self {
+            FrameSource::Grid { image, .. } | FrameSource::Rects { image, .. } => vec![image],
+            FrameSource::Files(files) => files.iter().map(String::as_str).collect(),
+            FrameSource::Animated(_) | FrameSource::Atlas(_) => Vec::new(),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Self, SpriteError> {
+        let manifest: Manifest = toml::from_str(text).map_err(SpriteError::Manifest)?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    fn validate(&self) -> Result<(), SpriteError> {
+        if let Some(grid) = self.grid {
+            if grid.frame_width == Some(0) || grid.frame_height == Some(0) {
+                return Err(SpriteError::Invalid(
+                    "grid frames must have a non-zero size".to_string(),
+                ));
+            }
+        }
+        for (name, spec) in &self.animations {
+            self.source(name, spec)?;
+        }
+        for (name, recolor) in &self.variants {
+            recolor.validate().map_err(|reason| {
+                SpriteError::Invalid(format!("variant `{}`: {}", name, reason))
+            })?;
+        }
+        // Animations are checked once they're loaded
+        self.machine()
+            .validate(|_| true)
+            .map_err(SpriteError::Invalid)?;
+        Ok(())
+    }
+
+    /// The built-in states with the pack's own
+    pub fn machine(&self) -> Machine {
+        Machine::with_states(&self.states)
+    }
+
+    /// A named variant of the pack
+    pub fn variant(&self, name: &str) -> Result<&Recolor, SpriteError> {
+        self.variants
+            .get(name)
+            .ok_or_else(|| SpriteError::Invalid(format!("no variant named `{}`", name)))
+    }
+
+    /// Resolve where an animation's frames come from
+    pub fn source<'a>(
+        &'a self,
+        name: &str,
+        spec: &'a AnimationSpec,
+    ) -> Result<FrameSource<'a>, SpriteError> {
+        let invalid =
+            |reason: &str| SpriteError::Invalid(format!("animation `{}`: {}", name, reason));
+        let image = spec.image.as_deref().or(self.image.as_deref());
+
+        let needs_atlas = |source| {
+            if self.atlas.is_some() {
+                Ok(source)
+            } else {
+                Err(invalid("`tag` and `prefix` need an `atlas`"))
+            }
+        };
+
+        let source = match (
+            &spec.frames,
+            &spec.rects,
+            &spec.files,
+            &spec.animated,
+            &spec.tag,
+            &spec.prefix,
+        ) {
+            (Some(indices), None, None, None, None, None)
+                if self.grid.is_none() && self.atlas.is_some() =>
+            {
+                FrameSource::Atlas(AtlasSelector::Indices(indices))
+            }
+            (Some(cells), None, None, None, None, None) => {
+                let grid = self
+                    .grid
+                    .ok_or_else(|| invalid("`frames` needs a [grid] section"))?;
+                let image = image.ok_or_else(|| invalid("`frames` needs an image"))?;
+                if grid.columns == 0 || grid.rows == 0 {
+                    return Err(invalid("grid must have at least one column and row"));
+                }
+                // A grid too big to count has room for any cell
+                let outside = |&&cell: &&u32| {
+                    grid.columns
+                        .checked_mul(grid.rows)
+                        .is_some_and(|count| cell >= count)
+                };
+                if let Some(cell) = cells.iter().find(outside) {
+                    return Err(invalid(&format!(
+                        "frame {} is outside the {}x{} grid",
+                        cell, grid.columns, grid.rows
+                    )));
+                }
+                FrameSource::Grid { image, grid, cells }
+            }
+            (None, Some(rects), None, None, None, None) => {
+                let image = image.ok_or_else(|| invalid("`rects` needs an image"))?;
+                if rects.iter().any(|r| r[2] == 0 || r[3] == 0) {
+                    return Err(invalid("rects must have a non-zero size"));
+                }
+                FrameSource::Rects { image, rects }
+            }
+            (None, None, Some(files), None, None, None) => FrameSource::Files(files),
+            (None, None, None, Some(file), None, None) => FrameSource::Animated(file),
+            (None, None, None, None, Some(tag), None) => {
+                needs_atlas(FrameSource::Atlas(AtlasSelector::Tag(tag)))?
+            }
+            (None, None, None, None, None, Some(prefix)) => {
+                needs_atlas(FrameSource::Atlas(AtlasSelector::Prefix(prefix)))?
+            }
+            _ => return Err(invalid(
+                "needs exactly one of `frames`, `rects`, `files`, `animated`, `tag` or `prefix`",
+            )),
+        };
+
+        let empty = match &source {
+            FrameSource::Grid { cells, .. } => cells.is_empty(),
+            FrameSource::Rects { rects, .. } => rects.is_empty(),
+            FrameSource::Files(files) => files.is_empty(),
+            FrameSource::Atlas(AtlasSelector::Indices(indices)) => indices.is_empty(),
+            // Checked once the file is decoded
+            FrameSource::Animated(_) | FrameSource::Atlas(_) => false,
+        };
+        if empty {
+            return Err(invalid("has no frames"));
+        }
+        let has_timing = matches!(source, FrameSource::Animated(_) | FrameSource::Atlas(_));
+        if spec.frame_ms.is_none() && spec.durations.is_none() && !has_timing {
+            return Err(invalid("needs `frame_ms` or `durations`"));
+        }
+        if spec.anchor.is_some() && spec.anchors.is_some() {
+            return Err(invalid("has both `anchor` and `anchors`"));
+        }
+        if spec.weights.is_some() && spec.playback != Playback::Random {
+            return Err(invalid("`weights` needs playback = \"random\""));
+        }
+        Ok(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACK: &str = r##"
+        name = "dog"
+        image = "sheet.png"
+        anchor = "baseline"
+        facing = "left"
+
+        [grid]
+        columns = 4
+        rows = 2
+
+        [background]
+        mode = "key"
+        color = "#00c800"
+        tolerance = 40
+
+        [animations.idle]
+        frames = [0, 1, 7]
+        frame_ms = 500
+
+        [animations.typing]
+        rects = [[0, 0, 16, 16]]
+        durations = [100]
+        playback = "ping-pong"
+
+        [variants.night.palette]
+        "#8b5a2b" = "#202040"
+    "##;
+
+    /// Why `text` is rejected, given the `idle` animation the built-in states need
+    fn error(text: &str) -> String {
+        let idle = "[animations.idle]\nfiles = [\"idle.png\"]\nframe_ms = 100\n";
+        match Manifest::parse(&format!("{}\n{}", text, idle)) {
+            Ok(_) => panic!("accepted:\n{}", text),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[track_caller]
+    fn assert_rejected(text: &str, reason: &str) {
+        let err = error(text);
+        assert!(err.contains(reason), "expected `{}`, got `{}`", reason, err);
+    }
+
+    #[test]
+    fn parses_a_pack() {
+        let manifest = Manifest::parse(PACK).unwrap();
+        assert_eq!(manifest.name.as_deref(), Some("dog"));
+        assert_eq!(manifest.anchor, Anchor::Baseline);
+        assert_eq!(manifest.facing, Facing::Left);
+        assert_eq!(
+            manifest.background,
+            Background::Key(Key {
+                color: Color([0, 200, 0]),
+                tolerance: 40,
+                feather: 0,
+                despill: false,
+            })
+        );
+        let night = manifest.variant("night").unwrap();
+        assert_eq!(
+            night.palette[&Color([0x8b, 0x5a, 0x2b])],
+            Color([0x20, 0x20, 0x40])
+        );
+        assert!(manifest.variant("day").is_err());
+
+        let idle = &manifest.animations["idle"];
+        match manifest.source("idle", idle).unwrap() {
+            FrameSource::Grid { image, grid, cells } => {
+                assert_eq!(image, "sheet.png");
+                assert_eq!((grid.columns, grid.rows), (4, 2));
+                assert_eq!(cells, [0, 1, 7]);
+            }
+            other => panic!("{:?}", other),
+        }
+        let typing = &manifest.animations["typing"];
+        assert_eq!(typing.playback, Playback::PingPong);
+        assert!(matches!(
+            manifest.source("typing", typing).unwrap(),
+            FrameSource::Rects {
+                image: "sheet.png",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn defaults() {
+        let manifest = Manifest::parse("[animations.idle]\nanimated = \"idle.gif\"\n").unwrap();
+        assert_eq!(manifest.anchor, Anchor::Union);
+        assert_eq!(manifest.facing, Facing::Right);
+        assert_eq!(manifest.background, Background::Flood(Key::default()));
+        assert_eq!(Key::default().color, Color([255, 255, 255]));
+        assert_eq!(manifest.animations["idle"].playback, Playback::Loop);
+    }
+
+    #[test]
+    fn rejects_malformed_manifests() {
+        assert!(matches!(
+            Manifest::parse("nmae = \"dog\""),
+            Err(SpriteError::Manifest(_))
+        ));
+        assert_rejected(
+            "[background]\nmode = \"key\"\ncolor = \"green\"",
+            "invalid color",
+        );
+        assert_rejected(
+            "[background]\nmode = \"key\"\ncolor = \"#12345\"",
+            "invalid color",
+        );
+        assert_rejected(
+            "[animations.run]\nframes = [0]\nframe_ms = 1\nspeed = 2",
+            "unknown field",
+        );
+    }
+
+    #[test]
+    fn rejects_impossible_grids() {
+        let grid = |grid: &str, frames: &str| {
+            format!(
+                "image = \"sheet.png\"\n[grid]\n{}\n[animations.run]\nframes = {}\nframe_ms = 100",
+                grid, frames
+            )
+        };
+        assert_rejected(
+            &grid("columns = 4\nrows = 2\nframe_width = 0", "[0]"),
+            "grid frames must have a non-zero size",
+        );
+        assert_rejected(
+            &grid("columns = 4\nrows = 2\nframe_height = 0", "[0]"),
+            "grid frames must have a non-zero size",
+        );
+        assert_rejected(
+            &grid("columns = 0\nrows = 2", "[0]"),
+            "grid must have at least one column and row",
+        );
+        assert_rejected(
+            &grid("columns = 4\nrows = 2", "[8]"),
+            "frame 8 is outside the 4x2 grid",
+        );
+        // Too many cells to count doesn't overflow; every cell fits
+        Manifest::parse(&format!(
+            "{}\n[animations.idle]\nframes = [0]\nframe_ms = 1",
+            grid("columns = 65536\nrows = 65536", "[4294967295]")
+        ))
+        .unwrap();
+
+        assert_rejected(
+            "image = \"sheet.png\"\n[animations.run]\nframes = [0]\nframe_ms = 100",
+            "`frames` needs a [grid] section",
+        );
+        assert_rejected(
+            "[grid]\ncolumns = 1\nrows = 1\n[animations.run]\nframes = [0]\nframe_ms = 100",
+            "`frames` needs an image",
+        );
+    }
+
+    #[test]
+    fn rejects_impossible_animations() {
+        let run = |spec: &str| format!("image = \"sheet.png\"\n[animations.run]\n{}", spec);
+        assert_rejected(
+            &run("rects = [[0, 0, 0, 16]]\nframe_ms = 100"),
+            "animation `run`: rects must have a non-zero size",
+        );
+        assert_rejected(
+            "[animations.run]\nrects = [[0, 0, 16, 16]]\nframe_ms = 100",
+            "`rects` needs an image",
+        );
+        assert_rejected(&run("tag = \"Run\""), "`tag` and `prefix` need an `atlas`");
+        assert_rejected(
+            &run("prefix = \"run\""),
+            "`tag` and `prefix` need an `atlas`",
+        );
+        assert_rejected(
+            &run("files = [\"a.png\"]\nanimated = \"a.gif\""),
+            "needs exactly one of",
+        );
+        assert_rejected(&run("frame_ms = 100"), "needs exactly one of");
+        assert_rejected(&run("files = []\nframe_ms = 100"), "has no frames");
+        assert_rejected(
+            &run("files = [\"a.png\"]"),
+            "needs `frame_ms` or `durations`",
+        );
+        assert_rejected(
+            &run("files = [\"a.png\"]\nframe_ms = 100\nanchor = \"center\"\nanchors = [[0, 0]]"),
+            "has both `anchor` and `anchors`",
+        );
+        assert_rejected(
+            &run("files = [\"a.png\"]\nframe_ms = 100\nweights = [1]"),
+            "`weights` needs playback = \"random\"",
+        );
+        // Atlas packs have their own timing and can pick atlas frames by index
+        Manifest::parse(
+            "atlas = \"dog.json\"\n[animations.idle]\ntag = \"Idle\"\n[animations.run]\nframes = [0]",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_variants_and_states() {
+        assert_rejected(
+            "[variants.gray]\nsaturation = -1",
+            "variant `gray`: `saturation` must be zero or more",
+        );
+        assert_rejected(
+            "[variants.dim]\nbrightness = nan",
+            "variant `dim`: `brightness` must be zero or more",
+        );
+        assert_rejected(
+            "[states.idle]\nanimation = \"idle\"\n[[states.idle.transitions]]\nto = \"nowhere\"",
+            "state `idle`: transition to unknown state `nowhere`",
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Spotting error-heavy typing, so the character can look confused along with you
+//!
+//! Three patterns count: a lot of backspacing among recent keys, typing the same
+//! word and deleting it again, and one long run of deletes. Once one is spotted
+//! the detector starts over and stays quiet for a while, so a bad patch gets one
+//! reaction rather than one per key.
+
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Keys further back than this don't count toward the backspace ratio
+const WINDOW: Duration = Duration::from_secs(10);
+/// Deleted words further back than this aren't compared with new ones
+const ATTEMPT_WINDOW: Duration = Duration::from_secs(30);
+/// Quiet time after a reaction
+const COOLDOWN: Duration = Duration::from_secs(20);
+/// Shorter deleted words are too common to mean anything
+const MIN_ATTEMPT_CHARS: usize = 2;
+
+/// How readily typing counts as error-heavy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sensitivity {
+    /// Never react
+    Off,
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+/// Limits for one sensitivity
+struct Thresholds {
+    /// Share of recent keys that are backspaces
+    ratio: f32,
+    /// Recent keys needed before the ratio counts
+    min_keys: usize,
+    /// Backspaces in a row
+    delete_run: usize,
+    /// Times the same word was typed and then deleted
+    retypes: usize,
+}
+
+impl Sensitivity {
+    fn thresholds(self) -> Option<Thresholds> {
+        let (ratio, min_keys, delete_run, retypes) = match self {
+            Sensitivity::Off => return None,
+            Sensitivity::Low => (0.5, 16, 16, 3),
+            Sensitivity::Medium => (0.35, 12, 10, 2),
+            Sensitivity::High => (0.25, 8, 6, 2),
+        };
+        Some(Thresholds {
+            ratio,
+            min_keys,
+            delete_run,
+            retypes,
+        })
+    }
+}
+
+/// A key as far as mistakes go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stroke<'a> {
+    Text(&'a str),
+    Backspace,
+    /// Enter, arrows and the like: a word is over
+    Special,
+}
+
+/// Which pattern was spotted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mistake {
+    /// Backspaces made up too much of the recent keys
+    Backspaces,
+    /// The same word was typed and deleted again
+    Retyping(String),
+    /// A long run of deletes
+    DeleteRun,
+}
+
+/// Watches keys for error-heavy typing
+#[derive(Debug, Clone)]
+pub struct MistakeDetector {
+    sensitivity: Sensitivity,
+    /// Recent keys and whether each was a backspace
+    recent: VecDeque<(Instant, bool)>,
+    /// The word being typed
+    word: String,
+    /// Backspaces since the last other key
+    delete_run: usize,
+    /// Words as they were when deleting into them began
+    attempts: VecDeque<(Instant, String)>,
+    quiet_until: Option<Instant>,
+}
+
+impl MistakeDetector {
+    pub fn new(sensitivity: Sensitivity) -> Self {
+        Self {
+            sensitivity,
+            recent: VecDeque::new(),
+            word: String::new(),
+            delete_run: 0,
+            attempts: VecDeque::new(),
+            quiet_until: None,
+        }
+    }
+
+    /// Count a key; returns the pattern if this key completed one
+    pub fn on_key(&mut self, stroke: Stroke, now: Instant) -> Option<Mistake> {
+        let thresholds = self.sensitivity.thresholds()?;
+        while self
+            .recent
+            .front()
+            .is_some_and(|&(at, _)| now.duration_since(at) > WINDOW)
+        {
+            self.recent.pop_front();
+        }
+        while self
+            .attempts
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > ATTEMPT_WINDOW)
+        {
+            self.attempts.pop_front();
+        }
+        self.recent.push_back((now, stroke == Stroke::Backspace));
+
+        let mut retyped = None;
+        match stroke {
+            Stroke::Text(text) if text.trim().is_empty() => self.end_word(),
+            Stroke::Text(text) => {
+                self.word.push_str(text);
+                self.delete_run = 0;
+            }
+            Stroke::Backspace => {
+                if self.delete_run == 0 && self.word.chars().count() >= MIN_ATTEMPT_CHARS {
+                    let tries = 1 + self
+                        .attempts
+                        .iter()
+                        .filter(|(_, w)| *w == self.word)
+                        .count();
+                    if tries >= thresholds.retypes {
+                        retyped = Some(self.word.clone());
+                    }
+                    self.attempts.push_back((now, self.word.clone()));
+                }
+                self.word.pop();
+                self.delete_run += 1;
+            }
+            Stroke::Special => self.end_word(),
+        }
+
+        if self.quiet_until.is_some_and(|until| now < until) {
+            return None;
+        }
+        let backspaces = self
+            .recent
+            .iter()
+            .filter(|(_, backspace)| *backspace)
+            .count();
+        let mistake = if let Some(word) = retyped {
+            Mistake::Retyping(word)
+        } else if self.delete_run >= thresholds.delete_run {
+            Mistake::DeleteRun
+        } else if self.recent.len() >= thresholds.min_keys
+            && backspaces as f32 >= self.recent.len() as f32 * thresholds.ratio
+        {
+            Mistake::Backspaces
+        } else {
+            return None;
+        };
+
+        // Start over, so the next reaction needs fresh mistakes
+        *self = Self {
+            quiet_until: Some(now + COOLDOWN),
+            ..Self::new(self.sensitivity)
+        };
+        Some(mistake)
+    }
+
+    fn end_word(&mut self) {
+        self.word.clear();
+        self.delete_run = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `keys` (`⌫` is a backspace, `⏎` a special key) `gap_ms` apart from
+    /// `start_ms`, returning each mistake with the time it was spotted
+    fn feed(
+        detector: &mut MistakeDetector,
+        start: Instant,
+        start_ms: u64,
+        keys: &str,
+        gap_ms: u64,
+    ) -> Vec<(u64, Mistake)> {
+        let mut found = Vec::new();
+        for (i, key) in keys.chars().enumerate() {
+            let at_ms = start_ms + i as u64 * gap_ms;
+            let key = key.to_string();
+            let stroke = match key.as_str() {
+                "⌫" => Stroke::Backspace,
+                "⏎" => Stroke::Special,
+                text => Stroke::Text(text),
+            };
+            if let Some(mistake) = detector.on_key(stroke, start + Duration::from_millis(at_ms)) {
+                found.push((at_ms, mistake));
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn careful_typing_goes_unnoticed() {
+        let start = Instant::now();
+        let mut detector = MistakeDetector::new(Sensitivity::High);
+        // A typo fixed now and then
+        let text = "the quick brown fox jumsp⌫⌫ps over the lazy dgo⌫⌫og⏎and teh⌫⌫he cat";
+        assert_eq!(feed(&mut detector, start, 0, text, 150), vec![]);
+    }
+
+    #[test]
+    fn long_delete_runs_are_spotted() {
+        let start = Instant::now();
+        let mut detector = MistakeDetector::new(Sensitivity::Medium);
+        let text = "a much longer sentence here⌫⌫⌫⌫⌫⌫⌫⌫⌫⌫⌫⌫";
+        assert_eq!(
+            feed(&mut detector, start, 0, text, 100),
+            vec![(3600, Mistake::DeleteRun)]
+        );
+    }
+
+    #[test]
+    fn backspace_heavy_windows_are_spotted() {
+        let start = Instant::now();
+        let mut detector = MistakeDetector::new(Sensitivity::Medium);
+        // Every other correction in a short burst: 5 of 13 keys are backspaces
+        let found = feed(&mut detector, start, 0, "ab⌫c⌫de⌫f⌫gh⌫i", 200);
+        assert_eq!(found, vec![(2400, Mistake::Backspaces)]);
+
+        // Spread out over more than the window, the same keys are fine
+        let mut detector = MistakeDetector::new(Sensitivity::Medium);
+        assert_eq!(
+            feed(&mut detector, start, 0, "ab⌫c⌫de⌫f⌫gh⌫i", 1000),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn retyping_the_same_word_is_spotted() {
+        let start = Instant::now();
+        let mut detector = MistakeDetector::new(Sensitivity::Medium);
+        let text = "recieve⌫⌫⌫⌫ieve⌫";
+        let found = feed(&mut detector, start, 0, text, 300);
+        assert_eq!(found, vec![(4500, Mistake::Retyping("recieve".into()))]);
+
+        // Low sensitivity wants a third go
+        let mut detector = MistakeDetector::new(Sensitivity::Low);
+        assert_eq!(feed(&mut detector, start, 0, text, 300), vec![]);
+    }
+
+    #[test]
+    fn reactions_cool_down_and_can_be_turned_off() {
+        let start = Instant::now();
+        let deletes = "⌫".repeat(12);
+        let mut detector = MistakeDetector::new(Sensitivity::Medium);
+        assert_eq!(feed(&mut detector, start, 0, &deletes, 100).len(), 1);
+        // Still quiet 10 s later, not after 20
+        assert_eq!(feed(&mut detector, start, 10_000, &deletes, 100), vec![]);
+        assert_eq!(feed(&mut detector, start, 30_000, &deletes, 100).len(), 1);
+
+        let mut detector = MistakeDetector::new(Sensitivity::Off);
+        assert_eq!(
+            feed(&mut detector, start, 0, &deletes.repeat(3), 100),
+            vec![]
+        );
+    }
+}
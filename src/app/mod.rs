@@ -1,8 +1,37 @@
+pub mod anchor;
+pub mod atlas;
+pub mod background;
+pub mod cache;
+pub mod clock;
+pub mod config;
+pub mod doctor;
+pub mod emote;
+pub mod error;
+pub mod frame;
+pub mod graphics;
 pub mod keys;
+pub mod log;
+pub mod machine;
+pub mod manifest;
+pub mod mistakes;
+pub mod mood;
+pub mod playback;
+pub mod preview;
+pub mod recolor;
+#[cfg(test)]
+mod scenario;
+pub mod signals;
+pub mod sixel;
+pub mod sprite_check;
 pub mod spritesheet;
 pub mod state;
+pub mod tempo;
 pub mod terminal;
+#[cfg(test)]
+mod testing;
+pub mod text;
 pub mod ui;
+pub mod widget;
 
 // Re-export commonly used items
 pub use state::App;
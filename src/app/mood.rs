@@ -0,0 +1,281 @@
+//! The dog's mood, kept between sessions
+//!
+//! Three levels from 0 to 1. Happiness rises while you type and slowly fades
+//! otherwise; energy drains over long typing stretches and comes back during
+//! breaks; boredom grows while nobody types (between sessions too) and goes away
+//! quickly once typing starts. The state machine reads them as gauges, to pick idle
+//! variants and the greeting at startup.
+//!
+//! The levels are saved to `$XDG_STATE_HOME/tapwatch/mood.json` (next to the log)
+//! with the time they were saved, so the absence counts as a break on next start.
+
+use super::error::MoodError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MOOD_FILE_NAME: &str = "mood.json";
+
+/// Boredom above this gets an excited greeting (matches the built-in states)
+const EXCITED_BOREDOM: f32 = 0.6;
+/// Energy below this gets a sleepy greeting
+const TIRED_ENERGY: f32 = 0.25;
+/// Happiness above this gets a cheerful greeting
+const HAPPY: f32 = 0.75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Mood {
+    pub happiness: f32,
+    pub energy: f32,
+    pub boredom: f32,
+}
+
+impl Default for Mood {
+    fn default() -> Self {
+        Self {
+            happiness: 0.5,
+            energy: 1.0,
+            boredom: 0.0,
+        }
+    }
+}
+
+/// How fast each level moves, per hour
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MoodRates {
+    pub happiness_gain: f32,
+    pub happiness_decay: f32,
+    pub energy_drain: f32,
+    pub energy_recovery: f32,
+    pub boredom_growth: f32,
+    pub boredom_relief: f32,
+}
+
+impl Default for MoodRates {
+    fn default() -> Self {
+        Self {
+            happiness_gain: 0.5,
+            happiness_decay: 0.05,
+            energy_drain: 0.3,
+            energy_recovery: 0.5,
+            boredom_growth: 0.1,
+            boredom_relief: 12.0,
+        }
+    }
+}
+
+impl MoodRates {
+    pub fn validate(&self) -> Result<(), String> {
+        let rates = [
+            ("happiness_gain", self.happiness_gain),
+            ("happiness_decay", self.happiness_decay),
+            ("energy_drain", self.energy_drain),
+            ("energy_recovery", self.energy_recovery),
+            ("boredom_growth", self.boredom_growth),
+            ("boredom_relief", self.boredom_relief),
+        ];
+        match rates
+            .iter()
+            .find(|(_, rate)| !(rate.is_finite() && *rate >= 0.0))
+        {
+            Some((name, rate)) => Err(format!("{} must be 0 or more, not {}", name, rate)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// What the user was up to over a stretch of time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    Typing,
+    /// Not typing, or not even here
+    Break,
+}
+
+impl Mood {
+    /// Move the levels along for `elapsed` spent on `activity`
+    pub fn update(&mut self, elapsed: Duration, activity: Activity, rates: &MoodRates) {
+        let hours = elapsed.as_secs_f32() / 3600.0;
+        let step = |level: &mut f32, per_hour: f32| {
+            *level = (*level + per_hour * hours).clamp(0.0, 1.0);
+        };
+        match activity {
+            Activity::Typing => {
+                step(&mut self.happiness, rates.happiness_gain);
+                step(&mut self.energy, -rates.energy_drain);
+                step(&mut self.boredom, -rates.boredom_relief);
+            }
+            Activity::Break => {
+                step(&mut self.happiness, -rates.happiness_decay);
+                step(&mut self.energy, rates.energy_recovery);
+                step(&mut self.boredom, rates.boredom_growth);
+            }
+        }
+    }
+
+    /// What the dog says when the app starts
+    pub fn greeting(&self) -> &'static str {
+        if self.boredom > EXCITED_BOREDOM {
+            "HI!!"
+        } else if self.energy < TIRED_ENERGY {
+            "yawn"
+        } else if self.happiness > HAPPY {
+            "hi!"
+        } else {
+            "hi"
+        }
+    }
+}
+
+/// Where the mood is kept between sessions
+pub fn path() -> Option<PathBuf> {
+    let base = dirs::state_dir().or_else(dirs::cache_dir)?;
+    Some(base.join("tapwatch").join(MOOD_FILE_NAME))
+}
+
+#[derive(Serialize, Deserialize)]
+struct Saved {
+    mood: Mood,
+    /// Seconds since the Unix epoch
+    saved_at: u64,
+}
+
+/// The saved mood, moved along for the time since it was saved (a default mood if
+/// nothing was saved yet)
+pub fn load(path: &Path, now: SystemTime, rates: &MoodRates) -> Result<Mood, MoodError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Mood::default()),
+        Err(err) => return Err(MoodError::Read(path.to_path_buf(), err)),
+    };
+    let saved: Saved =
+        serde_json::from_str(&text).map_err(|err| MoodError::Parse(path.to_path_buf(), err))?;
+    let mut mood = saved.mood;
+    let away = now
+        .duration_since(UNIX_EPOCH + Duration::from_secs(saved.saved_at))
+        .unwrap_or_default();
+    mood.update(away, Activity::Break, rates);
+    Ok(mood)
+}
+
+pub fn save(path: &Path, mood: &Mood, now: SystemTime) -> Result<(), MoodError> {
+    let saved = Saved {
+        mood: *mood,
+        saved_at: now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+    };
+    let write = || {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&saved)?)
+    };
+    write().map_err(|err| MoodError::Write(path.to_path_buf(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn typing_cheers_up_and_tires_out_and_breaks_restore() {
+        let rates = MoodRates::default();
+        let mut mood = Mood {
+            boredom: 0.5,
+            ..Mood::default()
+        };
+        // A two-hour marathon, in one-second steps
+        for _ in 0..2 * 3600 {
+            mood.update(Duration::from_secs(1), Activity::Typing, &rates);
+        }
+        assert!((mood.happiness - 1.0).abs() < 1e-3);
+        assert!((mood.energy - 0.4).abs() < 1e-2, "energy {}", mood.energy);
+        assert_eq!(mood.boredom, 0.0);
+        assert_eq!(mood.greeting(), "hi!");
+
+        mood.update(HOUR, Activity::Break, &rates);
+        assert!((mood.energy - 0.9).abs() < 1e-2);
+        assert!((mood.happiness - 0.95).abs() < 1e-3);
+        assert!((mood.boredom - 0.1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn long_absences_get_an_excited_greeting() {
+        let rates = MoodRates::default();
+        let mut mood = Mood::default();
+        assert_eq!(mood.greeting(), "hi");
+        mood.update(HOUR * 5, Activity::Break, &rates);
+        assert_eq!(mood.greeting(), "hi");
+        mood.update(HOUR * 2, Activity::Break, &rates);
+        assert_eq!(mood.greeting(), "HI!!");
+
+        let tired = Mood {
+            energy: 0.1,
+            ..Mood::default()
+        };
+        assert_eq!(tired.greeting(), "yawn");
+    }
+
+    #[test]
+    fn rates_are_tunable() {
+        let lazy = MoodRates {
+            boredom_growth: 0.0,
+            energy_recovery: 2.0,
+            ..MoodRates::default()
+        };
+        let mut mood = Mood {
+            energy: 0.0,
+            ..Mood::default()
+        };
+        mood.update(HOUR / 2, Activity::Break, &lazy);
+        assert_eq!(mood.boredom, 0.0);
+        assert_eq!(mood.energy, 1.0);
+
+        assert!(lazy.validate().is_ok());
+        let backwards = MoodRates {
+            energy_drain: -1.0,
+            ..MoodRates::default()
+        };
+        assert!(backwards.validate().unwrap_err().contains("energy_drain"));
+    }
+
+    #[test]
+    fn saved_mood_comes_back_older() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(MOOD_FILE_NAME);
+        let rates = MoodRates::default();
+        let saved_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        // Nothing saved yet
+        assert_eq!(load(&path, saved_at, &rates).unwrap(), Mood::default());
+
+        let mood = Mood {
+            happiness: 0.9,
+            energy: 0.2,
+            boredom: 0.0,
+        };
+        save(&path, &mood, saved_at).unwrap();
+        assert_eq!(load(&path, saved_at, &rates).unwrap(), mood);
+        // A night away: rested, a bit bored
+        let morning = load(&path, saved_at + HOUR * 8, &rates).unwrap();
+        assert!((morning.boredom - 0.8).abs() < 1e-3);
+        assert_eq!(morning.energy, 1.0);
+        assert_eq!(morning.greeting(), "HI!!");
+        // A clock that went backwards isn't an absence
+        assert_eq!(load(&path, saved_at - HOUR, &rates).unwrap(), mood);
+
+        fs::write(&path, "not json").unwrap();
+        assert!(matches!(
+            load(&path, saved_at, &rates),
+            Err(MoodError::Parse(..))
+        ));
+    }
+}
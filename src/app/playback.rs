@@ -0,0 +1,248 @@
+//! Stepping through an animation's frames as its playback mode says
+
+use super::manifest::Playback;
+use super::spritesheet::Animation;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+/// Shown for a frame when the animation has no timing for it
+const FALLBACK_FRAME_DURATION: Duration = Duration::from_millis(100);
+
+/// Playback position within one animation
+#[derive(Debug, Clone)]
+pub struct Player {
+    /// Frame currently shown
+    pub frame: usize,
+    /// When the current frame came up
+    pub frame_started: Instant,
+    /// Ping-pong direction
+    reversing: bool,
+    /// A play-once animation has reached its end
+    finished: bool,
+}
+
+impl Player {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            frame: 0,
+            frame_started: now,
+            reversing: false,
+            finished: false,
+        }
+    }
+
+    /// Start over from `frame`
+    pub fn restart(&mut self, now: Instant, frame: usize) {
+        *self = Self::new(now);
+        self.frame = frame;
+    }
+
+    /// Whether a `once-hold` or `once-return` animation has played through
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Move to the next frame once the current one has been up long enough
+    /// Returns whether the frame changed
+    pub fn update(
+        &mut self,
+        animation: Option<&Animation>,
+        now: Instant,
+        rng: &mut impl Rng,
+    ) -> bool {
+        self.update_at_speed(animation, now, 1.0, rng)
+    }
+
+    /// Like `update`, with frames shown for their duration divided by `speed`
+    pub fn update_at_speed(
+        &mut self,
+        animation: Option<&Animation>,
+        now: Instant,
+        speed: f32,
+        rng: &mut impl Rng,
+    ) -> bool {
+        let Some(animation) = animation else {
+            return false;
+        };
+        if self.finished || animation.frames.is_empty() {
+            return false;
+        }
+
+        let duration = match animation.frame_durations.len() {
+            0 => FALLBACK_FRAME_DURATION,
+            n => animation.frame_durations[self.frame % n],
+        };
+        let shown = now.duration_since(self.frame_started).as_secs_f64() * speed as f64;
+        if shown < duration.as_secs_f64() {
+            return false;
+        }
+        self.advance(animation, now, rng)
+    }
+
+    /// Move to the next frame right away, whatever the timing
+    /// Returns whether the frame changed
+    pub fn advance(&mut self, animation: &Animation, now: Instant, rng: &mut impl Rng) -> bool {
+        if self.finished || animation.frames.is_empty() {
+            return false;
+        }
+        self.frame_started = now;
+        let previous = self.frame;
+        self.frame = self.next_frame(animation, rng);
+        self.frame != previous
+    }
+
+    fn next_frame(&mut self, animation: &Animation, rng: &mut impl Rng) -> usize {
+        let count = animation.frames.len();
+        let frame = self.frame % count;
+        let last = count - 1;
+
+        match animation.playback {
+            Playback::Loop => (frame + 1) % count,
+            Playback::PingPong if count == 1 => 0,
+            Playback::PingPong => {
+                if frame == last {
+                    self.reversing = true;
+                } else if frame == 0 {
+                    self.reversing = false;
+                }
+                if self.reversing {
+                    frame - 1
+                } else {
+                    frame + 1
+                }
+            }
+            Playback::OnceHold | Playback::OnceReturn if frame < last => frame + 1,
+            Playback::OnceHold => {
+                self.finished = true;
+                frame
+            }
+            Playback::OnceReturn => {
+                self.finished = true;
+                0
+            }
+            Playback::Random => match WeightedIndex::new(&animation.weights) {
+                Ok(weights) => weights.sample(rng),
+                // No weights (or all zero): every frame is equally likely
+                Err(_) => rng.gen_range(0..count),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::frame::Frame;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const STEP: Duration = Duration::from_millis(100);
+
+    fn animation(frames: usize, playback: Playback) -> Animation {
+        Animation {
+            frames: (0..frames)
+                .map(|_| Frame::new(image::RgbaImage::new(1, 1)))
+                .collect(),
+            frame_durations: vec![STEP; frames],
+            playback,
+            weights: Vec::new(),
+        }
+    }
+
+    /// Frames shown over `steps` frame durations, starting with the first
+    fn play(animation: &Animation, steps: usize) -> Vec<usize> {
+        let start = Instant::now();
+        let mut player = Player::new(start);
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut shown = vec![player.frame];
+        for step in 1..=steps {
+            player.update(Some(animation), start + STEP * step as u32, &mut rng);
+            shown.push(player.frame);
+        }
+        shown
+    }
+
+    #[test]
+    fn loop_wraps_around() {
+        assert_eq!(
+            play(&animation(3, Playback::Loop), 6),
+            [0, 1, 2, 0, 1, 2, 0]
+        );
+    }
+
+    #[test]
+    fn ping_pong_bounces_without_repeating_ends() {
+        assert_eq!(
+            play(&animation(3, Playback::PingPong), 8),
+            [0, 1, 2, 1, 0, 1, 2, 1, 0]
+        );
+        assert_eq!(play(&animation(1, Playback::PingPong), 2), [0, 0, 0]);
+    }
+
+    #[test]
+    fn once_hold_stays_on_last_frame() {
+        let animation = animation(3, Playback::OnceHold);
+        assert_eq!(play(&animation, 5), [0, 1, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn once_return_goes_back_to_first_frame() {
+        let animation = animation(3, Playback::OnceReturn);
+        assert_eq!(play(&animation, 5), [0, 1, 2, 0, 0, 0]);
+
+        let start = Instant::now();
+        let mut player = Player::new(start);
+        let mut rng = StdRng::seed_from_u64(0);
+        for step in 1..=3 {
+            player.update(Some(&animation), start + STEP * step, &mut rng);
+        }
+        assert!(player.is_finished());
+    }
+
+    #[test]
+    fn random_follows_weights() {
+        let mut animation = animation(3, Playback::Random);
+        animation.weights = vec![0, 1, 0];
+        assert!(play(&animation, 10)[1..].iter().all(|&f| f == 1));
+
+        animation.weights.clear();
+        let shown = play(&animation, 60);
+        assert!((0..3).all(|f| shown.contains(&f)));
+    }
+
+    #[test]
+    fn per_frame_durations() {
+        let mut animation = animation(2, Playback::Loop);
+        animation.frame_durations = vec![Duration::from_millis(50), Duration::from_millis(300)];
+        let start = Instant::now();
+        let mut player = Player::new(start);
+        let mut rng = StdRng::seed_from_u64(0);
+        let at = |ms| start + Duration::from_millis(ms);
+
+        player.update(Some(&animation), at(50), &mut rng);
+        assert_eq!(player.frame, 1);
+        player.update(Some(&animation), at(300), &mut rng);
+        assert_eq!(player.frame, 1);
+        player.update(Some(&animation), at(350), &mut rng);
+        assert_eq!(player.frame, 0);
+    }
+
+    #[test]
+    fn speed_scales_frame_durations() {
+        let animation = animation(3, Playback::Loop);
+        let start = Instant::now();
+        let mut player = Player::new(start);
+        let mut rng = StdRng::seed_from_u64(0);
+        let at = |ms| start + Duration::from_millis(ms);
+
+        player.update_at_speed(Some(&animation), at(49), 2.0, &mut rng);
+        assert_eq!(player.frame, 0);
+        player.update_at_speed(Some(&animation), at(50), 2.0, &mut rng);
+        assert_eq!(player.frame, 1);
+        player.update_at_speed(Some(&animation), at(249), 0.5, &mut rng);
+        assert_eq!(player.frame, 1);
+        player.update_at_speed(Some(&animation), at(250), 0.5, &mut rng);
+        assert_eq!(player.frame, 2);
+    }
+}
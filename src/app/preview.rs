@@ -0,0 +1,673 @@
+//! `tapwatch sprites preview` - plays a pack's animations while you edit it
+//!
+//! Every file the pack is read from is watched, wherever it lives, and the pack
+//! is re-processed whenever one changes, so saving in the art tool updates the
+//! preview. A pack that fails to load keeps showing the last good frames with the
+//! error underneath. `v` cycles through the pack's color variants; each is
+//! recolored once per load and kept.
+
+use super::frame;
+use super::graphics::{self, GraphicsBackend};
+use super::manifest::{Background, Manifest};
+use super::playback::Player;
+use super::recolor::Variant;
+use super::signals;
+use super::spritesheet::{self, Animation, ProcessedAnimation};
+use super::terminal;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use ratatui::{
+    layout::Rect,
+    prelude::CrosstermBackend,
+    style::{Color, Style},
+    Frame, Terminal,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, stdout};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// How often the loop wakes up to advance frames and check for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(15);
+/// Wait for a burst of file events to settle before reloading
+const RELOAD_DELAY: Duration = Duration::from_millis(150);
+const MIN_FPS: f32 = 0.5;
+const MAX_FPS: f32 = 60.0;
+
+const INFO_TEXT: Color = Color::Rgb(200, 200, 200);
+const HELP_TEXT: Color = Color::Rgb(150, 150, 150);
+const ERROR_TEXT: Color = Color::Rgb(255, 120, 120);
+
+const HELP: &str =
+    "space play/pause · ←/→ step · ↑/↓ animation · +/- fps · 0 pack timing · v variant · b background · r reload · q quit";
+
+/// One animation ready to show
+struct Clip {
+    name: String,
+    animation: Animation,
+    size: (u32, u32),
+}
+
+struct Preview {
+    dir: PathBuf,
+    pack_name: String,
+    /// Frames before recoloring, from the last good load
+    processed: Vec<ProcessedAnimation>,
+    manifest: Option<Manifest>,
+    /// Variant shown (`None` for the pack's own colors)
+    variant: Option<String>,
+    /// Clips of every variant shown since the last load
+    variant_clips: HashMap<Option<String>, Vec<Clip>>,
+    current: usize,
+    player: Player,
+    rng: StdRng,
+    playing: bool,
+    /// Overrides the pack's frame durations
+    fps: Option<f32>,
+    remove_background: bool,
+    error: Option<String>,
+    /// Bumped on every reload so the image is redrawn even if the frame index didn't move
+    generation: u64,
+    should_quit: bool,
+}
+
+impl Preview {
+    fn new(dir: &Path, animation: Option<&str>) -> Self {
+        let mut preview = Self {
+            dir: dir.to_path_buf(),
+            pack_name: String::new(),
+            processed: Vec::new(),
+            manifest: None,
+            variant: None,
+            variant_clips: HashMap::new(),
+            current: 0,
+            player: Player::new(Instant::now()),
+            rng: StdRng::from_entropy(),
+            playing: true,
+            fps: None,
+            remove_background: true,
+            error: None,
+            generation: 0,
+            should_quit: false,
+        };
+        preview.reload();
+        if let Some(name) = animation {
+            match preview.clips().iter().position(|c| c.name == name) {
+                Some(index) => preview.current = index,
+                None => preview.error = Some(format!("no animation named `{}`", name)),
+            }
+        }
+        preview
+    }
+
+    /// Re-process the pack from disk, keeping the current animation and frame
+    fn reload(&mut self) {
+        match self.load() {
+            Ok((manifest, processed)) => {
+                let current_name = self.clip().map(|c| c.name.clone());
+                self.pack_name = manifest.name.clone().unwrap_or_default();
+                // The variant may have been renamed or removed
+                if self
+                    .variant
+                    .as_ref()
+                    .is_some_and(|name| !manifest.variants.contains_key(name))
+                {
+                    self.variant = None;
+                }
+                self.manifest = Some(manifest);
+                self.processed = processed;
+                self.variant_clips.clear();
+                self.error = None;
+                self.show_variant(current_name);
+            }
+            // Keep the last good frames on screen while the pack is broken
+            Err(err) => self.error = Some(err),
+        }
+        self.generation += 1;
+    }
+
+    fn load(&self) -> Result<(Manifest, Vec<ProcessedAnimation>), String> {
+        let mut manifest = spritesheet::read_manifest(&self.dir).map_err(|e| e.to_string())?;
+        if !self.remove_background {
+            manifest.background = Background::None;
+        }
+        let processed =
+            spritesheet::process_files(&self.dir, &manifest).map_err(|e| e.to_string())?;
+        Ok((manifest, processed))
+    }
+
+    /// Put the current variant's clips on screen, recoloring them the first time,
+    /// and stay on the animation called `current_name` if there still is one
+    fn show_variant(&mut self, current_name: Option<String>) {
+        let Some(manifest) = &self.manifest else {
+            return;
+        };
+        if !self.variant_clips.contains_key(&self.variant) {
+            match recolored_clips(manifest, self.processed.clone(), &self.variant) {
+                Ok(clips) => {
+                    self.variant_clips.insert(self.variant.clone(), clips);
+                }
+                Err(err) => {
+                    self.error = Some(err);
+                    return;
+                }
+            }
+        }
+        self.current = current_name
+            .and_then(|name| self.clips().iter().position(|c| c.name == name))
+            .unwrap_or(0);
+        self.player.frame = self.player.frame.min(self.frame_count().saturating_sub(1));
+        self.generation += 1;
+    }
+
+    /// Next variant in manifest order, wrapping around to the pack's own colors
+    fn switch_variant(&mut self) {
+        let Some(manifest) = &self.manifest else {
+            return;
+        };
+        let mut names = manifest.variants.keys();
+        let next = match &self.variant {
+            None => names.next().cloned(),
+            Some(current) => names.skip_while(|name| *name != current).nth(1).cloned(),
+        };
+        let current_name = self.clip().map(|c| c.name.clone());
+        self.variant = next;
+        self.show_variant(current_name);
+    }
+
+    /// Clips of the variant on screen
+    fn clips(&self) -> &[Clip] {
+        self.variant_clips
+            .get(&self.variant)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    fn clip(&self) -> Option<&Clip> {
+        self.clips().get(self.current)
+    }
+
+    /// The frame on screen, if there is one
+    fn current_frame(&self) -> Option<&frame::Frame> {
+        self.clip()?.animation.frames.get(self.player.frame)
+    }
+
+    fn frame_count(&self) -> usize {
+        self.clip().map_or(0, |c| c.animation.frames.len())
+    }
+
+    /// How long the current frame stays up
+    fn frame_duration(&self) -> Duration {
+        match (self.fps, self.clip()) {
+            (Some(fps), _) => Duration::from_secs_f32(1.0 / fps),
+            (None, Some(clip)) if !clip.animation.frame_durations.is_empty() => {
+                let durations = &clip.animation.frame_durations;
+                durations[self.player.frame % durations.len()]
+            }
+            _ => Duration::from_millis(100),
+        }
+    }
+
+    /// Advance by the clip's playback mode once the frame has been up long enough
+    fn tick(&mut self) {
+        if !self.playing || self.player.frame_started.elapsed() < self.frame_duration() {
+            return;
+        }
+        let clip = self
+            .variant_clips
+            .get(&self.variant)
+            .and_then(|clips| clips.get(self.current));
+        if let Some(clip) = clip {
+            self.player
+                .advance(&clip.animation, Instant::now(), &mut self.rng);
+        }
+    }
+
+    /// Step one frame in order, whatever the playback mode
+    fn step(&mut self, forward: bool) {
+        let count = self.frame_count();
+        if count == 0 {
+            return;
+        }
+        self.playing = false;
+        let frame = self.player.frame;
+        let frame = if forward {
+            (frame + 1) % count
+        } else {
+            (frame + count - 1) % count
+        };
+        self.player.restart(Instant::now(), frame);
+    }
+
+    fn switch_animation(&mut self, forward: bool) {
+        let count = self.clips().len();
+        if count == 0 {
+            return;
+        }
+        self.current = if forward {
+            (self.current + 1) % count
+        } else {
+            (self.current + count - 1) % count
+        };
+        self.player.restart(Instant::now(), 0);
+    }
+
+    /// Speed up or slow down, starting from the current frame's own rate
+    fn change_fps(&mut self, factor: f32) {
+        let fps = self
+            .fps
+            .unwrap_or_else(|| 1.0 / self.frame_duration().as_secs_f32().max(f32::EPSILON));
+        self.fps = Some((fps * factor).clamp(MIN_FPS, MAX_FPS));
+    }
+
+    fn on_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.should_quit = true,
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                self.should_quit = true
+            }
+            KeyCode::Char(' ') => {
+                self.playing = !self.playing;
+                // A finished play-once clip starts over
+                let frame = if self.player.is_finished() {
+                    0
+                } else {
+                    self.player.frame
+                };
+                self.player.restart(Instant::now(), frame);
+            }
+            KeyCode::Left => self.step(false),
+            KeyCode::Right => self.step(true),
+            KeyCode::Up => self.switch_animation(false),
+            KeyCode::Down | KeyCode::Tab => self.switch_animation(true),
+            KeyCode::Char('+') | KeyCode::Char('=') => self.change_fps(1.25),
+            KeyCode::Char('-') => self.change_fps(0.8),
+            KeyCode::Char('0') => self.fps = None,
+            KeyCode::Char('b') => {
+                self.remove_background = !self.remove_background;
+                self.reload();
+            }
+            KeyCode::Char('v') => self.switch_variant(),
+            KeyCode::Char('r') => self.reload(),
+            _ => {}
+        }
+    }
+
+    /// Identifies what the image on screen shows
+    fn image_key(&self, area: Rect) -> (u64, usize, usize, Rect) {
+        (self.generation, self.current, self.player.frame, area)
+    }
+}
+
+/// Recolor processed animations into a variant, ready to display
+fn recolored_clips(
+    manifest: &Manifest,
+    mut processed: Vec<ProcessedAnimation>,
+    variant: &Option<String>,
+) -> Result<Vec<Clip>, String> {
+    let variant = Variant {
+        name: variant.clone(),
+        ..Variant::default()
+    };
+    variant
+        .apply(manifest, &mut processed)
+        .map_err(|e| e.to_string())?;
+    processed
+        .into_iter()
+        .map(|animation| {
+            Ok(Clip {
+                size: animation.frames.first().map_or((0, 0), |f| f.dimensions()),
+                name: animation.name,
+                animation: Animation {
+                    frames: animation
+                        .frames
+                        .into_iter()
+                        .map(frame::Frame::new)
+                        .collect(),
+                    frame_durations: animation.frame_durations,
+                    playback: animation.playback,
+                    weights: animation.weights,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Watches the directories holding a pack's files (the pack's own and any others
+/// its manifest reaches into, like `frames/` or `../shared`)
+struct Watch {
+    watcher: RecommendedWatcher,
+    /// Directories being watched
+    dirs: BTreeSet<PathBuf>,
+    /// The pack's files as (directory, file name); `None` while the manifest can't
+    /// say which they are, when any change in the pack directory counts
+    files: Option<BTreeSet<(PathBuf, OsString)>>,
+}
+
+impl Watch {
+    /// Follow what the manifest in `dir` reads now, watching directories it
+    /// started reading from and letting go of those it stopped
+    fn sync(&mut self, dir: &Path) -> notify::Result<()> {
+        let files = spritesheet::read_manifest(dir)
+            .and_then(|manifest| spritesheet::pack_files(dir, &manifest))
+            .ok()
+            .map(|files| {
+                files
+                    .iter()
+                    .filter_map(|file| split(&dir.join(file)))
+                    .collect::<BTreeSet<_>>()
+            });
+        let mut dirs = BTreeSet::from([canonical(dir)]);
+        dirs.extend(files.iter().flatten().map(|(dir, _)| dir.clone()));
+        // A missing directory is fine until a file in it is needed (and the next
+        // reload tries again)
+        dirs.retain(|dir| dir.is_dir());
+        self.files = files;
+
+        let gone: Vec<_> = self.dirs.difference(&dirs).cloned().collect();
+        for dir in gone {
+            let _ = self.watcher.unwatch(&dir);
+            self.dirs.remove(&dir);
+        }
+        for dir in dirs {
+            if !self.dirs.contains(&dir) {
+                self.watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+                self.dirs.insert(dir);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a change to `paths` could change the pack
+    fn affects(&self, paths: &[PathBuf], dir: &Path) -> bool {
+        let dir = canonical(dir);
+        paths
+            .iter()
+            .filter_map(|path| split(path))
+            .any(|key| match &self.files {
+                Some(files) => files.contains(&key),
+                None => key.0 == dir,
+            })
+    }
+}
+
+/// A path as its (canonical) directory and file name, so paths to the same file
+/// compare equal however they were spelled
+fn split(path: &Path) -> Option<(PathBuf, OsString)> {
+    Some((canonical(path.parent()?), path.file_name()?.to_os_string()))
+}
+
+fn canonical(dir: &Path) -> PathBuf {
+    fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// Run the preview until the user quits
+pub fn run(dir: &Path, animation: Option<&str>, graphics: GraphicsBackend) -> io::Result<()> {
+    let mut preview = Preview::new(dir, animation);
+
+    // Without a watcher the preview still works, reloading on `r`
+    let (tx, changes) = mpsc::channel();
+    let watch = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Some(event) = event.ok().filter(|e| !e.kind.is_access()) {
+            let _ = tx.send(event.paths);
+        }
+    })
+    .and_then(|watcher| {
+        let mut watch = Watch {
+            watcher,
+            dirs: BTreeSet::new(),
+            files: None,
+        };
+        watch.sync(dir)?;
+        Ok(watch)
+    });
+    let watch = match watch {
+        Ok(watch) => Some(watch),
+        Err(err) => {
+            preview.error = Some(format!("not watching for changes: {}", err));
+            None
+        }
+    };
+
+    signals::install_panic_hook();
+    terminal::enter()?;
+    let mut term = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let result = run_loop(&mut term, &mut preview, graphics, watch, &changes);
+    terminal::restore()?;
+    result
+}
+
+fn run_loop(
+    term: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    preview: &mut Preview,
+    graphics: GraphicsBackend,
+    mut watch: Option<Watch>,
+    changes: &Receiver<Vec<PathBuf>>,
+) -> io::Result<()> {
+    let mut shown = None;
+    let mut reload_at: Option<Instant> = None;
+    let mut watched_generation = preview.generation;
+
+    while !preview.should_quit {
+        // Files usually change in bursts (write, rename, touch); reload once it's quiet
+        while let Ok(paths) = changes.try_recv() {
+            if watch
+                .as_ref()
+                .is_some_and(|watch| watch.affects(&paths, &preview.dir))
+            {
+                reload_at = Some(Instant::now() + RELOAD_DELAY);
+            }
+        }
+        if reload_at.is_some_and(|at| Instant::now() >= at) {
+            reload_at = None;
+            preview.reload();
+        }
+        // The manifest may now read files from somewhere else
+        if let Some(watch) = watch
+            .as_mut()
+            .filter(|_| watched_generation != preview.generation)
+        {
+            watched_generation = preview.generation;
+            if let Err(err) = watch.sync(&preview.dir) {
+                preview.error = Some(format!("not watching for changes: {}", err));
+            }
+        }
+
+        let mut sprite_area = Rect::default();
+        term.draw(|frame| sprite_area = draw(frame, preview, graphics))?;
+
+        let key = preview.image_key(sprite_area);
+        if shown != Some(key) {
+            // A smaller frame or a new layout would leave parts of the old image behind
+            if shown.is_some_and(|(_, _, _, area)| area != sprite_area) {
+                terminal::clear_images()?;
+            }
+            if let Some(frame) = preview.current_frame() {
+                if let Err(err) = graphics.display(frame, sprite_area) {
+                    preview.error = Some(err.to_string());
+                }
+            }
+            shown = Some(key);
+        }
+
+        if event::poll(POLL_INTERVAL)? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    preview.on_key(key.code, key.modifiers)
+                }
+                Event::Resize(..) => {
+                    term.clear()?;
+                    shown = None;
+                }
+                _ => {}
+            }
+        }
+        preview.tick();
+    }
+    Ok(())
+}
+
+/// Draw the text around the sprite, returning where the sprite goes
+fn draw(frame: &mut Frame, preview: &Preview, graphics: GraphicsBackend) -> Rect {
+    let area = frame.area();
+    let buf = frame.buffer_mut();
+    if area.height < 4 {
+        return Rect::default();
+    }
+
+    let info = match preview.clip() {
+        Some(clip) => format!(
+            "{}{} · {} ({}/{}) · frame {}/{} · {} ms{} · background {} · {}",
+            preview.pack_name,
+            preview
+                .variant
+                .as_ref()
+                .map_or(String::new(), |v| format!(" ({})", v)),
+            clip.name,
+            preview.current + 1,
+            preview.clips().len(),
+            preview.player.frame + 1,
+            clip.animation.frames.len(),
+            preview.frame_duration().as_millis(),
+            if preview.fps.is_some() {
+                " (fps override)"
+            } else {
+                ""
+            },
+            if preview.remove_background {
+                "removed"
+            } else {
+                "kept"
+            },
+            if preview.playing { "playing" } else { "paused" },
+        ),
+        None => format!("{} · no animations", preview.dir.display()),
+    };
+    buf.set_stringn(
+        area.x,
+        area.y,
+        info,
+        area.width as usize,
+        Style::default().fg(INFO_TEXT),
+    );
+
+    let bottom = area.bottom() - 1;
+    buf.set_stringn(
+        area.x,
+        bottom,
+        HELP,
+        area.width as usize,
+        Style::default().fg(HELP_TEXT),
+    );
+    if let Some(error) = &preview.error {
+        buf.set_stringn(
+            area.x,
+            bottom - 1,
+            format!("⚠ {}", error),
+            area.width as usize,
+            Style::default().fg(ERROR_TEXT),
+        );
+    }
+
+    let available = Rect {
+        x: area.x,
+        y: area.y + 2,
+        width: area.width,
+        height: area.height.saturating_sub(5),
+    };
+    if graphics == GraphicsBackend::Disabled {
+        buf.set_stringn(
+            available.x,
+            available.y,
+            "this terminal can't show images or true color; use kitty, Ghostty, iTerm2, WezTerm or a Sixel terminal to see the frames",
+            available.width as usize,
+            Style::default().fg(HELP_TEXT),
+        );
+        return Rect::default();
+    }
+    let size = preview.clip().map_or((1, 1), |c| c.size);
+    let sprite_area = fit(size, available);
+    if graphics.uses_cells() {
+        if let Some(frame) = preview.current_frame() {
+            graphics::draw_cells(frame, sprite_area, buf);
+        }
+    }
+    sprite_area
+}
+
+/// The largest cell rectangle with the frame's aspect ratio, centered in `area`
+/// (cells are about twice as tall as wide)
+fn fit((width, height): (u32, u32), area: Rect) -> Rect {
+    let ratio = width.max(1) as f32 / height.max(1) as f32;
+    let mut cells_h = area.height as f32;
+    let mut cells_w = cells_h * 2.0 * ratio;
+    if cells_w > area.width as f32 {
+        cells_w = area.width as f32;
+        cells_h = cells_w / (2.0 * ratio);
+    }
+    let (w, h) = (cells_w.floor() as u16, cells_h.floor() as u16);
+    Rect {
+        x: area.x + (area.width - w) / 2,
+        y: area.y + (area.height - h) / 2,
+        width: w,
+        height: h,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::testing::write_pack;
+    use tempfile::tempdir;
+
+    #[test]
+    fn files_outside_the_pack_directory_are_watched() {
+        let root = tempdir().unwrap();
+        let dir = root.path().join("pack");
+        fs::create_dir_all(dir.join("frames")).unwrap();
+        write_pack(
+            &dir,
+            [0, 0, 200],
+            r#"
+            [animations.wag]
+            files = ["frames/wag.png", "../shared.png"]
+            frame_ms = 100
+            "#,
+        );
+        fs::copy(dir.join("frame.png"), dir.join("frames/wag.png")).unwrap();
+        fs::copy(dir.join("frame.png"), root.path().join("shared.png")).unwrap();
+
+        let mut watch = Watch {
+            watcher: notify::recommended_watcher(|_| {}).unwrap(),
+            dirs: BTreeSet::new(),
+            files: None,
+        };
+        watch.sync(&dir).unwrap();
+        assert_eq!(
+            watch.dirs,
+            BTreeSet::from([
+                canonical(root.path()),
+                canonical(&dir),
+                canonical(&dir.join("frames"))
+            ])
+        );
+
+        let affects = |watch: &Watch, path: PathBuf| watch.affects(&[path], &dir);
+        assert!(affects(&watch, dir.join("manifest.toml")));
+        assert!(affects(&watch, dir.join("frames/wag.png")));
+        assert!(affects(&watch, root.path().join("shared.png")));
+        // Neighbours of the files don't count
+        assert!(!affects(&watch, root.path().join("unrelated.png")));
+        assert!(!affects(&watch, dir.join("frames/old.png")));
+
+        // Without a manifest to go by, anything in the pack directory does
+        fs::write(dir.join("manifest.toml"), "not toml [").unwrap();
+        watch.sync(&dir).unwrap();
+        assert_eq!(watch.dirs, BTreeSet::from([canonical(&dir)]));
+        assert!(affects(&watch, dir.join("anything.png")));
+    }
+}
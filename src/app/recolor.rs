@@ -0,0 +1,237 @@
+//! Recoloring a pack's frames into a variant
+//!
+//! Palette entries move every pixel close to a source color by the same amount
+//! the source moves, so anti-aliased edges and shading keep their contrast. The
+//! hue, saturation and brightness changes then apply to the whole frame.
+
+use super::error::SpriteError;
+use super::manifest::{Color, Manifest, Recolor};
+use super::spritesheet::ProcessedAnimation;
+use image::{Rgba, RgbaImage};
+
+/// Which recoloring of a pack to show: one of the pack's named variants, then
+/// any extra changes from the config
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Variant {
+    pub name: Option<String>,
+    pub recolor: Recolor,
+}
+
+impl Variant {
+    /// Recolor processed frames; fails if the pack has no variant by that name
+    pub fn apply(
+        &self,
+        manifest: &Manifest,
+        animations: &mut [ProcessedAnimation],
+    ) -> Result<(), SpriteError> {
+        let named = match &self.name {
+            Some(name) => Some(manifest.variant(name)?),
+            None => None,
+        };
+        self.recolor
+            .validate()
+            .map_err(|reason| SpriteError::Invalid(format!("recolor: {}", reason)))?;
+        for recolor in named.into_iter().chain([&self.recolor]) {
+            for frame in animations.iter_mut().flat_map(|a| a.frames.iter_mut()) {
+                apply(frame, recolor);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Recolor a frame in place
+pub fn apply(img: &mut RgbaImage, recolor: &Recolor) {
+    if recolor.is_identity() {
+        return;
+    }
+    let adjust_hsv = recolor.hue.rem_euclid(360.0) != 0.0
+        || recolor.saturation != 1.0
+        || recolor.brightness != 1.0;
+
+    for pixel in img.pixels_mut() {
+        // Nothing to see, and the color of transparent pixels is arbitrary
+        if pixel[3] == 0 {
+            continue;
+        }
+        let mut rgb = [pixel[0], pixel[1], pixel[2]];
+        if let Some((from, to)) = nearest_palette_color(rgb, recolor) {
+            rgb = shift(rgb, from, to);
+        }
+        if adjust_hsv {
+            let (h, s, v) = to_hsv(rgb);
+            rgb = from_hsv(
+                (h + recolor.hue).rem_euclid(360.0),
+                (s * recolor.saturation).clamp(0.0, 1.0),
+                (v * recolor.brightness).clamp(0.0, 1.0),
+            );
+        }
+        *pixel = Rgba([rgb[0], rgb[1], rgb[2], pixel[3]]);
+    }
+}
+
+/// The palette entry whose source is closest to `rgb`, if any is within tolerance
+fn nearest_palette_color(rgb: [u8; 3], recolor: &Recolor) -> Option<(Color, Color)> {
+    recolor
+        .palette
+        .iter()
+        .map(|(&from, &to)| {
+            let distance = (0..3)
+                .map(|c| rgb[c].abs_diff(from.0[c]))
+                .max()
+                .unwrap_or(0);
+            (distance, from, to)
+        })
+        .filter(|&(distance, _, _)| distance <= recolor.tolerance)
+        .min_by_key(|&(distance, _, _)| distance)
+        .map(|(_, from, to)| (from, to))
+}
+
+/// Move `rgb` by the difference between `from` and `to`
+fn shift(rgb: [u8; 3], from: Color, to: Color) -> [u8; 3] {
+    std::array::from_fn(|c| (rgb[c] as i16 + to.0[c] as i16 - from.0[c] as i16).clamp(0, 255) as u8)
+}
+
+/// Hue in degrees, saturation and value in 0..=1
+fn to_hsv([r, g, b]: [u8; 3]) -> (f32, f32, f32) {
+    let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+fn from_hsv(hue: f32, saturation: f32, value: f32) -> [u8; 3] {
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    let channel = |c: f32| ((c + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    [channel(r), channel(g), channel(b)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recolored(pixels: &[[u8; 4]], recolor: &Recolor) -> Vec<[u8; 4]> {
+        let mut img = RgbaImage::from_fn(pixels.len() as u32, 1, |x, _| Rgba(pixels[x as usize]));
+        apply(&mut img, recolor);
+        img.pixels().map(|p| p.0).collect()
+    }
+
+    #[test]
+    fn identity_leaves_pixels_alone() {
+        let pixels = [[12, 200, 90, 255], [1, 2, 3, 128]];
+        assert_eq!(recolored(&pixels, &Recolor::default()), pixels);
+        let full_turn = Recolor {
+            hue: 360.0,
+            ..Recolor::default()
+        };
+        assert!(full_turn.is_identity());
+    }
+
+    #[test]
+    fn hsv_round_trips() {
+        for rgb in [
+            [0, 0, 0],
+            [255, 255, 255],
+            [139, 90, 43],
+            [10, 200, 250],
+            [255, 0, 128],
+        ] {
+            let (h, s, v) = to_hsv(rgb);
+            assert_eq!(from_hsv(h, s, v), rgb);
+        }
+    }
+
+    #[test]
+    fn hue_saturation_and_brightness() {
+        let red = [[255, 0, 0, 255]];
+        let hue = Recolor {
+            hue: 120.0,
+            ..Recolor::default()
+        };
+        assert_eq!(recolored(&red, &hue), [[0, 255, 0, 255]]);
+
+        let gray = Recolor {
+            saturation: 0.0,
+            ..Recolor::default()
+        };
+        assert_eq!(recolored(&red, &gray), [[255, 255, 255, 255]]);
+
+        let dim = Recolor {
+            brightness: 0.5,
+            ..Recolor::default()
+        };
+        assert_eq!(recolored(&[[200, 100, 50, 77]], &dim), [[100, 50, 25, 77]]);
+    }
+
+    #[test]
+    fn embedded_variants_recolor_the_dog() {
+        use crate::app::spritesheet::{SpritePack, TYPING};
+
+        let plain = SpritePack::embedded();
+        for name in ["midnight", "mint", "ghost"] {
+            let variant = Variant {
+                name: Some(name.to_string()),
+                ..Variant::default()
+            };
+            let pack = SpritePack::embedded_variant(&variant, None).unwrap();
+            assert_ne!(pack.frame(TYPING, 0), plain.frame(TYPING, 0), "{}", name);
+            // Cached: the same pack comes back
+            assert!(std::sync::Arc::ptr_eq(
+                &pack,
+                &SpritePack::embedded_variant(&variant, None).unwrap()
+            ));
+        }
+
+        let unknown = Variant {
+            name: Some("plaid".to_string()),
+            ..Variant::default()
+        };
+        assert!(SpritePack::embedded_variant(&unknown, None).is_err());
+    }
+
+    #[test]
+    fn palette_moves_nearby_shades_along() {
+        let recolor = Recolor {
+            palette: [(Color([100, 60, 20]), Color([40, 40, 140]))].into(),
+            tolerance: 10,
+            ..Recolor::default()
+        };
+        let pixels = [
+            [100, 60, 20, 255], // the palette color
+            [108, 66, 26, 255], // a lighter shade of it
+            [200, 60, 20, 255], // too far away
+            [100, 60, 20, 0],   // transparent
+        ];
+        assert_eq!(
+            recolored(&pixels, &recolor),
+            [
+                [40, 40, 140, 255],
+                [48, 46, 146, 255],
+                [200, 60, 20, 255],
+                [100, 60, 20, 0],
+            ]
+        );
+    }
+}
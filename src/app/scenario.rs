@@ -0,0 +1,951 @@
+//! Scripted, clock-driven runs of `App` + `ui::draw` against a `TestBackend`
+//!
+//! A scenario is a list of timed key events plus checks at chosen points in time.
+//! The runner steps a `ManualClock` frame by frame, so nothing sleeps in real time.
+
+use super::clock::ManualClock;
+use super::emote::{Emote, Priority};
+use super::graphics::GraphicsBackend;
+use super::machine;
+use super::manifest::Facing;
+use super::mood::Mood;
+use super::spritesheet::{self, SpritePack};
+use super::state::App;
+use super::terminal::Direction;
+use super::testing::write_pack;
+use super::ui;
+use ratatui::{backend::TestBackend, buffer::Buffer, Terminal};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Simulated frame length (matches the main loop's 30 fps)
+const FRAME_DURATION: Duration = Duration::from_millis(1000 / 30);
+
+type Check = Box<dyn Fn(&Snapshot)>;
+
+/// Something the user does
+enum Input {
+    Key(String),
+    Mouse(u16),
+    Snap(Direction),
+    /// The app says hello, as it does on start
+    Greet,
+    Emote(Emote, Priority),
+    /// Something went wrong, and the app reports it
+    Error(String),
+}
+
+/// State visible to a check at a point in time
+pub struct Snapshot<'a> {
+    pub at: Duration,
+    pub app: &'a App,
+    pub buffer: &'a Buffer,
+}
+
+impl Snapshot<'_> {
+    /// Whether nothing at all was rendered into the buffer
+    pub fn is_blank(&self) -> bool {
+        self.buffer
+            .content()
+            .iter()
+            .all(|cell| cell.symbol() == " ")
+    }
+
+    /// The rendered buffer as one string per row (for failure messages)
+    pub fn dump(&self) -> String {
+        let area = self.buffer.area;
+        (area.top()..area.bottom())
+            .map(|y| {
+                (area.left()..area.right())
+                    .map(|x| self.buffer[(x, y)].symbol())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn assert_state(&self, expected: &str) {
+        assert_eq!(
+            self.app.animation_state.name, expected,
+            "animation state at {:?}",
+            self.at
+        );
+    }
+
+    pub fn assert_text(&self, expected: &str) {
+        assert_eq!(self.app.typed_text, expected, "typed text at {:?}", self.at);
+    }
+
+    pub fn assert_blank(&self) {
+        assert!(
+            self.is_blank(),
+            "expected blank screen at {:?}:\n{}",
+            self.at,
+            self.dump()
+        );
+    }
+
+    pub fn assert_not_blank(&self) {
+        assert!(!self.is_blank(), "expected rendered text at {:?}", self.at);
+    }
+}
+
+/// A script of timed key events and checks
+pub struct Scenario {
+    size: (u16, u16),
+    seed: u64,
+    sprites: Option<Arc<SpritePack>>,
+    sleep_after: Option<Duration>,
+    mood: Mood,
+    edit_cursor: bool,
+    log: Option<PathBuf>,
+    inputs: Vec<(Duration, Input)>,
+    checks: Vec<(Duration, Check)>,
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Self {
+            size: (60, 20),
+            seed: 0,
+            sprites: None,
+            sleep_after: None,
+            mood: Mood::default(),
+            edit_cursor: false,
+            log: None,
+            inputs: Vec::new(),
+            checks: Vec::new(),
+        }
+    }
+
+    /// Seed the app's random source (idle frame choice)
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Play a different sprite pack
+    pub fn sprites(mut self, sprites: Arc<SpritePack>) -> Self {
+        self.sprites = Some(sprites);
+        self
+    }
+
+    /// Fall asleep after `ms` without keys (instead of minutes)
+    pub fn sleep_after(mut self, ms: u64) -> Self {
+        self.sleep_after = Some(Duration::from_millis(ms));
+        self
+    }
+
+    /// Append reported errors to the log file at `path`
+    pub fn log(mut self, path: PathBuf) -> Self {
+        self.log = Some(path);
+        self
+    }
+
+    /// Start in this mood
+    pub fn mood(mut self, mood: Mood) -> Self {
+        self.mood = mood;
+        self
+    }
+
+    /// Edit the text around a cursor, like an editor
+    pub fn edit_cursor(mut self) -> Self {
+        self.edit_cursor = true;
+        self
+    }
+
+    /// Greet at `at_ms`, as the app does when it starts
+    pub fn greet(mut self, at_ms: u64) -> Self {
+        self.inputs
+            .push((Duration::from_millis(at_ms), Input::Greet));
+        self
+    }
+
+    /// Queue an emote at `at_ms`, as a signal from another program would
+    pub fn emote(mut self, at_ms: u64, emote: Emote, priority: Priority) -> Self {
+        self.inputs
+            .push((Duration::from_millis(at_ms), Input::Emote(emote, priority)));
+        self
+    }
+
+    /// Report an error with this text at `at_ms`
+    pub fn error(mut self, at_ms: u64, text: &str) -> Self {
+        self.inputs
+            .push((Duration::from_millis(at_ms), Input::Error(text.to_string())));
+        self
+    }
+
+    /// Press `key` at `at_ms` milliseconds into the run
+    pub fn key(mut self, at_ms: u64, key: &str) -> Self {
+        self.inputs
+            .push((Duration::from_millis(at_ms), Input::Key(key.to_string())));
+        self
+    }
+
+    /// Move the mouse to `column` at `at_ms`
+    pub fn mouse(mut self, at_ms: u64, column: u16) -> Self {
+        self.inputs
+            .push((Duration::from_millis(at_ms), Input::Mouse(column)));
+        self
+    }
+
+    /// Snap the window to a screen edge at `at_ms`
+    pub fn snap(mut self, at_ms: u64, direction: Direction) -> Self {
+        self.inputs
+            .push((Duration::from_millis(at_ms), Input::Snap(direction)));
+        self
+    }
+
+    /// Type each character of `text`, starting at `at_ms` and `gap_ms` apart
+    pub fn type_text(mut self, at_ms: u64, text: &str, gap_ms: u64) -> Self {
+        for (i, c) in text.chars().enumerate() {
+            let at = Duration::from_millis(at_ms + gap_ms * i as u64);
+            self.inputs.push((at, Input::Key(c.to_string())));
+        }
+        self
+    }
+
+    /// Run `check` on the first frame at or after `at_ms`
+    pub fn at(mut self, at_ms: u64, check: impl Fn(&Snapshot) + 'static) -> Self {
+        self.checks
+            .push((Duration::from_millis(at_ms), Box::new(check)));
+        self
+    }
+
+    /// Drive the app until every check has run
+    pub fn run(mut self) {
+        self.inputs.sort_by_key(|(at, _)| *at);
+        self.checks.sort_by_key(|(at, _)| *at);
+
+        let clock = ManualClock::new();
+        let sprites = self.sprites.unwrap_or_else(SpritePack::embedded);
+        let mut app = App::with_clock_and_sprites(Arc::new(clock.clone()), sprites)
+            .with_seed(self.seed)
+            .with_mood(self.mood)
+            .with_edit_cursor(self.edit_cursor)
+            .with_log(self.log);
+        if let Some(sleep_after) = self.sleep_after {
+            app = app.with_sleep_after(Some(sleep_after));
+        }
+        let mut terminal =
+            Terminal::new(TestBackend::new(self.size.0, self.size.1)).expect("test backend");
+
+        let mut inputs = self.inputs.into_iter().peekable();
+        let mut checks = self.checks.into_iter().peekable();
+
+        while checks.peek().is_some() {
+            let now = clock.elapsed();
+
+            while let Some((_, input)) = inputs.next_if(|(at, _)| *at <= now) {
+                match input {
+                    Input::Key(key) => app.on_key(key),
+                    Input::Mouse(column) => app.on_mouse(column),
+                    Input::Snap(direction) => app.on_snap(&direction),
+                    Input::Greet => app.greet(),
+                    Input::Emote(emote, priority) => {
+                        app.emote(emote, priority);
+                    }
+                    Input::Error(text) => app.report(&io::Error::other(text)),
+                }
+            }
+            app.tick();
+
+            // Images are never emitted here; only the text layer reaches the buffer
+            let elapsed = app.get_elapsed();
+            let completed = terminal
+                .draw(|frame| ui::draw(frame, &mut app, GraphicsBackend::Disabled, false, elapsed))
+                .expect("draw");
+            let snapshot = Snapshot {
+                at: now,
+                app: &app,
+                buffer: completed.buffer,
+            };
+
+            while let Some((_, check)) = checks.next_if(|(at, _)| *at <= now) {
+                check(&snapshot);
+            }
+
+            clock.advance(FRAME_DURATION);
+        }
+    }
+}
+
+#[test]
+fn starts_idle_with_blank_screen() {
+    Scenario::new()
+        .at(0, |s| {
+            s.assert_state(machine::IDLE);
+            s.assert_text("");
+            s.assert_blank();
+        })
+        .at(500, |s| s.assert_blank())
+        .run();
+}
+
+#[test]
+fn typing_switches_to_typing_animation_and_renders_text() {
+    Scenario::new()
+        .type_text(100, "hi", 100)
+        .at(150, |s| {
+            s.assert_state(machine::TYPING);
+            s.assert_text("h");
+        })
+        .at(300, |s| {
+            s.assert_text("hi");
+            s.assert_not_blank();
+        })
+        .run();
+}
+
+#[test]
+fn a_single_key_plays_typing_frames_at_the_slowest_tempo() {
+    // 250 ms frames at half speed
+    Scenario::new()
+        .key(0, "a")
+        .at(450, |s| assert_eq!(s.app.player.frame, 0))
+        .at(550, |s| assert_eq!(s.app.player.frame, 1))
+        .at(1050, |s| assert_eq!(s.app.player.frame, 2))
+        .run();
+}
+
+#[test]
+fn typing_frames_keep_pace_with_the_typing() {
+    // Frame changes between 1.5 s and 3.5 s of typing keys `gap_ms` apart, and
+    // the state at the end
+    let run = |gap_ms: u64| {
+        let seen = Arc::new(std::sync::Mutex::new((None, 0, String::new())));
+        let mut scenario =
+            Scenario::new().type_text(0, &"x".repeat(4000 / gap_ms as usize), gap_ms);
+        for at in (1500..3500).step_by(FRAME_DURATION.as_millis() as usize) {
+            let seen = seen.clone();
+            scenario = scenario.at(at, move |s| {
+                let mut seen = seen.lock().unwrap();
+                if seen.0.is_some_and(|frame| frame != s.app.player.frame) {
+                    seen.1 += 1;
+                }
+                seen.0 = Some(s.app.player.frame);
+                seen.2 = s.app.animation_state.name.clone();
+            });
+        }
+        scenario.run();
+        let (_, changes, state) = seen.lock().unwrap().clone();
+        (changes, state)
+    };
+
+    // 20 WPM: half speed, 500 ms frames
+    let (slow, state) = run(600);
+    assert!((3..=5).contains(&slow), "{} frames at 20 WPM", slow);
+    assert_eq!(state, machine::TYPING);
+    // 120 WPM: about double speed (125 ms frames, rounded up to whole ticks), and excited
+    let (fast, state) = run(100);
+    assert!((10..=17).contains(&fast), "{} frames at 120 WPM", fast);
+    assert_eq!(state, "excited");
+}
+
+#[test]
+fn stops_typing_after_linger_then_fades_text_out() {
+    Scenario::new()
+        .type_text(0, "dog", 50)
+        .at(3000, |s| s.assert_state(machine::TYPING))
+        .at(3200, |s| {
+            s.assert_state(machine::IDLE);
+            assert!(s.app.fade_effect.is_some(), "fade should be running");
+            s.assert_text("dog");
+        })
+        .at(4200, |s| {
+            s.assert_text("");
+            s.assert_blank();
+        })
+        .run();
+}
+
+#[test]
+fn keypress_during_linger_keeps_typing() {
+    Scenario::new()
+        .key(0, "a")
+        .key(2500, "b")
+        .at(4000, |s| {
+            s.assert_state(machine::TYPING);
+            s.assert_text("ab");
+        })
+        .at(5600, |s| s.assert_state(machine::IDLE))
+        .run();
+}
+
+#[test]
+fn special_key_replaces_text_until_next_character() {
+    Scenario::new()
+        .type_text(0, "ab", 50)
+        .key(200, "⏎")
+        .at(250, |s| s.assert_text("Enter"))
+        .key(300, "c")
+        .at(350, |s| s.assert_text("c"))
+        .key(400, "⌫")
+        .at(450, |s| s.assert_text(""))
+        .run();
+}
+
+#[test]
+fn same_seed_gives_same_idle_frame() {
+    let run = |seed| {
+        let frame = Arc::new(std::sync::Mutex::new(0));
+        let seen = frame.clone();
+        Scenario::new()
+            .seed(seed)
+            .key(0, "a")
+            .at(3500, move |s| *seen.lock().unwrap() = s.app.player.frame)
+            .run();
+        let idle_frame = *frame.lock().unwrap();
+        idle_frame
+    };
+    assert_eq!(run(7), run(7));
+}
+
+/// A tiny pack, drawn looking right, with one-frame idle and typing animations
+/// plus whatever `extra` adds to the manifest
+fn tiny_pack(extra: &str) -> Arc<SpritePack> {
+    let dir = tempfile::tempdir().unwrap();
+    write_pack(dir.path(), [200, 100, 50], extra);
+    Arc::new(SpritePack::load(dir.path(), &Default::default()).unwrap())
+}
+
+/// A tiny pack with a three-frame 100 ms turn
+fn pack_with_turn() -> Arc<SpritePack> {
+    tiny_pack(
+        r#"
+        [animations.turn]
+        files = ["frame.png", "frame.png", "frame.png"]
+        frame_ms = 100
+        "#,
+    )
+}
+
+#[test]
+fn looks_toward_the_snapped_edge() {
+    // The dog is drawn looking left and has no turn animation
+    Scenario::new()
+        .at(0, |s| assert_eq!(s.app.facing, Facing::Left))
+        .snap(100, Direction::Right)
+        .at(150, |s| {
+            assert_eq!(s.app.facing, Facing::Right);
+            assert!(s.app.turn.is_none());
+        })
+        .snap(200, Direction::Top)
+        .at(250, |s| assert_eq!(s.app.facing, Facing::Right))
+        .snap(300, Direction::Left)
+        .at(350, |s| assert_eq!(s.app.facing, Facing::Left))
+        .run();
+}
+
+#[test]
+fn follows_the_mouse_and_the_text() {
+    // 60 columns wide: the dog's middle is column 30
+    Scenario::new()
+        .mouse(100, 55)
+        .at(150, |s| assert_eq!(s.app.facing, Facing::Right))
+        .mouse(200, 31)
+        .at(250, |s| assert_eq!(s.app.facing, Facing::Right))
+        .mouse(300, 2)
+        .at(350, |s| assert_eq!(s.app.facing, Facing::Left))
+        // Centered text grows to the right of the dog
+        .type_text(400, "woof", 20)
+        .at(600, |s| assert_eq!(s.app.facing, Facing::Right))
+        .run();
+}
+
+#[test]
+fn turn_animation_plays_before_looking_the_other_way() {
+    let sprites = pack_with_turn();
+    let right = sprites
+        .frame_facing(spritesheet::IDLE, 0, Facing::Right)
+        .unwrap();
+    let left = sprites
+        .frame_facing(spritesheet::IDLE, 0, Facing::Left)
+        .unwrap();
+    assert_ne!(right, left, "frames looking left should be mirrored");
+
+    Scenario::new()
+        .sprites(sprites)
+        .at(0, |s| assert_eq!(s.app.facing, Facing::Right))
+        .snap(100, Direction::Left)
+        .at(150, |s| {
+            let pose = s.app.pose();
+            assert_eq!(pose.animation, spritesheet::TURN);
+            // Turn frames are shown the way the dog looked before
+            assert_eq!(pose.facing, Facing::Right);
+        })
+        .at(250, |s| assert_eq!(s.app.pose().frame, 1))
+        .at(450, |s| {
+            let pose = s.app.pose();
+            assert_eq!(pose.animation, spritesheet::IDLE);
+            assert_eq!(pose.facing, Facing::Left);
+        })
+        .run();
+}
+
+#[test]
+fn pack_states_drive_the_animation() {
+    // Typing faster than seven keys a second makes the dog wag once, then check again
+    let sprites = tiny_pack(
+        r#"
+        [animations.wag]
+        files = ["frame.png", "frame.png"]
+        frame_ms = 100
+        playback = "once-hold"
+
+        [states.typing]
+        animation = "typing"
+
+        [[states.typing.transitions]]
+        to = "excited"
+        when = [{ gauge = "typing_rate", above = 7.0 }]
+
+        [[states.typing.transitions]]
+        to = "idle"
+        idle_ms = 3000
+
+        [states.excited]
+        animation = "wag"
+
+        [[states.excited.transitions]]
+        to = "typing"
+        finished = true
+        "#,
+    );
+
+    Scenario::new()
+        .sprites(sprites)
+        .type_text(0, "abcdefghij", 50)
+        .at(200, |s| s.assert_state(machine::TYPING))
+        .at(500, |s| {
+            s.assert_state("excited");
+            assert_eq!(s.app.pose().animation, "wag");
+        })
+        // The rate has fallen off since the last key
+        .at(2600, |s| s.assert_state(machine::TYPING))
+        .at(3600, |s| s.assert_state(machine::IDLE))
+        .run();
+}
+
+#[test]
+fn dozes_off_dimmed_without_sleep_frames_and_wakes_on_a_key() {
+    Scenario::new()
+        .sleep_after(1000)
+        .at(900, |s| s.assert_state(machine::IDLE))
+        .at(1100, |s| {
+            // No lie-down frames: straight to sleep, on dimmed idle frames
+            s.assert_state(machine::SLEEPING);
+            let pose = s.app.pose();
+            assert_eq!(pose.animation, spritesheet::IDLE);
+            assert!(pose.dim);
+        })
+        .at(2300, |s| {
+            assert!(s.dump().contains('Z'), "no zZz:\n{}", s.dump())
+        })
+        // No wake frames either: the key goes straight to typing
+        .key(3000, "a")
+        .at(3050, |s| {
+            s.assert_state(machine::TYPING);
+            s.assert_text("a");
+            assert!(!s.app.pose().dim);
+            assert!(!s.dump().contains('Z'));
+        })
+        .run();
+}
+
+#[test]
+fn lies_down_sleeps_and_stretches_awake() {
+    let sprites = tiny_pack(
+        r#"
+        [animations.lie-down]
+        files = ["frame.png", "frame.png"]
+        frame_ms = 100
+        playback = "once-hold"
+
+        [animations.sleep]
+        files = ["frame.png"]
+        frame_ms = 1000
+
+        [animations.wake]
+        files = ["frame.png", "frame.png", "frame.png"]
+        frame_ms = 100
+        playback = "once-hold"
+        "#,
+    );
+
+    Scenario::new()
+        .sprites(sprites)
+        .sleep_after(1000)
+        .at(1100, |s| {
+            s.assert_state("lying-down");
+            assert_eq!(s.app.pose().animation, "lie-down");
+        })
+        .at(1400, |s| {
+            s.assert_state(machine::SLEEPING);
+            let pose = s.app.pose();
+            assert_eq!(pose.animation, "sleep");
+            assert!(!pose.dim);
+        })
+        .key(2000, "a")
+        .at(2100, |s| s.assert_state("waking"))
+        // The key still shows up once the dog is awake
+        .at(2400, |s| {
+            s.assert_state(machine::TYPING);
+            s.assert_text("a");
+        })
+        .run();
+}
+
+#[test]
+fn looping_lie_down_and_wake_still_move_on() {
+    // No `playback`, so both loop and never finish
+    let sprites = tiny_pack(
+        r#"
+        [animations.lie-down]
+        files = ["frame.png", "frame.png"]
+        frame_ms = 100
+
+        [animations.sleep]
+        files = ["frame.png"]
+        frame_ms = 1000
+
+        [animations.wake]
+        files = ["frame.png", "frame.png", "frame.png"]
+        frame_ms = 100
+        "#,
+    );
+
+    Scenario::new()
+        .sprites(sprites)
+        .sleep_after(1000)
+        .at(3900, |s| s.assert_state("lying-down"))
+        .at(4100, |s| s.assert_state(machine::SLEEPING))
+        .key(5000, "a")
+        .at(6900, |s| s.assert_state("waking"))
+        .at(7100, |s| {
+            s.assert_state(machine::TYPING);
+            s.assert_text("a");
+        })
+        .run();
+}
+
+#[test]
+fn deleting_a_lot_makes_the_dog_confused_for_a_moment() {
+    let mut scenario = Scenario::new().type_text(0, "hello world", 100);
+    for i in 0..12 {
+        scenario = scenario.key(1100 + i * 100, "⌫");
+    }
+    scenario
+        .at(1500, |s| s.assert_state(machine::TYPING))
+        .at(2300, |s| {
+            s.assert_state("confused");
+            // No confused frames: the idle ones, with a question mark
+            assert_eq!(s.app.pose().animation, spritesheet::IDLE);
+            assert!(s.dump().contains('?'), "no question mark:\n{}", s.dump());
+        })
+        .at(4500, |s| {
+            s.assert_state(machine::TYPING);
+            assert!(!s.dump().contains('?'));
+        })
+        .run();
+}
+
+#[test]
+fn a_mistake_after_a_pause_still_confuses_the_dog() {
+    Scenario::new()
+        .type_text(0, "recieve⌫⌫⌫⌫ieve", 100)
+        .at(4500, |s| s.assert_state(machine::IDLE))
+        // Deleting into the same word again, straight from idle
+        .key(5000, "⌫")
+        .at(5100, |s| s.assert_state("confused"))
+        .at(7500, |s| s.assert_state(machine::TYPING))
+        .run();
+}
+
+#[test]
+fn greets_by_mood_and_the_mood_follows_the_typing() {
+    // Back after a long time: excited, paws going (no greeting frames of its own)
+    let bored = Mood {
+        boredom: 0.7,
+        ..Mood::default()
+    };
+    Scenario::new()
+        .mood(bored)
+        .greet(0)
+        .at(100, |s| {
+            s.assert_state("greeting-excited");
+            s.assert_text("HI!!");
+            assert_eq!(s.app.pose().animation, spritesheet::TYPING);
+        })
+        .at(2600, |s| s.assert_state(machine::IDLE))
+        // A minute of steady typing is all it takes to stop being bored
+        .type_text(3000, &"x".repeat(300), 200)
+        .at(3100, |s| s.assert_text("x"))
+        .at(63_000, |s| {
+            assert!(s.app.mood.boredom < 0.6, "boredom {}", s.app.mood.boredom);
+            assert!(s.app.mood.happiness > 0.5);
+            assert!(s.app.mood.energy < 1.0);
+        })
+        .run();
+
+    // A first start: a plain hello, typed over by the first key
+    Scenario::new()
+        .greet(0)
+        .at(100, |s| {
+            s.assert_state("greeting");
+            s.assert_text("hi");
+        })
+        .key(500, "a")
+        .at(600, |s| {
+            s.assert_state(machine::TYPING);
+            s.assert_text("a");
+        })
+        .run();
+}
+
+#[test]
+fn mood_picks_the_idle_variant() {
+    let sprites = tiny_pack(
+        r#"
+        [animations.idle-tired]
+        files = ["frame.png"]
+        frame_ms = 1000
+        "#,
+    );
+    let tired = Mood {
+        energy: 0.1,
+        ..Mood::default()
+    };
+    Scenario::new()
+        .sprites(sprites.clone())
+        .mood(tired)
+        .greet(0)
+        .at(100, |s| {
+            s.assert_text("yawn");
+            assert_eq!(s.app.pose().animation, spritesheet::IDLE);
+        })
+        .at(2600, |s| {
+            s.assert_state(machine::IDLE);
+            assert_eq!(s.app.pose().animation, "idle-tired");
+        })
+        .run();
+
+    Scenario::new()
+        .sprites(sprites)
+        .greet(0)
+        .at(2600, |s| {
+            assert_eq!(s.app.pose().animation, spritesheet::IDLE)
+        })
+        .run();
+}
+
+#[test]
+fn enter_and_saves_get_a_reaction() {
+    // No emote frames: the wag wiggles the idle frames, the hearts float beside them
+    Scenario::new()
+        .key(100, "⏎")
+        .at(150, |s| assert_eq!(s.app.pose().shift, (0, 0)))
+        .at(250, |s| {
+            assert_eq!(s.app.pose().shift, (1, 0));
+            assert_eq!(s.app.pose().animation, spritesheet::TYPING);
+        })
+        .at(1400, |s| assert!(s.app.emotes.playing().is_none()))
+        // Ctrl+S: hearts, and an Enter during them is let go
+        .key(2000, "⌃s")
+        .at(2050, |s| s.assert_text("Enter"))
+        .key(2100, "⏎")
+        .at(2500, |s| {
+            let playing = s.app.emotes.playing().expect("an emote");
+            assert_eq!(playing.emote, Emote::Hearts);
+            assert!(s.dump().contains('♥'), "no hearts:\n{}", s.dump());
+        })
+        .at(3300, |s| {
+            assert!(s.app.emotes.playing().is_none());
+            assert!(!s.dump().contains('♥'));
+        })
+        // A plain `s` is just a letter
+        .key(4000, "s")
+        .at(4100, |s| assert!(s.app.emotes.playing().is_none()))
+        .run();
+}
+
+#[test]
+fn an_emote_takes_over_the_loop_then_hands_it_back() {
+    let sprites = tiny_pack(
+        r#"
+        [animations.jump]
+        files = ["frame.png", "frame.png", "frame.png"]
+        frame_ms = 100
+        "#,
+    );
+    Scenario::new()
+        .sprites(sprites)
+        .type_text(0, "make", 100)
+        .emote(350, Emote::Jump, Priority::High)
+        .emote(360, Emote::Spin, Priority::Normal)
+        .at(400, |s| {
+            let pose = s.app.pose();
+            assert_eq!(pose.animation, "jump");
+            assert_eq!(pose.frame, 0);
+            // Still typing underneath
+            s.assert_state(machine::TYPING);
+        })
+        .at(500, |s| assert_eq!(s.app.pose().frame, 1))
+        // The spin has no frames: the typing ones, looking back and forth
+        .at(700, |s| {
+            assert_eq!(s.app.emotes.playing().unwrap().emote, Emote::Spin);
+            assert_eq!(s.app.pose().animation, spritesheet::TYPING);
+        })
+        .at(2000, |s| {
+            let pose = s.app.pose();
+            assert_eq!(pose.animation, spritesheet::TYPING);
+            assert_eq!(pose.facing, s.app.facing);
+        })
+        .run();
+}
+
+#[test]
+fn accented_letters_and_emoji_scroll_without_splitting() {
+    // 60 columns fit 15 letters; well past that, and past the 100-letter limit
+    // (the skin tone arrives as a key of its own and joins the thumb before it)
+    let text = "aü👍🏽é".repeat(40);
+    Scenario::new()
+        .type_text(0, &text, 10)
+        .at(2100, |s| {
+            assert_eq!(s.app.typed_text.len(), 100);
+            assert!(s.app.typed_text.as_str().ends_with("aü👍🏽é"));
+            s.assert_not_blank();
+        })
+        .key(2200, "⌫")
+        .key(2250, "⌫")
+        .at(2300, |s| {
+            assert!(s.app.typed_text.as_str().ends_with("éaü"))
+        })
+        .run();
+}
+
+#[test]
+fn wide_and_combined_letters_line_up_with_the_caret() {
+    // "日" is two columns of blank glyphs and "e" + accent one "e": with the caret
+    // that's five letters, centered from column 20
+    Scenario::new()
+        .edit_cursor()
+        .type_text(0, "日e\u{301}a", 50)
+        .at(250, |s| {
+            assert_eq!(s.app.caret_column, Some(40));
+            // So the "e" is drawn two glyphs, not one, after column 20
+            let drawn = |x: u16| (0..20).any(|y| s.buffer[(x, y)].symbol() != " ");
+            assert_eq!((0..60).find(|&x| drawn(x)), Some(28), "\n{}", s.dump());
+            assert!((40..60).all(|x| !drawn(x)));
+        })
+        .run();
+}
+
+#[test]
+fn the_text_follows_an_editor_cursor() {
+    // 60 columns: "helo" and the caret are five letters, centered from column 20
+    Scenario::new()
+        .edit_cursor()
+        .type_text(0, "helo", 50)
+        .at(250, |s| assert_eq!(s.app.caret_column, Some(40)))
+        .key(300, "←")
+        .key(350, "l")
+        .at(400, |s| {
+            s.assert_text("hello");
+            assert_eq!(s.app.typed_text.after_cursor(), "o");
+            // Six letters from column 18, the caret fifth
+            assert_eq!(s.app.caret_column, Some(38));
+        })
+        .key(450, "⇲")
+        .type_text(500, " wrld", 50)
+        .key(800, "⌃w")
+        .key(850, "⇱")
+        .key(900, "⌦")
+        .at(950, |s| {
+            s.assert_text("ello ");
+            assert_eq!(s.app.typed_text.before_cursor(), "");
+        })
+        // Enter still shows its name, and the next letter starts afresh
+        .key(1000, "⏎")
+        .at(1050, |s| s.assert_text("Enter"))
+        .key(1100, "→")
+        .key(1150, "a")
+        .at(1200, |s| s.assert_text("a"))
+        .run();
+}
+
+#[test]
+fn without_the_editor_cursor_arrows_show_their_names() {
+    Scenario::new()
+        .type_text(0, "hi", 50)
+        .key(200, "←")
+        .at(250, |s| s.assert_text("Left"))
+        .key(300, "⌦")
+        .at(350, |s| s.assert_text("Del"))
+        // Nor does a word get deleted, and the chord types nothing either
+        .key(400, "⌃w")
+        .at(450, |s| s.assert_text("Del"))
+        .run();
+}
+
+#[test]
+fn errors_show_once_until_they_stop_recurring() {
+    fn status<'a>(s: &'a Snapshot) -> Option<&'a str> {
+        s.app.status.as_ref().map(|status| status.text.as_str())
+    }
+    fn logged(path: &Path) -> Vec<String> {
+        let log = fs::read_to_string(path).unwrap_or_default();
+        log.lines()
+            .map(|line| line.split_once("] ").unwrap().1.to_string())
+            .collect()
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tapwatch.log");
+    let (first, second) = (path.clone(), path.clone());
+    Scenario::new()
+        .log(path)
+        .error(0, "pack gone")
+        .error(3000, "pack gone")
+        .at(5000, move |s| {
+            assert_eq!(status(s), Some("pack gone"));
+            assert!(s.dump().contains("⚠ pack gone"));
+            assert_eq!(logged(&first), ["pack gone"]);
+        })
+        // Six seconds after it last came up
+        .at(8900, |s| assert!(status(s).is_some()))
+        .at(9100, |s| {
+            assert_eq!(status(s), None);
+            assert!(!s.dump().contains("pack gone"));
+        })
+        .error(10000, "pack gone")
+        .error(10500, "config broken")
+        .at(11000, move |s| {
+            assert_eq!(status(s), Some("config broken"));
+            assert_eq!(logged(&second), ["pack gone", "pack gone", "config broken"]);
+        })
+        .run();
+}
+
+#[test]
+#[allow(deprecated)]
+fn the_old_two_states_follow_the_machine() {
+    use crate::AnimationState;
+    Scenario::new()
+        .at(0, |s| {
+            assert_eq!(
+                AnimationState::from(&s.app.animation_state),
+                AnimationState::Idle
+            )
+        })
+        .key(100, "a")
+        .at(150, |s| {
+            assert_eq!(
+                AnimationState::from(&s.app.animation_state),
+                AnimationState::Typing
+            )
+        })
+        .run();
+}
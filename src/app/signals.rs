@@ -0,0 +1,86 @@
+use super::emote::Emote;
+use super::terminal;
+use std::io;
+use std::panic;
+use std::sync::mpsc::{self, Receiver};
+
+/// Process-level events the main loop has to react to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// SIGTERM, SIGHUP or SIGINT - shut down cleanly
+    Quit,
+    /// SIGTSTP - hand the terminal back and stop
+    Suspend,
+    /// SIGCONT - we were stopped externally and resumed, repaint everything
+    Resume,
+    /// SIGWINCH - the window changed size, images need repositioning
+    Resize,
+    /// SIGUSR1 (a jump) or SIGUSR2 (a bark) - another program has news, like a
+    /// build finishing
+    Emote(Emote),
+}
+
+/// Restore the terminal before the default panic message is printed
+/// Without this a panic leaves the shell in raw mode on the alternate screen
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = terminal::restore();
+        default_hook(info);
+    }));
+}
+
+/// Listen for process signals on a background thread
+#[cfg(unix)]
+pub fn spawn_listener() -> io::Result<Receiver<Signal>> {
+    use signal_hook::consts::{
+        SIGCONT, SIGHUP, SIGINT, SIGTERM, SIGTSTP, SIGUSR1, SIGUSR2, SIGWINCH,
+    };
+    use signal_hook::iterator::Signals;
+
+    let mut signals = Signals::new([
+        SIGTERM, SIGHUP, SIGINT, SIGTSTP, SIGCONT, SIGWINCH, SIGUSR1, SIGUSR2,
+    ])?;
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for raw in signals.forever() {
+            let signal = match raw {
+                SIGTSTP => Signal::Suspend,
+                SIGCONT => Signal::Resume,
+                SIGWINCH => Signal::Resize,
+                SIGUSR1 => Signal::Emote(Emote::Jump),
+                SIGUSR2 => Signal::Emote(Emote::Bark),
+                _ => Signal::Quit,
+            };
+            if tx.send(signal).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+/// Signals are not delivered on this platform; the receiver never yields
+#[cfg(not(unix))]
+pub fn spawn_listener() -> io::Result<Receiver<Signal>> {
+    let (tx, rx) = mpsc::channel();
+    std::mem::forget(tx);
+    Ok(rx)
+}
+
+/// Restore the terminal, stop the process, and take the terminal back once resumed
+/// Used for SIGTSTP and for Ctrl+Z, which raw mode delivers as a key instead of a signal
+#[cfg(unix)]
+pub fn suspend() -> io::Result<()> {
+    terminal::restore()?;
+    // Our SIGTSTP handler replaced the default action, so stop with SIGSTOP instead
+    signal_hook::low_level::raise(signal_hook::consts::SIGSTOP)?;
+    terminal::enter()
+}
+
+#[cfg(not(unix))]
+pub fn suspend() -> io::Result<()> {
+    Ok(())
+}
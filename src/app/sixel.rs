@@ -0,0 +1,127 @@
+//! Sixel encoding for terminals that speak DEC graphics (foot, mlterm, xterm, …)
+//!
+//! Colors are reduced to a fixed 6x6x6 cube rather than a palette chosen per
+//! image: sprites are small and flat-shaded, and it keeps encoding cheap enough to
+//! do on first draw. Mostly transparent pixels are left unpainted.
+
+use image::RgbaImage;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Steps per channel in the color cube
+const LEVELS: u16 = 6;
+/// Pixels less opaque than this aren't drawn
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// The image as a complete Sixel escape sequence
+pub fn encode(image: &RgbaImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let color = |x: u32, y: u32| {
+        let pixel = image.get_pixel(x, y);
+        (pixel[3] >= ALPHA_THRESHOLD)
+            .then(|| level(pixel[0]) * LEVELS * LEVELS + level(pixel[1]) * LEVELS + level(pixel[2]))
+    };
+
+    // P2 = 1: unpainted pixels keep what's behind them; raster attributes give 1:1 pixels
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    let mut used = [false; (LEVELS * LEVELS * LEVELS) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            if let Some(c) = color(x, y) {
+                used[c as usize] = true;
+            }
+        }
+    }
+    for (c, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+        let c = c as u16;
+        let percent = |level: u16| level * 100 / (LEVELS - 1);
+        let _ = write!(
+            out,
+            "#{};2;{};{};{}",
+            c,
+            percent(c / (LEVELS * LEVELS)),
+            percent(c / LEVELS % LEVELS),
+            percent(c % LEVELS)
+        );
+    }
+
+    // Six rows at a time: one line of sixels per color, overprinted with `$`
+    for top in (0..height).step_by(6) {
+        let mut rows: BTreeMap<u16, Vec<u8>> = BTreeMap::new();
+        for x in 0..width {
+            for dy in 0..6.min(height - top) {
+                if let Some(c) = color(x, top + dy) {
+                    rows.entry(c).or_insert_with(|| vec![0; width as usize])[x as usize] |= 1 << dy;
+                }
+            }
+        }
+        for (i, (c, mut bits)) in rows.into_iter().enumerate() {
+            if i > 0 {
+                out.push('$');
+            }
+            let _ = write!(out, "#{}", c);
+            while bits.last() == Some(&0) {
+                bits.pop();
+            }
+            push_runs(&mut out, &bits);
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out.into_bytes()
+}
+
+/// A channel's step in the color cube
+fn level(value: u8) -> u16 {
+    (value as u16 * (LEVELS - 1) + 127) / 255
+}
+
+/// Sixel characters with runs of four or more compressed as `!<count><char>`
+fn push_runs(out: &mut String, bits: &[u8]) {
+    let mut i = 0;
+    while i < bits.len() {
+        let run = bits[i..].iter().take_while(|&&b| b == bits[i]).count();
+        let ch = (b'?' + bits[i]) as char;
+        if run >= 4 {
+            let _ = write!(out, "!{}{}", run, ch);
+        } else {
+            out.extend(std::iter::repeat_n(ch, run));
+        }
+        i += run;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 250, 255]);
+    const CLEAR: Rgba<u8> = Rgba([255, 255, 255, 0]);
+
+    fn encoded(image: &RgbaImage) -> String {
+        String::from_utf8(encode(image)).unwrap()
+    }
+
+    #[test]
+    fn transparent_pixels_are_not_painted() {
+        // A red column next to a transparent one
+        let image = RgbaImage::from_fn(2, 6, |x, _| if x == 0 { RED } else { CLEAR });
+        assert_eq!(
+            encoded(&image),
+            "\x1bP0;1;0q\"1;1;2;6#180;2;100;0;0#180~-\x1b\\"
+        );
+    }
+
+    #[test]
+    fn colors_share_a_band_and_runs_are_compressed() {
+        // Top row red, everything below blue, seven rows: two bands
+        let image = RgbaImage::from_fn(5, 7, |_, y| if y == 0 { RED } else { BLUE });
+        assert_eq!(
+            encoded(&image),
+            "\x1bP0;1;0q\"1;1;5;7#5;2;0;0;100#180;2;100;0;0\
+             #5!5}$#180!5@-#5!5@-\x1b\\"
+        );
+    }
+}
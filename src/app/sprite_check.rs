@@ -0,0 +1,425 @@
+//! `tapwatch sprites check` - validates a sprite pack and draws a contact sheet
+//!
+//! The pack goes through the same pipeline the app uses, so what the report and
+//! the contact sheet show is exactly what will be on screen.
+
+use super::anchor::Placement;
+use super::error::SpriteError;
+use super::spritesheet::{self, ProcessedAnimation};
+use image::{imageops, Rgba, RgbaImage};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+/// Largest side of a frame on the contact sheet
+const CELL_SIZE: u32 = 128;
+const CELL_PADDING: u32 = 8;
+const CHECKER_SIZE: u32 = 8;
+
+const CHECKER_LIGHT: Rgba<u8> = Rgba([235, 235, 235, 255]);
+const CHECKER_DARK: Rgba<u8> = Rgba([205, 205, 205, 255]);
+const SHEET_BACKGROUND: Rgba<u8> = Rgba([60, 60, 60, 255]);
+const BOUNDS_COLOR: Rgba<u8> = Rgba([0, 190, 0, 255]);
+const CLIPPED_COLOR: Rgba<u8> = Rgba([230, 0, 0, 255]);
+const ANCHOR_COLOR: Rgba<u8> = Rgba([255, 0, 255, 255]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Something wrong with the pack
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub severity: Severity,
+    pub animation: Option<String>,
+    pub frame: Option<usize>,
+    pub message: String,
+}
+
+/// One line of the overview
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub name: String,
+    pub frames: usize,
+    pub size: (u32, u32),
+    pub cycle: Duration,
+}
+
+/// Everything found in a pack, printable
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub pack: String,
+    /// In contact sheet row order
+    pub animations: Vec<Summary>,
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    pub fn has_errors(&self) -> bool {
+        self.problems.iter().any(|p| p.severity == Severity::Error)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Sprite pack \"{}\"\n", self.pack)?;
+        let width = self
+            .animations
+            .iter()
+            .map(|a| a.name.len())
+            .max()
+            .unwrap_or(0);
+        for (row, animation) in self.animations.iter().enumerate() {
+            writeln!(
+                f,
+                "  {:width$}  {} frame(s), {}x{}, {:.2}s per cycle (row {})",
+                animation.name,
+                animation.frames,
+                animation.size.0,
+                animation.size.1,
+                animation.cycle.as_secs_f32(),
+                row + 1,
+                width = width
+            )?;
+        }
+        writeln!(f)?;
+
+        for problem in &self.problems {
+            let mark = match problem.severity {
+                Severity::Warning => "!",
+                Severity::Error => "✗",
+            };
+            let location = match (&problem.animation, problem.frame) {
+                (Some(animation), Some(frame)) => format!("{} frame {}: ", animation, frame),
+                (Some(animation), None) => format!("{}: ", animation),
+                _ => String::new(),
+            };
+            writeln!(f, "{} {}{}", mark, location, problem.message)?;
+        }
+        if self.problems.is_empty() {
+            writeln!(f, "✓ no problems found")?;
+        }
+
+        let count = |severity| {
+            self.problems
+                .iter()
+                .filter(|p| p.severity == severity)
+                .count()
+        };
+        write!(
+            f,
+            "\n{} warning(s), {} error(s)",
+            count(Severity::Warning),
+            count(Severity::Error)
+        )
+    }
+}
+
+/// Load a pack and look for problems; the processed animations feed the contact sheet
+pub fn check(dir: &Path) -> Result<(Report, Vec<ProcessedAnimation>), SpriteError> {
+    let (manifest, animations) = spritesheet::process_dir(dir)?;
+    let mut problems = Vec::new();
+
+    for missing in spritesheet::missing_animations(&animations) {
+        problems.push(Problem {
+            severity: Severity::Error,
+            animation: None,
+            frame: None,
+            message: format!("missing `{}` animation", missing),
+        });
+    }
+    if let Err(message) = manifest
+        .machine()
+        .validate(|name| animations.iter().any(|a| a.name == name))
+    {
+        problems.push(Problem {
+            severity: Severity::Error,
+            animation: None,
+            frame: None,
+            message,
+        });
+    }
+
+    for animation in &animations {
+        let problem = |severity, frame, message| Problem {
+            severity,
+            animation: Some(animation.name.clone()),
+            frame,
+            message,
+        };
+
+        for (i, placement) in animation.placements.iter().enumerate() {
+            if placement.bounds.is_none() {
+                problems.push(problem(
+                    Severity::Error,
+                    Some(i),
+                    "empty after background removal".to_string(),
+                ));
+            } else if placement.clipped {
+                problems.push(problem(
+                    Severity::Warning,
+                    Some(i),
+                    "content is clipped by anchoring".to_string(),
+                ));
+            }
+        }
+
+        let mut sizes: BTreeMap<(u32, u32), usize> = BTreeMap::new();
+        for &size in &animation.source_sizes {
+            *sizes.entry(size).or_default() += 1;
+        }
+        if sizes.len() > 1 {
+            let list: Vec<String> = sizes
+                .iter()
+                .map(|((w, h), n)| format!("{}x{} ×{}", w, h, n))
+                .collect();
+            problems.push(problem(
+                Severity::Warning,
+                None,
+                format!("frames have different sizes ({})", list.join(", ")),
+            ));
+        }
+    }
+
+    // The dog changes size on screen when switching between these
+    let sizes: Vec<(u32, u32)> = animations.iter().map(frame_size).collect();
+    if let Some(first) = sizes.first() {
+        if sizes.iter().any(|size| size != first) {
+            let list: Vec<String> = animations
+                .iter()
+                .zip(&sizes)
+                .map(|(a, (w, h))| format!("{} {}x{}", a.name, w, h))
+                .collect();
+            problems.push(Problem {
+                severity: Severity::Warning,
+                animation: None,
+                frame: None,
+                message: format!("animations have different sizes ({})", list.join(", ")),
+            });
+        }
+    }
+
+    let report = Report {
+        pack: manifest.name.unwrap_or_default(),
+        animations: animations
+            .iter()
+            .map(|a| Summary {
+                name: a.name.clone(),
+                frames: a.frames.len(),
+                size: frame_size(a),
+                cycle: a.frame_durations.iter().sum(),
+            })
+            .collect(),
+        problems,
+    };
+    Ok((report, animations))
+}
+
+fn frame_size(animation: &ProcessedAnimation) -> (u32, u32) {
+    animation
+        .frames
+        .first()
+        .map_or((0, 0), RgbaImage::dimensions)
+}
+
+/// Every processed frame on a checkerboard, one animation per row, with the
+/// content bounds (red if clipped) and the anchor marked
+pub fn contact_sheet(animations: &[ProcessedAnimation]) -> RgbaImage {
+    let columns = animations.iter().map(|a| a.frames.len()).max().unwrap_or(0) as u32;
+    let rows = animations.len() as u32;
+    let pitch = CELL_SIZE + CELL_PADDING;
+    let mut sheet = RgbaImage::from_pixel(
+        (columns * pitch + CELL_PADDING).max(1),
+        (rows * pitch + CELL_PADDING).max(1),
+        SHEET_BACKGROUND,
+    );
+
+    for (row, animation) in animations.iter().enumerate() {
+        for (column, (frame, placement)) in animation
+            .frames
+            .iter()
+            .zip(&animation.placements)
+            .enumerate()
+        {
+            let cell = draw_cell(frame, placement);
+            let x = CELL_PADDING + column as u32 * pitch;
+            let y = CELL_PADDING + row as u32 * pitch;
+            imageops::replace(&mut sheet, &cell, x as i64, y as i64);
+        }
+    }
+    sheet
+}
+
+/// One frame scaled into a cell, with its bounds and anchor drawn on top
+fn draw_cell(frame: &RgbaImage, placement: &Placement) -> RgbaImage {
+    let (width, height) = frame.dimensions();
+    let scale = CELL_SIZE as f32 / width.max(height).max(1) as f32;
+    let scaled = |v: i64| (v as f32 * scale).round() as i64;
+    let cell_w = scaled(width as i64).max(1) as u32;
+    let cell_h = scaled(height as i64).max(1) as u32;
+
+    let mut cell = RgbaImage::from_fn(cell_w, cell_h, |x, y| {
+        if (x / CHECKER_SIZE + y / CHECKER_SIZE).is_multiple_of(2) {
+            CHECKER_LIGHT
+        } else {
+            CHECKER_DARK
+        }
+    });
+    let resized = imageops::resize(frame, cell_w, cell_h, imageops::FilterType::Triangle);
+    imageops::overlay(&mut cell, &resized, 0, 0);
+
+    if let Some((x, y, w, h)) = placement.bounds {
+        let left = scaled(x as i64 + placement.offset.0);
+        let top = scaled(y as i64 + placement.offset.1);
+        let right = scaled(x as i64 + placement.offset.0 + w as i64) - 1;
+        let bottom = scaled(y as i64 + placement.offset.1 + h as i64) - 1;
+        let color = if placement.clipped {
+            CLIPPED_COLOR
+        } else {
+            BOUNDS_COLOR
+        };
+        draw_rect(&mut cell, (left, top, right, bottom), color);
+    }
+
+    let (ax, ay) = (scaled(placement.anchor.0), scaled(placement.anchor.1));
+    for d in -3..=3 {
+        put(&mut cell, ax + d, ay, ANCHOR_COLOR);
+        put(&mut cell, ax, ay + d, ANCHOR_COLOR);
+    }
+    cell
+}
+
+/// Outline a rectangle, pulled inside the image so clipped bounds stay visible
+fn draw_rect(
+    img: &mut RgbaImage,
+    (left, top, right, bottom): (i64, i64, i64, i64),
+    color: Rgba<u8>,
+) {
+    let left = left.max(0);
+    let top = top.max(0);
+    let right = right.min(img.width() as i64 - 1);
+    let bottom = bottom.min(img.height() as i64 - 1);
+    for x in left..=right {
+        put(img, x, top, color);
+        put(img, x, bottom, color);
+    }
+    for y in top..=bottom {
+        put(img, left, y, color);
+        put(img, right, y, color);
+    }
+}
+
+/// Set a pixel, ignoring coordinates outside the image
+fn put(img: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>) {
+    if x >= 0 && y >= 0 && (x as u32) < img.width() && (y as u32) < img.height() {
+        img.put_pixel(x as u32, y as u32, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::testing::write_pack;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn clean_pack_has_no_problems() {
+        let dir = tempdir().unwrap();
+        write_pack(dir.path(), [200, 100, 50], "");
+        let (report, animations) = check(dir.path()).unwrap();
+
+        assert!(report.problems.is_empty(), "{}", report);
+        assert!(!report.has_errors());
+        let names: Vec<_> = report.animations.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["idle", "typing"]);
+        assert_eq!(report.animations[1].size, (4, 4));
+        assert_eq!(report.animations[1].cycle, Duration::from_millis(250));
+        assert_eq!(animations.len(), 2);
+        assert!(report.to_string().contains("✓ no problems found"));
+    }
+
+    #[test]
+    fn broken_packs_are_reported() {
+        let dir = tempdir().unwrap();
+
+        // A frame that's fully transparent, and a required animation left out
+        let blank = image::RgbaImage::new(4, 4);
+        write_pack(dir.path(), [200, 100, 50], "");
+        blank.save(dir.path().join("blank.png")).unwrap();
+        fs::write(
+            dir.path().join("manifest.toml"),
+            "[background]\nmode = \"none\"\n\
+             [animations.idle]\nfiles = [\"frame.png\", \"blank.png\"]\nframe_ms = 100\n",
+        )
+        .unwrap();
+        let (report, _) = check(dir.path()).unwrap();
+        assert!(report.has_errors());
+        let text = report.to_string();
+        assert!(
+            text.contains("✗ idle frame 1: empty after background removal"),
+            "{}",
+            text
+        );
+        assert!(text.contains("✗ missing `typing` animation"), "{}", text);
+
+        // A frame file that isn't there stops the check
+        write_pack(
+            dir.path(),
+            [200, 100, 50],
+            "[animations.sleep]\nfiles = [\"gone.png\"]\nframe_ms = 100",
+        );
+        match check(dir.path()) {
+            Err(SpriteError::Io(path, _)) => assert!(path.ends_with("gone.png")),
+            other => panic!("{:?}", other.map(|(report, _)| report)),
+        }
+
+        // So do durations that don't match the frames
+        write_pack(
+            dir.path(),
+            [200, 100, 50],
+            "[animations.sleep]\nfiles = [\"frame.png\"]\ndurations = [100, 200]",
+        );
+        let Err(err) = check(dir.path()) else {
+            panic!("mismatched durations accepted");
+        };
+        assert!(err.to_string().contains("durations"), "{}", err);
+    }
+
+    #[test]
+    fn contact_sheet_has_a_row_per_animation() {
+        let dir = tempdir().unwrap();
+        write_pack(
+            dir.path(),
+            [200, 100, 50],
+            "[animations.sleep]\nfiles = [\"frame.png\", \"frame.png\", \"frame.png\"]\nframe_ms = 100",
+        );
+        let (report, animations) = check(dir.path()).unwrap();
+        let sheet = contact_sheet(&animations);
+
+        // Three columns for the longest animation, three rows
+        let pitch = CELL_SIZE + CELL_PADDING;
+        assert_eq!(report.animations.len(), 3);
+        assert_eq!(
+            sheet.dimensions(),
+            (3 * pitch + CELL_PADDING, 3 * pitch + CELL_PADDING)
+        );
+        assert_eq!(*sheet.get_pixel(0, 0), SHEET_BACKGROUND);
+        // Cells are filled where an animation has a frame and left bare past its end
+        let middle = |column: u32, row: u32| {
+            let at = |i: u32| CELL_PADDING + i * pitch + CELL_SIZE / 2;
+            *sheet.get_pixel(at(column), at(row))
+        };
+        assert_ne!(middle(0, 0), SHEET_BACKGROUND);
+        assert_eq!(middle(1, 0), SHEET_BACKGROUND);
+        assert_ne!(middle(2, 1), SHEET_BACKGROUND);
+
+        assert_eq!(
+            contact_sheet(&[]).dimensions(),
+            (CELL_PADDING, CELL_PADDING)
+        );
+    }
+}
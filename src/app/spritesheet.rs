@@ -1,13 +1,42 @@
-use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use super::anchor::{self, Anchoring, Placement};
+use super::atlas::Atlas;
+use super::background;
+use super::cache::{CacheKey, FrameCache};
+use super::error::SpriteError;
+use super::frame::Frame;
+use super::log;
+use super::machine::Machine;
+use super::manifest::{
+    AtlasSelector, Background, Facing, FrameSource, Manifest, Playback, MANIFEST_FILE_NAME,
+};
+use super::recolor::Variant;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageFormat, RgbaImage};
 use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs;
 use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-// Embed sprite sheet at compile time for reliable loading
+// Embed the default sprite pack at compile time for reliable loading
 const SPRITE_SHEET_BYTES: &[u8] = include_bytes!("../assets/dog_sprites.png");
+const SPRITE_SHEET_NAME: &str = "dog_sprites.png";
+const EMBEDDED_MANIFEST: &str = include_str!("../assets/dog_sprites.toml");
 
-// Frame layout in the sprite sheet (1024x1024 pixels, 4x4 grid)
-const FRAME_WIDTH: u32 = 256;  // 1024 / 4
-const FRAME_HEIGHT: u32 = 256; // 1024 / 4
+/// Animation shown while the user is away from the keyboard
+pub const IDLE: &str = "idle";
+/// Animation shown while keys are being pressed
+pub const TYPING: &str = "typing";
+/// Optional animation played when the character turns around
+pub const TURN: &str = "turn";
+
+/// Animations every pack must provide
+const REQUIRED_ANIMATIONS: &[&str] = &[IDLE, TYPING];
 
 // Frame positions (x, y, width, height)
 pub struct FrameRect {
@@ -17,174 +46,743 @@ pub struct FrameRect {
     pub height: u32,
 }
 
-// Define frame positions for each animation state
-// Idle animation: Rows 1-2 (first 8 frames)
-const IDLE_FRAMES: &[FrameRect] = &[
-    // Row 1: 4 idle frames
-    FrameRect { x: 0, y: 0, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH, y: 0, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH * 2, y: 0, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH * 3, y: 0, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    // Row 2: 4 more idle frames
-    FrameRect { x: 0, y: FRAME_HEIGHT, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH, y: FRAME_HEIGHT, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH * 2, y: FRAME_HEIGHT, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH * 3, y: FRAME_HEIGHT, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-];
-
-// Typing animation: Rows 3-4 (last 8 frames)
-const TYPING_FRAMES: &[FrameRect] = &[
-    // Row 3: 4 typing frames
-    FrameRect { x: 0, y: FRAME_HEIGHT * 2, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH, y: FRAME_HEIGHT * 2, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH * 2, y: FRAME_HEIGHT * 2, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH * 3, y: FRAME_HEIGHT * 2, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    // Row 4: 4 more typing frames
-    FrameRect { x: 0, y: FRAME_HEIGHT * 3, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH, y: FRAME_HEIGHT * 3, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH * 2, y: FRAME_HEIGHT * 3, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-    FrameRect { x: FRAME_WIDTH * 3, y: FRAME_HEIGHT * 3, width: FRAME_WIDTH, height: FRAME_HEIGHT },
-];
-
-// Background color to make transparent (white/light gray)
-const BG_THRESHOLD: u8 = 240; // Pixels with R,G,B all above this become transparent
-
-// Load the sprite sheet once from embedded bytes
-static SPRITE_SHEET: Lazy<Option<DynamicImage>> = Lazy::new(|| {
-    image::load_from_memory(SPRITE_SHEET_BYTES).ok()
-});
-
-// Pre-extract frames as PNG bytes for iTerm2 display
-static TYPING_FRAME_BYTES: Lazy<Vec<Vec<u8>>> = Lazy::new(|| {
-    extract_frames(TYPING_FRAMES)
-});
-
-static IDLE_FRAME_BYTES: Lazy<Vec<Vec<u8>>> = Lazy::new(|| {
-    extract_frames(IDLE_FRAMES)
-});
-
-fn extract_frames(frames: &[FrameRect]) -> Vec<Vec<u8>> {
-    let Some(sheet) = SPRITE_SHEET.as_ref() else {
-        return vec![];
-    };
+/// Recolored packs, looked up by variant
+type VariantCache = Vec<(Variant, Arc<SpritePack>)>;
 
-    frames
-        .iter()
-        .filter_map(|rect| {
-            // Crop the frame from the sprite sheet
-            let cropped = sheet.crop_imm(rect.x, rect.y, rect.width, rect.height);
+// Each variant of the embedded pack is recolored only once
+static EMBEDDED_VARIANTS: Lazy<Mutex<VariantCache>> = Lazy::new(|| Mutex::new(Vec::new()));
 
-            // Convert to RGBA and remove background
-            let rgba = remove_background(&cropped.to_rgba8());
+/// Frame duration when neither the manifest nor the atlas specifies one
+const DEFAULT_FRAME_DURATION: Duration = Duration::from_millis(100);
 
-            // Center the content to prevent shifting during animation
-            let centered = center_content(&rgba, FRAME_WIDTH, FRAME_HEIGHT);
+/// An animation after background removal and anchoring, before recoloring
+#[derive(Clone)]
+pub struct ProcessedAnimation {
+    pub name: String,
+    /// Output frames, all the same size
+    pub frames: Vec<RgbaImage>,
+    /// Size of each frame as cut from its source
+    pub source_sizes: Vec<(u32, u32)>,
+    /// Where each frame's content was moved to
+    pub placements: Vec<Placement>,
+    pub frame_durations: Vec<Duration>,
+    pub playback: Playback,
+    /// Per-frame weights for random playback (empty: all equal)
+    pub weights: Vec<u32>,
+}
 
-            // Convert to PNG bytes
-            let mut bytes = Vec::new();
-            let mut cursor = Cursor::new(&mut bytes);
-            DynamicImage::ImageRgba8(centered)
-                .write_to(&mut cursor, ImageFormat::Png)
-                .ok()?;
-            Some(bytes)
-        })
-        .collect()
+/// One processed animation: its frames plus how to play them
+pub struct Animation {
+    pub frames: Vec<Frame>,
+    pub frame_durations: Vec<Duration>,
+    pub playback: Playback,
+    /// Per-frame weights for random playback (empty: all equal)
+    pub weights: Vec<u32>,
 }
 
-/// Remove white/light background by making those pixels transparent
-fn remove_background(img: &RgbaImage) -> RgbaImage {
-    let mut result = img.clone();
+/// A fully processed set of animations, ready to display
+pub struct SpritePack {
+    pub name: String,
+    /// Which way the character looks in the unmirrored frames
+    pub facing: Facing,
+    /// When each animation plays
+    pub machine: Machine,
+    animations: HashMap<String, Animation>,
+}
 
-    for pixel in result.pixels_mut() {
-        let Rgba([r, g, b, _a]) = *pixel;
+impl SpritePack {
+    /// The dog that ships with TapWatch (processed once per process, without the
+    /// disk cache unless `embedded_variant` already brought it in from there)
+    pub fn embedded() -> Arc<SpritePack> {
+        Self::embedded_variant(&Variant::default(), None)
+            .unwrap_or_else(|_| Arc::new(Self::empty()))
+    }
 
-        // If pixel is very light (near white), make it transparent
-        if r > BG_THRESHOLD && g > BG_THRESHOLD && b > BG_THRESHOLD {
-            *pixel = Rgba([r, g, b, 0]); // Fully transparent
+    /// The built-in dog, recolored, reusing frames from `frames` the first time
+    /// this variant is asked for
+    pub fn embedded_variant(
+        variant: &Variant,
+        frames: Option<&FrameCache>,
+    ) -> Result<Arc<SpritePack>, SpriteError> {
+        let mut cache = EMBEDDED_VARIANTS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, pack)) = cache.iter().find(|(v, _)| v == variant) {
+            return Ok(pack.clone());
         }
+        let pack = Arc::new(Self::embedded_with(variant, frames)?);
+        cache.push((variant.clone(), pack.clone()));
+        Ok(pack)
     }
 
-    result
-}
+    /// Process the built-in dog, reusing frames from `cache` if they're there
+    pub fn embedded_with(
+        variant: &Variant,
+        cache: Option<&FrameCache>,
+    ) -> Result<Self, SpriteError> {
+        let files = [
+            (SPRITE_SHEET_NAME, SPRITE_SHEET_BYTES),
+            (MANIFEST_FILE_NAME, EMBEDDED_MANIFEST.as_bytes()),
+        ];
+        let cache = cache.map(|cache| (cache, CacheKey::for_files("", files, variant)));
+        Self::cached(cache, || {
+            let manifest = Manifest::parse(EMBEDDED_MANIFEST)?;
+            let processed = process(&manifest, |name| match name {
+                SPRITE_SHEET_NAME => Ok(SPRITE_SHEET_BYTES.to_vec()),
+                _ => Err(SpriteError::Invalid(format!("{} is not embedded", name))),
+            })?;
+            Self::from_processed(&manifest, processed, variant)
+        })
+    }
+
+    /// The pack stored under the key in the cache, or else `make` it and store it
+    fn cached(
+        cache: Option<(&FrameCache, CacheKey)>,
+        make: impl FnOnce() -> Result<Self, SpriteError>,
+    ) -> Result<Self, SpriteError> {
+        if let Some(pack) = cache.as_ref().and_then(|(cache, key)| cache.load(key)) {
+            return Ok(pack);
+        }
+        let pack = make()?;
+        if let Some((cache, key)) = &cache {
+            // Not being able to write the cache only costs time on the next start
+            if let (Err(err), Some(log)) = (cache.store(key, &pack), log::path()) {
+                log::write(&log, &format!("frame cache not written: {}", err));
+            }
+        }
+        Ok(pack)
+    }
+
+    /// Put a pack together from already processed animations
+    pub(crate) fn from_parts(
+        name: String,
+        facing: Facing,
+        machine: Machine,
+        animations: HashMap<String, Animation>,
+    ) -> Self {
+        Self {
+            name,
+            facing,
+            machine,
+            animations,
+        }
+    }
+
+    /// A pack with no animations (nothing is drawn)
+    pub fn empty() -> Self {
+        Self {
+            name: String::new(),
+            facing: Facing::default(),
+            machine: Machine::default(),
+            animations: HashMap::new(),
+        }
+    }
+
+    /// Load a pack from a directory containing `manifest.toml`, processing it from
+    /// scratch
+    pub fn load(dir: &Path, variant: &Variant) -> Result<Self, SpriteError> {
+        Self::load_with(dir, variant, None)
+    }
+
+    /// Load a pack, reusing frames from `cache` if nothing in the pack changed
+    pub fn load_with(
+        dir: &Path,
+        variant: &Variant,
+        cache: Option<&FrameCache>,
+    ) -> Result<Self, SpriteError> {
+        let cache = match cache {
+            Some(cache) => Some((cache, CacheKey::for_dir(dir, variant)?)),
+            None => None,
+        };
+        Self::cached(cache, || {
+            let (manifest, animations) = process_dir(dir)?;
+            Self::from_processed(&manifest, animations, variant)
+        })
+    }
 
-/// Find the bounding box of non-transparent content in an image
-fn find_content_bounds(img: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
-    let (width, height) = img.dimensions();
-    let mut min_x = width;
-    let mut min_y = height;
-    let mut max_x = 0u32;
-    let mut max_y = 0u32;
+    /// Recolor processed animations, making sure the ones the app plays are there
+    fn from_processed(
+        manifest: &Manifest,
+        mut processed: Vec<ProcessedAnimation>,
+        variant: &Variant,
+    ) -> Result<Self, SpriteError> {
+        if let Some(missing) = missing_animations(&processed).first() {
+            return Err(SpriteError::Invalid(format!(
+                "missing `{}` animation",
+                missing
+            )));
+        }
+        let machine = manifest.machine();
+        machine
+            .validate(|name| processed.iter().any(|a| a.name == name))
+            .map_err(SpriteError::Invalid)?;
+        variant.apply(manifest, &mut processed)?;
+
+        let animations = processed
+            .into_iter()
+            .map(|animation| {
+                let playable = Animation {
+                    frames: animation.frames.into_iter().map(Frame::new).collect(),
+                    frame_durations: animation.frame_durations,
+                    playback: animation.playback,
+                    weights: animation.weights,
+                };
+                (animation.name, playable)
+            })
+            .collect();
+
+        Ok(Self {
+            name: manifest.name.clone().unwrap_or_default(),
+            facing: manifest.facing,
+            machine,
+            animations,
+        })
+    }
+    pub fn animation(&self, name: &str) -> Option<&Animation> {
+        self.animations.get(name)
+    }
+
+    /// Every animation with its name, in no particular order
+    pub(crate) fn animations(&self) -> impl Iterator<Item = (&str, &Animation)> {
+        self.animations.iter().map(|(name, a)| (name.as_str(), a))
+    }
+
+    /// Names of all animations in the pack
+    pub fn animation_names(&self) -> impl Iterator<Item = &str> {
+        self.animations.keys().map(String::as_str)
+    }
+
+    /// Number of frames in an animation (0 if the pack doesn't have it)
+    pub fn frame_count(&self, name: &str) -> usize {
+        self.animation(name).map_or(0, |a| a.frames.len())
+    }
+
+    /// How long a frame of an animation is shown (index wraps around)
+    pub fn frame_duration(&self, name: &str, frame_index: usize) -> Duration {
+        match self.animation(name) {
+            Some(a) if !a.frame_durations.is_empty() => {
+                a.frame_durations[frame_index % a.frame_durations.len()]
+            }
+            _ => DEFAULT_FRAME_DURATION,
+        }
+    }
 
-    for (x, y, pixel) in img.enumerate_pixels() {
-        let Rgba([_, _, _, a]) = *pixel;
-        if a > 10 {  // Non-transparent pixel
-            min_x = min_x.min(x);
-            min_y = min_y.min(y);
-            max_x = max_x.max(x);
-            max_y = max_y.max(y);
+    /// An animation frame (index wraps around)
+    pub fn frame(&self, name: &str, frame_index: usize) -> Option<&Frame> {
+        let frames = &self.animation(name)?.frames;
+        if frames.is_empty() {
+            return None;
         }
+        Some(&frames[frame_index % frames.len()])
+    }
+
+    /// An animation frame looking the given way, mirrored if the drawing looks the
+    /// other way (index wraps around)
+    pub fn frame_facing(&self, name: &str, frame_index: usize, facing: Facing) -> Option<&Frame> {
+        let frame = self.frame(name, frame_index)?;
+        Some(if facing == self.facing {
+            frame
+        } else {
+            frame.mirrored()
+        })
     }
 
-    if max_x >= min_x && max_y >= min_y {
-        Some((min_x, min_y, max_x - min_x + 1, max_y - min_y + 1))
-    } else {
-        None
+    /// One pass through an animation (zero if the pack doesn't have it)
+    pub fn cycle_duration(&self, name: &str) -> Duration {
+        self.animation(name)
+            .map_or(Duration::ZERO, |a| a.frame_durations.iter().sum())
+    }
+}
+
+/// Read a pack directory's manifest and process every animation in it
+pub fn process_dir(dir: &Path) -> Result<(Manifest, Vec<ProcessedAnimation>), SpriteError> {
+    let manifest = read_manifest(dir)?;
+    let animations = process_files(dir, &manifest)?;
+    Ok((manifest, animations))
+}
+
+/// Parse a pack directory's manifest (the name defaults to the directory's)
+pub fn read_manifest(dir: &Path) -> Result<Manifest, SpriteError> {
+    let manifest_path = dir.join(MANIFEST_FILE_NAME);
+    let text = fs::read_to_string(&manifest_path)
+        .map_err(|err| SpriteError::Io(manifest_path.clone(), err))?;
+    let mut manifest = Manifest::parse(&text)?;
+    if manifest.name.is_none() {
+        manifest.name = dir.file_name().map(|n| n.to_string_lossy().into_owned());
+    }
+    Ok(manifest)
+}
+
+/// Every file processing reads for a manifest, as named in it (relative to `dir`)
+/// The manifest itself comes first; the rest are sorted
+pub fn pack_files(dir: &Path, manifest: &Manifest) -> Result<Vec<String>, SpriteError> {
+    let mut files = Vec::new();
+    if let Some(file) = &manifest.atlas {
+        files.push(file.clone());
+        // The atlas can name its own image
+        let image = match &manifest.image {
+            Some(image) => image.clone(),
+            None => {
+                let path = dir.join(file);
+                let json =
+                    fs::read_to_string(&path).map_err(|err| SpriteError::Io(path.clone(), err))?;
+                Atlas::parse(&json)?.image.ok_or_else(|| {
+                    SpriteError::Invalid("atlas names no image; set `image`".to_string())
+                })?
+            }
+        };
+        files.push(image);
+    }
+    for (name, spec) in &manifest.animations {
+        match manifest.source(name, spec)? {
+            FrameSource::Animated(file) => files.push(file.to_string()),
+            source => files.extend(source.images().into_iter().map(str::to_string)),
+        }
     }
+    files.sort();
+    files.dedup();
+    files.insert(0, MANIFEST_FILE_NAME.to_string());
+    Ok(files)
 }
 
-/// Center the content within a frame of the specified size
-fn center_content(img: &RgbaImage, target_width: u32, target_height: u32) -> RgbaImage {
-    let Some((content_x, content_y, content_w, content_h)) = find_content_bounds(img) else {
-        return img.clone();
+/// Process a manifest's animations, reading files relative to `dir`
+pub fn process_files(
+    dir: &Path,
+    manifest: &Manifest,
+) -> Result<Vec<ProcessedAnimation>, SpriteError> {
+    process(manifest, |name| {
+        let path: PathBuf = dir.join(name);
+        fs::read(&path).map_err(|err| SpriteError::Io(path, err))
+    })
+}
+
+/// Cut, clean up and anchor every animation the manifest describes
+/// `read` returns the contents of a file named in the manifest
+fn process(
+    manifest: &Manifest,
+    read: impl Fn(&str) -> Result<Vec<u8>, SpriteError> + Sync,
+) -> Result<Vec<ProcessedAnimation>, SpriteError> {
+    let decode = |name: &str| -> Result<DynamicImage, SpriteError> {
+        image::load_from_memory(&read(name)?)
+            .map_err(|err| SpriteError::Image(name.to_string(), err))
     };
 
-    // Create new image with transparent background
-    let mut result = RgbaImage::from_pixel(target_width, target_height, Rgba([0, 0, 0, 0]));
+    // Atlas packs: the JSON describes the frames, the image holds them
+    let atlas = match &manifest.atlas {
+        Some(file) => {
+            let json = String::from_utf8(read(file)?)
+                .map_err(|_| SpriteError::Invalid(format!("{} is not UTF-8", file)))?;
+            let atlas = Atlas::parse(&json)?;
+            let image = manifest
+                .image
+                .clone()
+                .or_else(|| atlas.image.clone())
+                .ok_or_else(|| {
+                    SpriteError::Invalid("atlas names no image; set `image`".to_string())
+                })?;
+            let sheet = decode(&image)?;
+            Some((atlas, sheet))
+        }
+        None => None,
+    };
 
-    // Calculate offset to center the content
-    let offset_x = (target_width.saturating_sub(content_w)) / 2;
-    let offset_y = (target_height.saturating_sub(content_h)) / 2;
+    let mut sources = manifest
+        .animations
+        .iter()
+        .map(|(name, spec)| Ok((name.clone(), Some(spec), manifest.source(name, spec)?)))
+        .collect::<Result<Vec<_>, SpriteError>>()?;
 
-    // Copy content to centered position
-    for y in 0..content_h {
-        for x in 0..content_w {
-            let src_x = content_x + x;
-            let src_y = content_y + y;
-            let dst_x = offset_x + x;
-            let dst_y = offset_y + y;
+    // Atlas tags the manifest doesn't mention become animations of the same name
+    if let Some((atlas, _)) = &atlas {
+        for tag in &atlas.tags {
+            let name = tag.name.to_lowercase();
+            if !sources.iter().any(|(existing, _, _)| *existing == name) {
+                sources.push((
+                    name,
+                    None,
+                    FrameSource::Atlas(AtlasSelector::Tag(&tag.name)),
+                ));
+            }
+        }
+    }
 
-            if src_x < img.width() && src_y < img.height()
-                && dst_x < target_width && dst_y < target_height
-            {
-                result.put_pixel(dst_x, dst_y, *img.get_pixel(src_x, src_y));
+    // Decode each image once even if several animations share it
+    let mut files: Vec<&str> = sources.iter().flat_map(|(_, _, s)| s.images()).collect();
+    files.sort_unstable();
+    files.dedup();
+    let images = files
+        .into_par_iter()
+        .map(|file| Ok((file, decode(file)?)))
+        .collect::<Result<HashMap<&str, DynamicImage>, SpriteError>>()?;
+
+    let mut animations = Vec::new();
+    for (name, spec, source) in &sources {
+        // Per-frame durations from an atlas or animated file, if the source has any
+        let mut source_durations = Vec::new();
+
+        let frames = match source {
+            FrameSource::Grid { image, grid, cells } => {
+                let sheet = &images[image];
+                let width = grid.frame_width.unwrap_or(sheet.width() / grid.columns);
+                let height = grid.frame_height.unwrap_or(sheet.height() / grid.rows);
+                if width == 0 || height == 0 {
+                    return Err(SpriteError::Invalid(format!(
+                        "{} ({}x{}) is too small for a {}x{} grid",
+                        image,
+                        sheet.width(),
+                        sheet.height(),
+                        grid.columns,
+                        grid.rows
+                    )));
+                }
+                let rects: Vec<FrameRect> = cells
+                    .iter()
+                    .map(|cell| {
+                        let x = (cell % grid.columns).checked_mul(width);
+                        let y = (cell / grid.columns).checked_mul(height);
+                        match (x, y) {
+                            (Some(x), Some(y)) => Ok(FrameRect {
+                                x,
+                                y,
+                                width,
+                                height,
+                            }),
+                            _ => Err(SpriteError::Invalid(format!(
+                                "frame {} is outside the {}x{} image",
+                                cell,
+                                sheet.width(),
+                                sheet.height()
+                            ))),
+                        }
+                    })
+                    .collect::<Result<_, _>>()?;
+                extract_frames(sheet, &rects, manifest.background)?
+            }
+            FrameSource::Rects { image, rects } => {
+                let rects: Vec<FrameRect> = rects
+                    .iter()
+                    .map(|&[x, y, width, height]| FrameRect {
+                        x,
+                        y,
+                        width,
+                        height,
+                    })
+                    .collect();
+                extract_frames(&images[image], &rects, manifest.background)?
+            }
+            FrameSource::Files(files) => files
+                .par_iter()
+                .map(|file| {
+                    let img = &images[file.as_str()];
+                    let rect = FrameRect {
+                        x: 0,
+                        y: 0,
+                        width: img.width(),
+                        height: img.height(),
+                    };
+                    Ok(extract_frames(img, &[rect], manifest.background)?.remove(0))
+                })
+                .collect::<Result<Vec<_>, SpriteError>>()?,
+            FrameSource::Animated(file) => {
+                let decoded = decode_animation(file, &read(file)?)?;
+                if decoded.is_empty() {
+                    return Err(SpriteError::Invalid(format!("{} has no frames", file)));
+                }
+                source_durations = decoded.iter().map(|(_, delay)| *delay).collect();
+                decoded
+                    .par_iter()
+                    .map(|(frame, _)| background::remove(frame, manifest.background))
+                    .collect()
+            }
+            FrameSource::Atlas(selector) => {
+                let (atlas, sheet) = atlas.as_ref().ok_or_else(|| {
+                    SpriteError::Invalid(format!("animation `{}` needs an atlas", name))
+                })?;
+                let indices = atlas_frames(atlas, name, *selector)?;
+                source_durations = indices.iter().map(|&i| atlas.frames[i].duration).collect();
+                indices
+                    .par_iter()
+                    .map(|&i| {
+                        Ok(background::remove(
+                            &atlas.frame_image(sheet, i)?,
+                            manifest.background,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        // Per-frame lists must cover every frame exactly
+        let check_length = |what: &str, len: usize| {
+            if len == frames.len() {
+                Ok(())
+            } else {
+                Err(SpriteError::Invalid(format!(
+                    "animation `{}` has {} frames but {} {}",
+                    name,
+                    frames.len(),
+                    len,
+                    what
+                )))
+            }
+        };
+        // Line the frames up so the character stays planted
+        let anchoring = match spec.and_then(|s| s.anchors.as_deref()) {
+            Some(points) => {
+                check_length("anchors", points.len())?;
+                Anchoring::Points(points)
             }
+            None => Anchoring::Mode(spec.and_then(|s| s.anchor).unwrap_or(manifest.anchor)),
+        };
+        let size = anchor::frame_size(&frames);
+        let placements = anchor::plan(&frames, anchoring, size);
+        let frames_out = frames
+            .par_iter()
+            .zip(&placements)
+            .map(|(frame, placement)| anchor::apply(frame, placement, size))
+            .collect();
+
+        let durations = spec.and_then(|s| s.durations.as_deref());
+        if let Some(durations) = durations {
+            check_length("durations", durations.len())?;
+        }
+        let weights = spec.and_then(|s| s.weights.clone()).unwrap_or_default();
+        if !weights.is_empty() {
+            check_length("weights", weights.len())?;
         }
+
+        // The manifest's timing wins over the file's own
+        let frame_durations = (0..frames.len())
+            .map(|i| match (durations, spec.and_then(|s| s.frame_ms)) {
+                (Some(durations), _) => Duration::from_millis(durations[i]),
+                (None, Some(ms)) => Duration::from_millis(ms),
+                (None, None) => source_durations
+                    .get(i)
+                    .copied()
+                    .flatten()
+                    .unwrap_or(DEFAULT_FRAME_DURATION),
+            })
+            .collect();
+
+        animations.push(ProcessedAnimation {
+            name: name.clone(),
+            source_sizes: frames.iter().map(RgbaImage::dimensions).collect(),
+            frames: frames_out,
+            placements,
+            frame_durations,
+            playback: spec.map_or(Playback::Loop, |s| s.playback),
+            weights,
+        });
     }
 
-    result
+    Ok(animations)
 }
 
-/// Check if sprite sheet is loaded
-pub fn is_loaded() -> bool {
-    SPRITE_SHEET.is_some()
+/// Animations the app needs that aren't in a processed pack
+pub fn missing_animations(animations: &[ProcessedAnimation]) -> Vec<&'static str> {
+    REQUIRED_ANIMATIONS
+        .iter()
+        .copied()
+        .filter(|required| !animations.iter().any(|a| a.name == *required))
+        .collect()
 }
 
-/// Get a typing animation frame as PNG bytes
-pub fn get_typing_frame(frame_index: usize) -> Option<&'static [u8]> {
-    let frames = &*TYPING_FRAME_BYTES;
-    if frames.is_empty() {
-        return None;
+/// Decode every frame of an animated GIF, APNG or WebP with its delay
+/// (`None` for a zero delay, which viewers treat as "use a default")
+fn decode_animation(
+    name: &str,
+    bytes: &[u8],
+) -> Result<Vec<(RgbaImage, Option<Duration>)>, SpriteError> {
+    let image_error = |err| SpriteError::Image(name.to_string(), err);
+    let format = image::guess_format(bytes).map_err(image_error)?;
+    let frames = match format {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(bytes))
+            .map_err(image_error)?
+            .into_frames(),
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(Cursor::new(bytes)).map_err(image_error)?;
+            if !decoder.is_apng().map_err(image_error)? {
+                // A still PNG is a one-frame animation
+                let image = DynamicImage::from_decoder(decoder).map_err(image_error)?;
+                return Ok(vec![(image.to_rgba8(), None)]);
+            }
+            decoder.apng().map_err(image_error)?.into_frames()
+        }
+        ImageFormat::WebP => WebPDecoder::new(Cursor::new(bytes))
+            .map_err(image_error)?
+            .into_frames(),
+        _ => {
+            return Err(SpriteError::Invalid(format!(
+                "{} is not a GIF, PNG or WebP file",
+                name
+            )))
+        }
+    };
+
+    frames
+        .map(|frame| {
+            let frame = frame.map_err(image_error)?;
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let ms = numer.checked_div(denom).unwrap_or(0);
+            let delay = (ms > 0).then(|| Duration::from_millis(ms as u64));
+            Ok((frame.into_buffer(), delay))
+        })
+        .collect()
+}
+
+/// Resolve which atlas frames an animation plays, in order
+fn atlas_frames(
+    atlas: &Atlas,
+    name: &str,
+    selector: AtlasSelector,
+) -> Result<Vec<usize>, SpriteError> {
+    let invalid =
+        |reason: String| SpriteError::Invalid(format!("animation `{}`: {}", name, reason));
+    match selector {
+        AtlasSelector::Indices(indices) => indices
+            .iter()
+            .map(|&i| {
+                let i = i as usize;
+                if i < atlas.frames.len() {
+                    Ok(i)
+                } else {
+                    Err(invalid(format!("atlas has no frame {}", i)))
+                }
+            })
+            .collect(),
+        AtlasSelector::Tag(tag) => atlas
+            .tag(tag)
+            .map(|t| t.frames.clone())
+            .ok_or_else(|| invalid(format!("atlas has no tag `{}`", tag))),
+        AtlasSelector::Prefix(prefix) => {
+            let indices = atlas.frames_with_prefix(prefix);
+            if indices.is_empty() {
+                return Err(invalid(format!("no atlas frames start with `{}`", prefix)));
+            }
+            Ok(indices)
+        }
     }
-    let index = frame_index % frames.len();
-    Some(&frames[index])
 }
 
-/// Get an idle animation frame as PNG bytes
-pub fn get_idle_frame(frame_index: usize) -> Option<&'static [u8]> {
-    let frames = &*IDLE_FRAME_BYTES;
-    if frames.is_empty() {
-        return None;
+/// Crop frames out of a sheet and remove their background
+fn extract_frames(
+    sheet: &DynamicImage,
+    frames: &[FrameRect],
+    background: Background,
+) -> Result<Vec<RgbaImage>, SpriteError> {
+    frames
+        .par_iter()
+        .map(|rect| {
+            let fits = |start: u32, size: u32, limit: u32| {
+                start.checked_add(size).is_some_and(|end| end <= limit)
+            };
+            if !fits(rect.x, rect.width, sheet.width())
+                || !fits(rect.y, rect.height, sheet.height())
+            {
+                return Err(SpriteError::Invalid(format!(
+                    "frame at {},{} ({}x{}) is outside the {}x{} image",
+                    rect.x,
+                    rect.y,
+                    rect.width,
+                    rect.height,
+                    sheet.width(),
+                    sheet.height()
+                )));
+            }
+
+            // Crop the frame from the sprite sheet
+            let cropped = sheet.crop_imm(rect.x, rect.y, rect.width, rect.height);
+            Ok(background::remove(&cropped.to_rgba8(), background))
+        })
+        .collect()
+}
+
+/// Whether the built-in dog loads (`SpritePack::embedded` is empty otherwise)
+pub fn is_loaded() -> bool {
+    SpritePack::embedded_variant(&Variant::default(), None).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::testing::write_pack;
+    use tempfile::tempdir;
+
+    #[test]
+    fn frames_past_the_image_are_rejected() {
+        let sheet = DynamicImage::ImageRgba8(RgbaImage::new(32, 32));
+        let rect = |x, y, width, height| FrameRect {
+            x,
+            y,
+            width,
+            height,
+        };
+        assert!(extract_frames(&sheet, &[rect(16, 16, 16, 16)], Background::None).is_ok());
+        assert!(extract_frames(&sheet, &[rect(17, 0, 16, 16)], Background::None).is_err());
+        // Sizes that would wrap around are outside too
+        let err = extract_frames(&sheet, &[rect(8, 0, u32::MAX, 16)], Background::None)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("is outside the 32x32 image"), "{}", err);
+        assert!(extract_frames(&sheet, &[rect(0, 1, 16, u32::MAX)], Background::None).is_err());
+    }
+
+    #[test]
+    fn gif_frames_keep_their_delays() {
+        let frames = decode_animation("wag.gif", &gif(&[70, 60, 120])).unwrap();
+        let ms = |delay: Option<Duration>| delay.map(|d| d.as_millis());
+        let delays: Vec<_> = frames.iter().map(|(_, delay)| ms(*delay)).collect();
+        assert_eq!(delays, [Some(70), Some(60), Some(120)]);
+        // Each frame is its own picture
+        let reds: Vec<_> = frames
+            .iter()
+            .map(|(frame, _)| {
+                assert_eq!(frame.dimensions(), (2, 2));
+                frame.get_pixel(0, 0)[0]
+            })
+            .collect();
+        assert_eq!(reds, [0, 50, 100]);
+    }
+
+    /// A GIF of 2x2 frames shown for each of `delays`
+    fn gif(delays: &[u32]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = image::codecs::gif::GifEncoder::new(&mut bytes);
+        for (i, &ms) in delays.iter().enumerate() {
+            let pixels = RgbaImage::from_pixel(2, 2, image::Rgba([i as u8 * 50, 0, 0, 255]));
+            let delay = image::Delay::from_numer_denom_ms(ms, 1);
+            encoder
+                .encode_frame(image::Frame::from_parts(pixels, 0, 0, delay))
+                .unwrap();
+        }
+        drop(encoder);
+        bytes
+    }
+
+    #[test]
+    fn zero_delays_get_the_default_duration() {
+        let frames = decode_animation("blink.gif", &gif(&[0, 50])).unwrap();
+        let delays: Vec<_> = frames.iter().map(|(_, delay)| *delay).collect();
+        assert_eq!(delays, [None, Some(Duration::from_millis(50))]);
+
+        // A zero delay would flash past, so the frame is shown for the default
+        let dir = tempdir().unwrap();
+        write_pack(
+            dir.path(),
+            [200, 100, 50],
+            "[animations.blink]\nanimated = \"blink.gif\"",
+        );
+        fs::write(dir.path().join("blink.gif"), gif(&[0, 50])).unwrap();
+        let (_, animations) = process_dir(dir.path()).unwrap();
+        let blink = animations.iter().find(|a| a.name == "blink").unwrap();
+        assert_eq!(
+            blink.frame_durations,
+            [DEFAULT_FRAME_DURATION, Duration::from_millis(50)]
+        );
+
+        // A still image is a single frame with no delay of its own
+        let still = fs::read(dir.path().join("frame.png")).unwrap();
+        let frames = decode_animation("frame.png", &still).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].1, None);
+        assert!(decode_animation("notes.txt", b"not an image").is_err());
     }
-    let index = frame_index % frames.len();
-    Some(&frames[index])
 }
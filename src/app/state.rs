@@ -1,52 +1,159 @@
-use rand::Rng;
+use super::clock::{Clock, SystemClock};
+use super::emote::{self, Emote, EmoteQueue, Priority, Queued};
+use super::log;
+use super::machine::{self, Action, Inputs, Overlay, Shown, StartFrame};
+use super::manifest::Facing;
+use super::mistakes::{MistakeDetector, Sensitivity, Stroke};
+use super::mood::{Activity, Mood, MoodRates};
+use super::playback::Player;
+use super::spritesheet::{self, SpritePack};
+use super::tempo::{Tempo, TypingRate};
+use super::terminal::Direction;
+use super::text::TextBuffer;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ratatui::layout::Rect;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tachyonfx::{fx, Effect, Interpolation, Shader};
 
-/// Animation frame duration for typing effect (slower)
-const TYPING_ANIMATION_DURATION: Duration = Duration::from_millis(250);
-
-/// Animation frame duration for idle effect (very slow - 10 seconds per frame)
-const IDLE_ANIMATION_DURATION: Duration = Duration::from_secs(10);
-
-/// Number of typing frames in the sprite sheet (rows 3-4)
-const TYPING_FRAME_COUNT: usize = 8;
-
-/// Number of idle frames in the sprite sheet (rows 1-2)
-const IDLE_FRAME_COUNT: usize = 8;
-
-/// How long to continue typing animation after last keypress
-const TYPING_LINGER_DURATION: Duration = Duration::from_secs(3);
-
 /// Duration for fade-out effect when stopping typing
 const FADE_OUT_DURATION: u32 = 800; // milliseconds
 
 /// Duration for coalesce effect when typing (fast, snappy)
 const TYPING_EFFECT_DURATION: u32 = 150; // milliseconds
 
-/// Maximum length of accumulated text (generous limit to prevent memory issues)
+/// Maximum graphemes of accumulated text (generous limit to prevent memory issues)
 const MAX_TEXT_LENGTH: usize = 100;
 
+/// How long an error stays on the status line after it last occurred
+const STATUS_DURATION: Duration = Duration::from_secs(6);
+
+/// Columns either side of the dog's middle where a target doesn't make it turn
+const FACING_DEAD_ZONE: u16 = 2;
+
+/// How long without keys before the dog falls asleep, unless configured
+pub const DEFAULT_SLEEP_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Typing slower than this (keys per second) counts as a break for the mood
+const MOOD_TYPING_RATE: f32 = 0.2;
+
+/// Most states passed through in one go (a pack may chain optional states in a loop)
+const MAX_SKIPPED_STATES: usize = 8;
+
+/// What a key does to the text when emulating an editor's cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Left,
+    Right,
+    Home,
+    End,
+    DeleteForward,
+    DeleteWord,
+}
+
+/// What the dog last paid attention to, deciding which way it looks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FacingCue {
+    /// Where new characters appear
+    Text,
+    /// The screen edge the window was snapped to
+    Edge(Facing),
+    /// The mouse pointer's column
+    Mouse(u16),
+}
+
+/// A turn animation in progress
+#[derive(Debug, Clone)]
+pub struct Turn {
+    pub player: Player,
+    /// Which way the dog looked before turning (the frames are shown that way)
+    pub from: Facing,
+    pub until: Instant,
+}
+
+/// What the sprite shows: an animation frame, looking one way
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pose {
+    pub animation: String,
+    pub frame: usize,
+    pub facing: Facing,
+    /// Darkened, for a state showing another state's frames
+    pub dim: bool,
+    /// Columns right and rows down from where the sprite stands
+    pub shift: (i16, i16),
+}
+
+/// What the dog was doing before its states came from the sprite pack
+#[deprecated(
+    note = "states are named by the pack's state machine now; read `App::animation_state.name`"
+)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AnimationState {
     Idle,
     Typing,
 }
 
+#[allow(deprecated)]
+impl From<&machine::AnimationState> for AnimationState {
+    /// `typing` is typing; any other state counts as idle
+    fn from(state: &machine::AnimationState) -> Self {
+        if state.name == machine::TYPING {
+            Self::Typing
+        } else {
+            Self::Idle
+        }
+    }
+}
+
+/// A short message shown on the status line
+pub struct StatusMessage {
+    pub text: String,
+    pub shown_at: Instant,
+}
+
 pub struct App {
-    /// Current animation state
-    pub animation_state: AnimationState,
-    /// Current animation frame for typing animation
-    pub typing_frame: usize,
-    /// Current animation frame for idle animation
-    pub idle_frame: usize,
+    /// Current state of the pack's state machine
+    pub animation_state: machine::AnimationState,
+    /// Playback position in the current state's animation
+    pub player: Player,
+    /// Which way the dog looks
+    pub facing: Facing,
+    /// What the dog turns toward
+    pub facing_cue: Option<FacingCue>,
+    /// Turn animation playing, if any
+    pub turn: Option<Turn>,
+    /// One-shot reactions, played over the state's animation
+    pub emotes: EmoteQueue,
+    /// Playback position in the emote's animation
+    emote_player: Player,
+    /// Whether arrows, Home, End and deletes edit the text around a cursor instead
+    /// of showing the key's name
+    pub edit_cursor: bool,
+    /// Where the sprite was last laid out
+    pub sprite_area: Option<Rect>,
+    /// Column just after the last typed character, as last rendered
+    pub caret_column: Option<u16>,
     /// Time of the last keypress (for typing animation duration)
     pub last_keypress_time: Instant,
+    /// Keys per second, smoothed
+    pub typing_rate: TypingRate,
+    /// How far the typing rate speeds up or slows down `tempo` states
+    pub tempo: Tempo,
+    /// Watches for error-heavy typing
+    pub mistakes: MistakeDetector,
+    /// Kept between sessions; read by the state machine as gauges
+    pub mood: Mood,
+    pub mood_rates: MoodRates,
+    /// When the mood was last moved along
+    mood_updated: Instant,
+    /// Animation variant picked on entering the current state
+    variant: Option<String>,
+    /// How long without keys before falling asleep (`None`: stay awake)
+    pub sleep_after: Option<Duration>,
     /// Accumulated typed text (cleared on special keys or timeout)
-    pub typed_text: String,
-    /// Time of last typing animation frame change
-    pub last_typing_frame_time: Instant,
-    /// Time of last idle animation frame change
-    pub last_idle_frame_time: Instant,
+    pub typed_text: TextBuffer,
     /// Whether the app should quit
     pub should_quit: bool,
     /// Scanline offset for animation effect
@@ -54,9 +161,9 @@ pub struct App {
     /// Frame counter for effects
     pub frame_count: u64,
     /// Track last rendered state to avoid unnecessary image redraws
-    pub last_rendered_state: Option<AnimationState>,
-    /// Track last rendered frame index
-    pub last_rendered_frame: usize,
+    pub last_rendered_state: Option<machine::AnimationState>,
+    /// Track last rendered animation frame and facing
+    pub last_rendered_pose: Option<Pose>,
     /// Track last rendered text
     pub last_rendered_text: String,
     /// Track last terminal size for redraw on resize
@@ -69,47 +176,179 @@ pub struct App {
     pub last_frame_time: Instant,
     /// Whether current text is from a special key (should be cleared on next regular key)
     pub is_special_key_text: bool,
-    /// Number of new characters added in the last keypress (for partial animation)
-    pub new_char_count: usize,
+    /// Columns taken by the characters added in the last keypress (for partial animation)
+    pub new_char_width: usize,
+    /// Most recent error, shown briefly on the status line
+    pub status: Option<StatusMessage>,
+    /// Log file errors are appended to (`None`: not logged)
+    log: Option<PathBuf>,
+    /// Frames and timing for every animation
+    pub sprites: Arc<SpritePack>,
+    /// Time source for all animation timing
+    clock: Arc<dyn Clock>,
+    /// Random source for idle frame variety
+    rng: StdRng,
 }
 
 impl App {
     pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Create an app playing `sprites` instead of the built-in dog
+    pub fn from_sprites(sprites: Arc<SpritePack>) -> Self {
+        Self::with_clock_and_sprites(Arc::new(SystemClock), sprites)
+    }
+
+    /// Create an app driven by the given clock instead of the wall clock
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self::with_clock_and_sprites(clock, SpritePack::embedded())
+    }
+
+    /// Create an app driven by `clock`, playing `sprites`
+    pub fn with_clock_and_sprites(clock: Arc<dyn Clock>, sprites: Arc<SpritePack>) -> Self {
+        let now = clock.now();
         Self {
-            animation_state: AnimationState::Idle,
-            typing_frame: 0,
-            idle_frame: 0,
-            last_keypress_time: Instant::now(),
-            typed_text: String::new(),
-            last_typing_frame_time: Instant::now(),
-            last_idle_frame_time: Instant::now(),
+            animation_state: machine::AnimationState::new(now),
+            player: Player::new(now),
+            facing: Facing::default(),
+            facing_cue: None,
+            turn: None,
+            emotes: EmoteQueue::default(),
+            emote_player: Player::new(now),
+            edit_cursor: false,
+            sprite_area: None,
+            caret_column: None,
+            last_keypress_time: now,
+            typing_rate: TypingRate::default(),
+            tempo: Tempo::default(),
+            mistakes: MistakeDetector::new(Sensitivity::default()),
+            mood: Mood::default(),
+            mood_rates: MoodRates::default(),
+            mood_updated: now,
+            variant: None,
+            sleep_after: Some(DEFAULT_SLEEP_AFTER),
+            typed_text: TextBuffer::new(MAX_TEXT_LENGTH),
             should_quit: false,
             scanline_offset: 0,
             frame_count: 0,
             last_rendered_state: None,
-            last_rendered_frame: 0,
+            last_rendered_pose: None,
             last_rendered_text: String::new(),
             last_terminal_size: (0, 0),
             fade_effect: None,
             typing_effect: None,
-            last_frame_time: Instant::now(),
+            last_frame_time: now,
             is_special_key_text: false,
-            new_char_count: 0,
+            new_char_width: 0,
+            status: None,
+            log: None,
+            sprites,
+            clock,
+            rng: StdRng::from_entropy(),
         }
+        .looking_as_drawn()
+    }
+
+    /// Look the way the pack's drawing does, starting from the pack's first state
+    fn looking_as_drawn(mut self) -> Self {
+        let now = self.now();
+        self.facing = self.sprites.facing;
+        self.animation_state = machine::AnimationState::new(now);
+        self.player = Player::new(now);
+        self.variant = None;
+        self
+    }
+
+    /// Use a fixed random seed so idle frame choices are reproducible
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Fall asleep after this long without keys (`None`: never)
+    pub fn with_sleep_after(mut self, sleep_after: Option<Duration>) -> Self {
+        self.sleep_after = sleep_after;
+        self
+    }
+
+    /// Limit how far the typing rate changes animation speed
+    pub fn with_tempo(mut self, tempo: Tempo) -> Self {
+        self.tempo = tempo;
+        self
+    }
+
+    /// How readily error-heavy typing makes the dog confused
+    pub fn with_mistake_sensitivity(mut self, sensitivity: Sensitivity) -> Self {
+        self.mistakes = MistakeDetector::new(sensitivity);
+        self
+    }
+
+    /// Start from a saved mood
+    pub fn with_mood(mut self, mood: Mood) -> Self {
+        self.mood = mood;
+        self
+    }
+
+    /// How fast the mood changes
+    pub fn with_mood_rates(mut self, rates: MoodRates) -> Self {
+        self.mood_rates = rates;
+        self
+    }
+
+    /// Show the text the way an editor would, with a cursor the keys move
+    pub fn with_edit_cursor(mut self, edit_cursor: bool) -> Self {
+        self.edit_cursor = edit_cursor;
+        self
+    }
+
+    /// Append reported errors to the log file at `path` (see `log::path`)
+    pub fn with_log(mut self, path: Option<PathBuf>) -> Self {
+        self.log = path;
+        self
+    }
+
+    /// Use a different sprite pack instead of the built-in dog
+    pub fn with_sprites(mut self, sprites: Arc<SpritePack>) -> Self {
+        self.sprites = sprites;
+        self.looking_as_drawn()
+    }
+
+    /// Current time according to the app's clock
+    pub fn now(&self) -> Instant {
+        self.clock.now()
     }
 
     /// Check if a key should be ignored (modifier keys)
     fn is_ignored_key(key: &str) -> bool {
-        matches!(key, "⇧" | "⌃" | "⌥" | "⌘")  // Shift, Ctrl, Alt, Cmd
+        matches!(key, "⇧" | "⌃" | "⌥" | "⌘") // Shift, Ctrl, Alt, Cmd
     }
 
     /// Check if a key is a special key that should clear accumulated text
     fn is_special_key(key: &str) -> bool {
         matches!(
             key,
-            "⏎" | "⇥" | "⎋" | "⌦"
-            | "↑" | "↓" | "←" | "→"
-            | "F1" | "F2" | "F3" | "F4" | "F5" | "F6" | "F7" | "F8" | "F9" | "F10" | "F11" | "F12"
+            "⏎" | "⇥"
+                | "⎋"
+                | "⌦"
+                | "⇱"
+                | "⇲"
+                | "↑"
+                | "↓"
+                | "←"
+                | "→"
+                | "F1"
+                | "F2"
+                | "F3"
+                | "F4"
+                | "F5"
+                | "F6"
+                | "F7"
+                | "F8"
+                | "F9"
+                | "F10"
+                | "F11"
+                | "F12"
         )
     }
 
@@ -125,6 +364,8 @@ impl App {
             "⇥" => "Tab",
             "⎋" => "Esc",
             "⌦" => "Del",
+            "⇱" => "Home",
+            "⇲" => "End",
             "↑" => "Up",
             "↓" => "Down",
             "←" => "Left",
@@ -133,36 +374,100 @@ impl App {
         }
     }
 
+    /// Check if a key is a chord sent as one string, like `⌃w` (see `keys::chord`)
+    fn is_chord(key: &str) -> bool {
+        ["⌃", "⌥", "⌘"]
+            .iter()
+            .any(|modifier| key.len() > modifier.len() && key.starts_with(modifier))
+    }
+
+    /// Check if a key is a save (Ctrl+S or Cmd+S)
+    fn is_save(key: &str) -> bool {
+        matches!(key, "⌃s" | "⌘s")
+    }
+
+    /// What `key` does to the text in editor mode
+    fn edit_for(key: &str) -> Option<Edit> {
+        match key {
+            "⌃w" | "⌥⌫" => Some(Edit::DeleteWord),
+            "←" => Some(Edit::Left),
+            "→" => Some(Edit::Right),
+            // The text is one line, so up and down go to its ends
+            "↑" | "⇱" => Some(Edit::Home),
+            "↓" | "⇲" => Some(Edit::End),
+            "⌦" => Some(Edit::DeleteForward),
+            _ => None,
+        }
+    }
+
     /// Handle a key press event
     pub fn on_key(&mut self, key: String) {
-        // Ignore modifier keys entirely
+        let now = self.now();
+
+        // Ignore modifier keys
         if Self::is_ignored_key(&key) {
             return;
         }
 
-        self.last_keypress_time = Instant::now();
+        let edit = Self::edit_for(&key).filter(|_| self.edit_cursor);
+        // A save is no typing; any other chord only counts as the edit it makes
+        if Self::is_save(&key) {
+            self.emote(Emote::Hearts, Priority::Normal);
+            return;
+        }
+        if Self::is_chord(&key) && edit.is_none() {
+            return;
+        }
+
+        self.last_keypress_time = now;
+        self.typing_rate.record(now);
+        self.facing_cue = Some(FacingCue::Text);
+        let mut events = vec![machine::KEY];
 
         // Clear any fade effect since we're typing again
         self.fade_effect = None;
 
-        // Handle backspace - delete last character
-        if Self::is_backspace(&key) {
+        if let Some(edit) = edit {
+            // A key's name shown is no text to edit
+            if self.is_special_key_text {
+                self.typed_text.clear();
+                self.is_special_key_text = false;
+            }
+            match edit {
+                Edit::Left => self.typed_text.left(),
+                Edit::Right => self.typed_text.right(),
+                Edit::Home => self.typed_text.home(),
+                Edit::End => self.typed_text.end(),
+                Edit::DeleteForward => {
+                    self.typed_text.delete_forward();
+                }
+                Edit::DeleteWord => {
+                    self.typed_text.delete_word_back();
+                }
+            }
+            events.push(match edit {
+                Edit::DeleteForward | Edit::DeleteWord => machine::BACKSPACE,
+                _ => machine::SPECIAL,
+            });
+            self.new_char_width = 0;
+        } else if Self::is_backspace(&key) {
+            // Delete the character before the cursor
+            events.push(machine::BACKSPACE);
             if !self.typed_text.is_empty() {
                 // If showing special key text, just clear it
                 if self.is_special_key_text {
                     self.typed_text.clear();
                     self.is_special_key_text = false;
                 } else {
-                    // Remove last character
-                    self.typed_text.pop();
+                    self.typed_text.delete_back();
                 }
             }
-            self.new_char_count = 0;
+            self.new_char_width = 0;
         } else if Self::is_special_key(&key) {
+            events.push(machine::SPECIAL);
             // Replace text with special key display
-            let display = Self::get_special_key_display(&key).to_string();
-            self.new_char_count = display.chars().count(); // All chars are "new"
-            self.typed_text = display;
+            self.typed_text.set(Self::get_special_key_display(&key));
+            self.new_char_width = self.typed_text.width(); // All chars are "new"
             self.is_special_key_text = true;
         } else {
             // If previous text was from a special key, clear it first
@@ -171,81 +476,337 @@ impl App {
                 self.is_special_key_text = false;
             }
 
-            // Track how many new characters we're adding
-            self.new_char_count = key.chars().count();
-
-            // Append to accumulated text
-            self.typed_text.push_str(&key);
+            // Add to accumulated text at the cursor (the oldest characters go past
+            // the limit), tracking how much of it is new
+            self.new_char_width = self.typed_text.insert_str(&key);
+        }
 
-            // Limit text length
-            if self.typed_text.len() > MAX_TEXT_LENGTH {
-                // Keep only the last MAX_TEXT_LENGTH characters
-                let start = self.typed_text.len() - MAX_TEXT_LENGTH;
-                self.typed_text = self.typed_text[start..].to_string();
+        let stroke = if let Some(edit) = edit {
+            match edit {
+                Edit::DeleteForward | Edit::DeleteWord => Stroke::Backspace,
+                _ => Stroke::Special,
             }
+        } else if Self::is_backspace(&key) {
+            Stroke::Backspace
+        } else if Self::is_special_key(&key) {
+            Stroke::Special
+        } else {
+            Stroke::Text(&key)
+        };
+        if self.mistakes.on_key(stroke, now).is_some() {
+            events.push(machine::MISTAKES);
         }
 
-        // Start typing animation if not already typing
-        if self.animation_state != AnimationState::Typing {
-            self.animation_state = AnimationState::Typing;
-            self.typing_frame = 0;
-            self.last_typing_frame_time = Instant::now();
+        // Let the state machine react (idle starts typing)
+        self.update_state(&events, now);
+
+        if key == "⏎" {
+            self.emote(Emote::TailWag, Priority::Low);
         }
 
         // Trigger coalesce effect for each keypress (text materializes)
-        if self.new_char_count > 0 {
-            self.typing_effect = Some(fx::coalesce((TYPING_EFFECT_DURATION, Interpolation::QuadOut)));
+        if self.new_char_width > 0 {
+            self.typing_effect = Some(fx::coalesce((
+                TYPING_EFFECT_DURATION,
+                Interpolation::QuadOut,
+            )));
         }
     }
 
     /// Update animation state based on timing
     pub fn tick(&mut self) {
-        let now = Instant::now();
+        let now = self.now();
         self.frame_count = self.frame_count.wrapping_add(1);
 
         // Update scanline animation
-        if self.frame_count % 3 == 0 {
+        if self.frame_count.is_multiple_of(3) {
             self.scanline_offset = (self.scanline_offset + 1) % 20;
         }
 
-        // Animate based on current state
-        match self.animation_state {
-            AnimationState::Typing => {
-                // Advance typing animation frames
-                if now.duration_since(self.last_typing_frame_time) >= TYPING_ANIMATION_DURATION {
-                    self.last_typing_frame_time = now;
-                    self.typing_frame = (self.typing_frame + 1) % TYPING_FRAME_COUNT;
-                }
+        // Expire the status line
+        if self
+            .status
+            .as_ref()
+            .is_some_and(|s| now.duration_since(s.shown_at) >= STATUS_DURATION)
+        {
+            self.status = None;
+        }
 
-                // Check if we should transition to idle (3 seconds after last keypress)
-                if now.duration_since(self.last_keypress_time) >= TYPING_LINGER_DURATION {
-                    self.animation_state = AnimationState::Idle;
-                    // Randomly select an idle frame for variety
-                    self.idle_frame = rand::thread_rng().gen_range(0..IDLE_FRAME_COUNT);
-                    self.last_idle_frame_time = now;
+        self.update_facing(now);
+        self.update_emotes(now);
 
-                    // Start dissolve effect for the text (characters disappear randomly)
-                    self.fade_effect = Some(fx::dissolve((FADE_OUT_DURATION, Interpolation::QuadOut)));
-                }
+        let sprites = self.sprites.clone();
+        let activity = if self.typing_rate.at(now) >= MOOD_TYPING_RATE {
+            Activity::Typing
+        } else {
+            Activity::Break
+        };
+        self.mood.update(
+            now.duration_since(self.mood_updated),
+            activity,
+            &self.mood_rates,
+        );
+        self.mood_updated = now;
+
+        let animation = sprites.animation(&self.current_animation().0);
+        let speed = self.speed(now);
+        self.player
+            .update_at_speed(animation, now, speed, &mut self.rng);
+        self.update_state(&[], now);
+
+        // Clear text after fade effect completes
+        if let Some(ref effect) = self.fade_effect {
+            if effect.done() {
+                self.typed_text.clear();
+                self.fade_effect = None;
+            }
+        }
+    }
+
+    /// Move the state machine along, starting the new state's animation if it moved
+    /// States the pack has nothing to show for are passed straight through
+    fn update_state(&mut self, events: &[&str], now: Instant) {
+        let sprites = self.sprites.clone();
+        let gauges = self.gauges(now);
+        let mut events = events;
+        let mut finished = self.player.is_finished();
+        // Bounded in case skipped states lead in a circle
+        for _ in 0..MAX_SKIPPED_STATES {
+            let inputs = Inputs {
+                now,
+                events,
+                since_key: now.duration_since(self.last_keypress_time),
+                sleep_after: self.sleep_after,
+                finished,
+                gauges: &gauges,
+            };
+            let Some(actions) = self.animation_state.update(&sprites.machine, &inputs) else {
+                return;
+            };
+            for action in actions {
+                self.run(action);
             }
-            AnimationState::Idle => {
-                // Slow idle animation
-                if now.duration_since(self.last_idle_frame_time) >= IDLE_ANIMATION_DURATION {
-                    self.last_idle_frame_time = now;
-                    self.idle_frame = (self.idle_frame + 1) % IDLE_FRAME_COUNT;
-                }
 
-                // Clear text after fade effect completes
-                if let Some(ref effect) = self.fade_effect {
-                    if effect.done() {
-                        self.typed_text.clear();
-                        self.fade_effect = None;
-                    }
+            let state = sprites.machine.state(&self.animation_state.name);
+            let shown = state.and_then(|s| s.shown(|name| sprites.animation(name).is_some()));
+            let Some(Shown::Animation { name, .. }) = shown else {
+                self.player.restart(now, 0);
+                self.variant = None;
+                events = &[];
+                finished = true;
+                continue;
+            };
+            self.variant = state
+                .and_then(|s| s.variant(|name| sprites.animation(name).is_some(), &gauges))
+                .map(str::to_string);
+            let name = self.variant.as_deref().unwrap_or(name);
+            let frame = match state.map(|s| s.start) {
+                // Randomly select a frame for variety
+                Some(StartFrame::Random) => {
+                    let frames = sprites.frame_count(name).max(1);
+                    self.rng.gen_range(0..frames)
                 }
+                Some(StartFrame::First) | None => 0,
+            };
+            self.player.restart(now, frame);
+            return;
+        }
+    }
+
+    fn run(&mut self, action: Action) {
+        match action {
+            // Characters disappear randomly
+            Action::DissolveText => {
+                self.fade_effect = Some(fx::dissolve((FADE_OUT_DURATION, Interpolation::QuadOut)))
+            }
+            Action::ClearText => {
+                self.typed_text.clear();
+                self.is_special_key_text = false;
+                self.fade_effect = None;
+            }
+            // Shown like a special key, so the first letter typed replaces it
+            Action::Greet => {
+                self.typed_text.set(self.mood.greeting());
+                self.is_special_key_text = true;
+                self.new_char_width = self.typed_text.width();
+                self.fade_effect = None;
+                self.typing_effect = Some(fx::coalesce((
+                    TYPING_EFFECT_DURATION,
+                    Interpolation::QuadOut,
+                )));
             }
         }
     }
 
+    /// Playback speed for the current state's animation
+    fn speed(&self, now: Instant) -> f32 {
+        match self.sprites.machine.state(&self.animation_state.name) {
+            Some(state) if state.tempo => self.tempo.speed(self.typing_rate.at(now)),
+            _ => 1.0,
+        }
+    }
+
+    /// The animation the current state shows, and whether it's dimmed
+    fn current_animation(&self) -> (String, bool) {
+        let state = self.sprites.machine.state(&self.animation_state.name);
+        match state.and_then(|s| s.shown(|name| self.sprites.animation(name).is_some())) {
+            Some(Shown::Animation { name, dim }) => {
+                (self.variant.as_deref().unwrap_or(name).to_string(), dim)
+            }
+            _ => (
+                state
+                    .map_or(spritesheet::IDLE, |s| s.animation.as_str())
+                    .to_string(),
+                false,
+            ),
+        }
+    }
+
+    /// What the state machine's conditions can read
+    fn gauges(&self, now: Instant) -> [(&'static str, f32); 4] {
+        [
+            (machine::TYPING_RATE, self.typing_rate.at(now)),
+            (machine::HAPPINESS, self.mood.happiness),
+            (machine::ENERGY, self.mood.energy),
+            (machine::BOREDOM, self.mood.boredom),
+        ]
+    }
+
+    /// Queue a one-shot reaction
+    pub fn emote(&mut self, emote: Emote, priority: Priority) -> Queued {
+        let now = self.now();
+        self.update_emotes(now);
+        let length = match self.sprites.cycle_duration(emote.animation()) {
+            length if length.is_zero() => emote::FALLBACK_DURATION,
+            length => length,
+        };
+        let queued = self.emotes.push(emote, priority, length, now);
+        if queued == Queued::Playing {
+            self.emote_player = Player::new(now);
+        }
+        queued
+    }
+
+    /// Move on to the next emote when one is done, and play the pack's frames for it
+    fn update_emotes(&mut self, now: Instant) {
+        if self.emotes.update(now) {
+            self.emote_player = Player::new(now);
+        }
+        if let Some(playing) = self.emotes.playing() {
+            let animation = self.sprites.animation(playing.emote.animation());
+            self.emote_player.update(animation, now, &mut self.rng);
+        }
+    }
+
+    /// The emote playing without frames of its own, and how long it has been on
+    fn frameless_emote(&self) -> Option<(&emote::Playing, Duration)> {
+        let playing = self.emotes.playing()?;
+        if self.sprites.animation(playing.emote.animation()).is_some() {
+            return None;
+        }
+        Some((playing, self.now().saturating_duration_since(playing.since)))
+    }
+
+    /// Say hello as the app starts, the way the mood and the pack's states say
+    pub fn greet(&mut self) {
+        let now = self.now();
+        self.update_state(&[machine::START], now);
+    }
+
+    /// Decoration to draw beside the sprite, and how long it has been up
+    pub fn overlay(&self) -> Option<(Overlay, Duration)> {
+        if let Some((playing, elapsed)) = self.frameless_emote() {
+            if let Some(overlay) = playing.emote.overlay() {
+                return Some((overlay, elapsed));
+            }
+        }
+        let state = self.sprites.machine.state(&self.animation_state.name)?;
+        Some((
+            state.overlay?,
+            self.now().duration_since(self.animation_state.since),
+        ))
+    }
+
+    /// The window was snapped to a screen edge; look toward it
+    pub fn on_snap(&mut self, direction: &Direction) {
+        match direction {
+            Direction::Left => self.facing_cue = Some(FacingCue::Edge(Facing::Left)),
+            Direction::Right => self.facing_cue = Some(FacingCue::Edge(Facing::Right)),
+            Direction::Top | Direction::Bottom => {}
+        }
+    }
+
+    /// The mouse moved to `column`; look toward it
+    pub fn on_mouse(&mut self, column: u16) {
+        self.facing_cue = Some(FacingCue::Mouse(column));
+    }
+
+    /// Play the turn animation, and turn toward the latest cue once it's done
+    fn update_facing(&mut self, now: Instant) {
+        if let Some(turn) = &mut self.turn {
+            if now < turn.until {
+                let animation = self.sprites.animation(spritesheet::TURN);
+                turn.player.update(animation, now, &mut self.rng);
+                return;
+            }
+            self.turn = None;
+        }
+
+        let target = match self.facing_cue {
+            Some(FacingCue::Text) => self.caret_column.and_then(|c| self.facing_toward(c)),
+            Some(FacingCue::Edge(facing)) => Some(facing),
+            Some(FacingCue::Mouse(column)) => self.facing_toward(column),
+            None => None,
+        };
+        if let Some(facing) = target.filter(|&facing| facing != self.facing) {
+            self.turn_to(facing, now);
+        }
+    }
+
+    /// Which way `column` is from the dog, if it's clearly off to one side
+    fn facing_toward(&self, column: u16) -> Option<Facing> {
+        let area = self.sprite_area?;
+        let middle = area.x + area.width / 2;
+        if column + FACING_DEAD_ZONE < middle {
+            Some(Facing::Left)
+        } else if column > middle + FACING_DEAD_ZONE {
+            Some(Facing::Right)
+        } else {
+            None
+        }
+    }
+
+    /// Look the other way, through the pack's turn animation if it has one
+    fn turn_to(&mut self, facing: Facing, now: Instant) {
+        let cycle = self.sprites.cycle_duration(spritesheet::TURN);
+        if !cycle.is_zero() {
+            self.turn = Some(Turn {
+                player: Player::new(now),
+                from: self.facing,
+                until: now + cycle,
+            });
+        }
+        self.facing = facing;
+    }
+
+    /// Show an error on the status line and append it to the log file, if there is one
+    /// A failure that keeps recurring is logged once and just stays on screen
+    pub fn report(&mut self, err: &dyn std::error::Error) {
+        let text = err.to_string();
+        let now = self.now();
+        if let Some(status) = self.status.as_mut().filter(|s| s.text == text) {
+            status.shown_at = now;
+            return;
+        }
+        if let Some(path) = &self.log {
+            log::write(path, &text);
+        }
+        self.status = Some(StatusMessage {
+            text,
+            shown_at: now,
+        });
+    }
+
     /// Request app to quit
     pub fn quit(&mut self) {
         self.should_quit = true;
@@ -253,42 +814,83 @@ impl App {
 
     /// Get elapsed time since last frame and reset timer
     pub fn get_elapsed(&mut self) -> Duration {
-        let now = Instant::now();
+        let now = self.now();
         let elapsed = now.duration_since(self.last_frame_time);
         self.last_frame_time = now;
         elapsed
     }
 
+    /// The frame to show right now, and which way it looks
+    pub fn pose(&self) -> Pose {
+        if let Some(playing) = self.emotes.playing() {
+            let name = playing.emote.animation();
+            if self.sprites.animation(name).is_some() {
+                return Pose {
+                    animation: name.to_string(),
+                    frame: self.emote_player.frame,
+                    facing: self.facing,
+                    dim: false,
+                    shift: (0, 0),
+                };
+            }
+        }
+        if let Some(turn) = &self.turn {
+            return Pose {
+                animation: spritesheet::TURN.to_string(),
+                frame: turn.player.frame,
+                facing: turn.from,
+                dim: false,
+                shift: (0, 0),
+            };
+        }
+        let (animation, dim) = self.current_animation();
+        let mut pose = Pose {
+            animation,
+            frame: self.player.frame,
+            facing: self.facing,
+            dim,
+            shift: (0, 0),
+        };
+        // Without frames of its own, an emote moves the state's frames around
+        if let Some((playing, elapsed)) = self.frameless_emote() {
+            let motion = playing.emote.motion(elapsed, playing.until - playing.since);
+            pose.shift = motion.shift;
+            if motion.turned {
+                pose.facing = pose.facing.opposite();
+            }
+        }
+        pose
+    }
+
     /// Check if the visual state has changed since last render
     pub fn needs_image_redraw(&self, terminal_size: (u16, u16)) -> bool {
         // Redraw if terminal size changed (images would be in wrong position)
         if self.last_terminal_size != terminal_size {
             return true;
         }
-        if self.last_rendered_state != Some(self.animation_state) {
+        if self.last_rendered_state.as_ref() != Some(&self.animation_state) {
             return true;
         }
-        let current_frame = match self.animation_state {
-            AnimationState::Idle => self.idle_frame,
-            AnimationState::Typing => self.typing_frame,
-        };
-        if self.last_rendered_frame != current_frame {
+        if self.last_rendered_pose != Some(self.pose()) {
             return true;
         }
-        if self.last_rendered_text != self.typed_text {
+        if self.last_rendered_text != self.typed_text.as_str() {
             return true;
         }
         false
     }
 
+    /// Forget what was last rendered so the sprite is drawn again on the next frame
+    /// (needed whenever the screen was cleared behind our back)
+    pub fn request_image_redraw(&mut self) {
+        self.last_rendered_state = None;
+    }
+
     /// Mark the current state as rendered
     pub fn mark_rendered(&mut self, terminal_size: (u16, u16)) {
-        self.last_rendered_state = Some(self.animation_state);
-        self.last_rendered_frame = match self.animation_state {
-            AnimationState::Idle => self.idle_frame,
-            AnimationState::Typing => self.typing_frame,
-        };
-        self.last_rendered_text = self.typed_text.clone();
+        self.last_rendered_state = Some(self.animation_state.clone());
+        self.last_rendered_pose = Some(self.pose());
+        self.last_rendered_text = self.typed_text.as_str().to_string();
         self.last_terminal_size = terminal_size;
     }
 }
@@ -0,0 +1,162 @@
+//! How fast the character types along with you
+//!
+//! Every key adds to a rate that decays exponentially, so a steady rhythm reads as
+//! its own keys per second while a pause lets it fall off smoothly instead of all
+//! at once. States marked `tempo` play their frames faster or slower in
+//! proportion, within the limits set in the config.
+
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// How quickly old keys stop counting (the rate's time constant)
+const SMOOTHING: Duration = Duration::from_secs(1);
+/// Keys per second that play animations at their own speed (about 60 WPM)
+pub const BASE_RATE: f32 = 5.0;
+/// Slowest speed ever used, whatever the config says
+const SLOWEST: f32 = 0.05;
+
+/// Keys per second, smoothed
+#[derive(Debug, Clone, Default)]
+pub struct TypingRate {
+    /// Rate as of the last key
+    value: f32,
+    last: Option<Instant>,
+}
+
+impl TypingRate {
+    /// Count a key pressed at `now`
+    pub fn record(&mut self, now: Instant) {
+        self.value = self.at(now) + 1.0 / SMOOTHING.as_secs_f32();
+        self.last = Some(now);
+    }
+
+    /// The rate at `now` (not before the last key)
+    pub fn at(&self, now: Instant) -> f32 {
+        match self.last {
+            Some(last) => {
+                let since = now.saturating_duration_since(last);
+                self.value * (-since.as_secs_f32() / SMOOTHING.as_secs_f32()).exp()
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// Limits on how far the typing rate can speed up or slow down animations
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tempo {
+    /// Speed multiplier when typing slowly (or just starting)
+    pub min_speed: f32,
+    /// Speed multiplier when typing fast
+    pub max_speed: f32,
+}
+
+impl Default for Tempo {
+    fn default() -> Self {
+        Self {
+            min_speed: 0.5,
+            max_speed: 2.0,
+        }
+    }
+}
+
+impl Tempo {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.min_speed > 0.0 && self.max_speed.is_finite()) {
+            return Err("speeds must be positive numbers".to_string());
+        }
+        if self.min_speed > self.max_speed {
+            return Err(format!(
+                "min_speed ({}) is above max_speed ({})",
+                self.min_speed, self.max_speed
+            ));
+        }
+        Ok(())
+    }
+
+    /// Playback speed multiplier for a typing rate
+    pub fn speed(&self, rate: f32) -> f32 {
+        (rate / BASE_RATE)
+            .min(self.max_speed)
+            .max(self.min_speed)
+            .max(SLOWEST)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The rate at `at_ms` after pressing keys at each of `keys_ms`
+    fn rate(keys_ms: impl IntoIterator<Item = u64>, at_ms: u64) -> f32 {
+        let start = Instant::now();
+        let mut rate = TypingRate::default();
+        for key in keys_ms {
+            rate.record(start + Duration::from_millis(key));
+        }
+        rate.at(start + Duration::from_millis(at_ms))
+    }
+
+    /// Keys `gap_ms` apart for `seconds`
+    fn steady(gap_ms: u64, seconds: u64) -> impl Iterator<Item = u64> {
+        (0..seconds * 1000).step_by(gap_ms as usize)
+    }
+
+    #[test]
+    fn steady_typing_reads_as_its_keys_per_second() {
+        assert_eq!(rate([], 1000), 0.0);
+        // 20 WPM is 100 characters a minute; 120 WPM, 600
+        let slow = rate(steady(600, 10), 9_900);
+        let fast = rate(steady(100, 10), 9_950);
+        assert!((1.4..2.0).contains(&slow), "20 WPM gave {}", slow);
+        assert!((9.5..10.5).contains(&fast), "120 WPM gave {}", fast);
+    }
+
+    #[test]
+    fn bursts_rise_quickly_and_pauses_fade_smoothly() {
+        // Ten keys 50 ms apart: well above a steady 60 WPM straight away
+        assert!(rate(steady(50, 10).take(10), 450) > 7.0);
+        // Only one key doesn't make a rate
+        assert!(rate([0], 0) < 1.5);
+
+        let keys = || steady(100, 5);
+        let stopped = rate(keys(), 4_900);
+        let pausing = rate(keys(), 5_400);
+        let gone = rate(keys(), 9_000);
+        assert!(stopped > pausing && pausing > gone);
+        assert!(
+            pausing > stopped / 2.0,
+            "a short pause shouldn't halve the rate"
+        );
+        assert!(gone < 0.5);
+    }
+
+    #[test]
+    fn speed_follows_the_rate_within_limits() {
+        let tempo = Tempo::default();
+        assert_eq!(tempo.speed(BASE_RATE), 1.0);
+        assert_eq!(tempo.speed(7.5), 1.5);
+        assert_eq!(tempo.speed(0.0), 0.5);
+        assert_eq!(tempo.speed(100.0), 2.0);
+
+        let fixed = Tempo {
+            min_speed: 1.0,
+            max_speed: 1.0,
+        };
+        assert!(fixed.validate().is_ok());
+        assert_eq!(fixed.speed(20.0), 1.0);
+
+        let backwards = Tempo {
+            min_speed: 3.0,
+            max_speed: 2.0,
+        };
+        assert!(backwards.validate().is_err());
+        assert!(Tempo {
+            min_speed: 0.0,
+            ..fixed
+        }
+        .validate()
+        .is_err());
+    }
+}
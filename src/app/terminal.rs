@@ -1,12 +1,86 @@
+use super::error::WindowError;
 use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use image::RgbaImage;
+use std::collections::BTreeSet;
 use std::io::{self, Write};
+use std::sync::Mutex;
+
+/// Base64 bytes per kitty graphics escape (the protocol's limit)
+const KITTY_CHUNK: usize = 4096;
+
+/// Images already sent to a kitty-protocol terminal, by id
+static KITTY_IMAGES: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
 
 // Compact window size to fit dog + text
-const FIT_WIDTH: u32 = 400;   // pixels - width for text display
-const FIT_HEIGHT: u32 = 340;  // pixels - height for text + dog (14 rows)
-const MARGIN: u32 = 0;        // no margin from screen edge
+const FIT_WIDTH: u32 = 400; // pixels - width for text display
+const FIT_HEIGHT: u32 = 340; // pixels - height for text + dog (14 rows)
+const MARGIN: u32 = 0; // no margin from screen edge
+
+/// Switch the terminal into raw mode on the alternate screen
+pub fn enter() -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, Hide)
+}
+
+/// Put the terminal back the way we found it
+/// Every step is attempted even if an earlier one fails, since this also runs from the
+/// panic hook and signal handling where a half-restored terminal is the worst outcome
+/// Leaving the alternate screen takes iTerm2 and Sixel images with it; kitty keeps
+/// its images until told otherwise
+pub fn restore() -> io::Result<()> {
+    let deleted = delete_kitty_images();
+    let raw = disable_raw_mode();
+    let screen = execute!(
+        io::stdout(),
+        DisableMouseCapture,
+        LeaveAlternateScreen,
+        Show
+    );
+    deleted.and(raw).and(screen)
+}
+
+/// Report mouse movement as events (until `restore`)
+pub fn capture_mouse() -> io::Result<()> {
+    execute!(io::stdout(), EnableMouseCapture)
+}
+
+/// Erase inline images still painted on the (alternate) screen
+/// iTerm2 and Sixel images live in the cell grid, so clearing the screen removes
+/// them; kitty images are deleted with the protocol's own command. The scrollback
+/// is left alone
+pub fn clear_images() -> io::Result<()> {
+    delete_kitty_images()?;
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    write!(handle, "\x1b[2J")?;
+    handle.flush()
+}
+
+/// Delete the kitty images we placed, if any, so they're sent again next time
+fn delete_kitty_images() -> io::Result<()> {
+    // Forgotten even if deleting fails: an image sent twice beats one never sent
+    let images = std::mem::take(&mut *KITTY_IMAGES.lock().unwrap_or_else(|e| e.into_inner()));
+    if images.is_empty() {
+        return Ok(());
+    }
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    // Uppercase `I`: free the image data by id, whether it's on screen or not
+    // (`d=A` only frees images with a placement, and earlier frames have none)
+    for id in images {
+        write!(handle, "\x1b_Ga=d,d=I,i={},q=2\x1b\\", id)?;
+    }
+    handle.flush()
+}
 
 /// Direction for window movement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
     Top,
     Bottom,
@@ -15,7 +89,7 @@ pub enum Direction {
 }
 
 /// Fit window and move to edge of current screen (the screen where the window is located)
-pub fn fit_and_move(direction: Direction) -> io::Result<()> {
+pub fn fit_and_move(direction: Direction) -> Result<(), WindowError> {
     // Helper script to find the screen containing the window's center point
     // Note: NSScreen uses Cocoa coords (bottom-left origin, y up)
     //       Window bounds use screen coords (top-left origin, y down)
@@ -92,7 +166,9 @@ pub fn fit_and_move(direction: Direction) -> io::Result<()> {
                 end tell
             end tell"#,
             find_screen = find_screen_script,
-            m = MARGIN, w = FIT_WIDTH, h = FIT_HEIGHT
+            m = MARGIN,
+            w = FIT_WIDTH,
+            h = FIT_HEIGHT
         ),
         Direction::Bottom => format!(
             r#"{find_screen}
@@ -112,7 +188,9 @@ pub fn fit_and_move(direction: Direction) -> io::Result<()> {
                 end tell
             end tell"#,
             find_screen = find_screen_script,
-            m = MARGIN, w = FIT_WIDTH, h = FIT_HEIGHT
+            m = MARGIN,
+            w = FIT_WIDTH,
+            h = FIT_HEIGHT
         ),
         Direction::Left => format!(
             r#"{find_screen}
@@ -128,7 +206,9 @@ pub fn fit_and_move(direction: Direction) -> io::Result<()> {
                 end tell
             end tell"#,
             find_screen = find_screen_script,
-            m = MARGIN, w = FIT_WIDTH, h = FIT_HEIGHT
+            m = MARGIN,
+            w = FIT_WIDTH,
+            h = FIT_HEIGHT
         ),
         Direction::Right => format!(
             r#"{find_screen}
@@ -146,20 +226,30 @@ pub fn fit_and_move(direction: Direction) -> io::Result<()> {
                 end tell
             end tell"#,
             find_screen = find_screen_script,
-            m = MARGIN, w = FIT_WIDTH, h = FIT_HEIGHT
+            m = MARGIN,
+            w = FIT_WIDTH,
+            h = FIT_HEIGHT
         ),
     };
     run_applescript(&script)
 }
 
-fn run_applescript(script: &str) -> io::Result<()> {
+fn run_applescript(script: &str) -> Result<(), WindowError> {
     use std::process::Command;
-    Command::new("osascript")
+    let output = Command::new("osascript")
         .arg("-e")
         .arg(script)
         .output()
-        .ok();
-    Ok(())
+        .map_err(WindowError::Spawn)?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(WindowError::Script {
+            status: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
 }
 
 /// Display an inline image using iTerm2's OSC 1337 protocol
@@ -178,10 +268,7 @@ pub fn display_image_inline(
 ) -> io::Result<()> {
     let encoded = STANDARD.encode(data);
 
-    let mut args = vec![
-        format!("size={}", data.len()),
-        "inline=1".to_string(),
-    ];
+    let mut args = vec![format!("size={}", data.len()), "inline=1".to_string()];
 
     if let Some(w) = width {
         args.push(format!("width={}", w));
@@ -232,3 +319,84 @@ pub fn display_image_at_position(
     write!(handle, "\x1b8")?;
     handle.flush()
 }
+
+/// Place an image with the kitty graphics protocol, replacing any placed before
+/// The pixels are sent only the first time; after that the terminal already holds
+/// the image under `id` and it is just placed again
+pub fn display_kitty_image(
+    id: u32,
+    image: &RgbaImage,
+    row: u16,
+    col: u16,
+    width: u16,
+    height: u16,
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    write!(handle, "\x1b7\x1b[{};{}H", row + 1, col + 1)?;
+    // Lowercase `d`: take placements off the screen but keep the images for later
+    write!(handle, "\x1b_Ga=d,d=a,q=2\x1b\\")?;
+
+    // C=1: leave the cursor where it is; q=2: no replies to read back
+    let placement = format!("i={},c={},r={},C=1,q=2", id, width, height);
+    let sent = KITTY_IMAGES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains(&id);
+    if sent {
+        write!(handle, "\x1b_Ga=p,{}\x1b\\", placement)?;
+    } else {
+        let encoded = STANDARD.encode(image.as_raw());
+        let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let more = u8::from(i + 1 < chunks.len());
+            if i == 0 {
+                write!(
+                    handle,
+                    "\x1b_Ga=T,f=32,s={},v={},{},m={};",
+                    image.width(),
+                    image.height(),
+                    placement,
+                    more
+                )?;
+            } else {
+                write!(handle, "\x1b_Gm={};", more)?;
+            }
+            handle.write_all(chunk)?;
+            write!(handle, "\x1b\\")?;
+        }
+    }
+
+    write!(handle, "\x1b8")?;
+    handle.flush()?;
+    // Only once the terminal has all of it; a failed send is tried again next frame
+    if !sent {
+        KITTY_IMAGES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id);
+    }
+    Ok(())
+}
+
+/// Paint a Sixel image with its top left corner at a cell
+/// The cells it covers are erased first: transparent pixels would otherwise let
+/// the previous frame show through
+pub fn display_sixel_at_position(
+    data: &[u8],
+    row: u16,
+    col: u16,
+    width: u16,
+    height: u16,
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    write!(handle, "\x1b7")?;
+    for line in row..row + height {
+        write!(handle, "\x1b[{};{}H\x1b[{}X", line + 1, col + 1, width)?;
+    }
+    write!(handle, "\x1b[{};{}H", row + 1, col + 1)?;
+    handle.write_all(data)?;
+    write!(handle, "\x1b8")?;
+    handle.flush()
+}
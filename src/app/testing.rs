@@ -0,0 +1,33 @@
+//! Fixtures shared by the unit tests
+
+use std::fs;
+use std::path::Path;
+
+/// Write a tiny pack into `dir`: a 4x4 `frame.png` filled with `color`, drawn looking
+/// right, and one-frame idle and typing animations, plus whatever `extra` adds to
+/// the manifest
+pub fn write_pack(dir: &Path, color: [u8; 3], extra: &str) {
+    let [r, g, b] = color;
+    // Lopsided so the mirrored frame differs
+    let frame = image::RgbaImage::from_fn(4, 4, |x, _| {
+        image::Rgba([r, g, b, if x == 0 { 0 } else { 255 }])
+    });
+    frame.save(dir.join("frame.png")).unwrap();
+    let manifest = r#"
+        [background]
+        mode = "none"
+
+        [animations.idle]
+        files = ["frame.png"]
+        frame_ms = 1000
+
+        [animations.typing]
+        files = ["frame.png"]
+        frame_ms = 250
+        "#;
+    fs::write(
+        dir.join("manifest.toml"),
+        format!("{}\n{}", manifest, extra),
+    )
+    .unwrap();
+}
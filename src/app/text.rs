@@ -0,0 +1,417 @@
+//! The typed text shown above the dog
+//!
+//! Text is kept and cut in whole grapheme clusters, so an accented letter, an emoji
+//! from an IME or a flag is never split in half: slicing a `String` at a byte or
+//! `char` count panics (or draws garbage) on exactly those. Widths are terminal
+//! columns as `unicode-width` counts them, with every grapheme taking at least one,
+//! since the big text gives even a lone combining mark a letter of its own.
+//!
+//! A cursor, always between two graphemes, says where typing goes. It stays at the
+//! end unless the app emulates an editor's cursor.
+
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Text made of whole graphemes with a cursor, keeping at most `limit` graphemes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextBuffer {
+    text: String,
+    /// Byte offset of the cursor, on a grapheme boundary
+    cursor: usize,
+    limit: usize,
+}
+
+/// Columns a grapheme takes (at least one)
+fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width().max(1)
+}
+
+/// Columns `text` takes
+pub fn width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+/// The longest start of `text` that fits in `columns`
+fn head(text: &str, columns: usize) -> &str {
+    let mut used = 0;
+    let mut end = 0;
+    for (at, grapheme) in text.grapheme_indices(true) {
+        used += grapheme_width(grapheme);
+        if used > columns {
+            break;
+        }
+        end = at + grapheme.len();
+    }
+    &text[..end]
+}
+
+/// The longest end of `text` that fits in `columns`
+fn tail(text: &str, columns: usize) -> &str {
+    let mut used = 0;
+    let mut start = text.len();
+    for (at, grapheme) in text.grapheme_indices(true).rev() {
+        used += grapheme_width(grapheme);
+        if used > columns {
+            break;
+        }
+        start = at;
+    }
+    &text[start..]
+}
+
+impl TextBuffer {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            text: String::new(),
+            cursor: 0,
+            limit,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Number of graphemes
+    pub fn len(&self) -> usize {
+        self.text.graphemes(true).count()
+    }
+
+    /// Columns the whole text takes
+    pub fn width(&self) -> usize {
+        width(&self.text)
+    }
+
+    /// The text before the cursor
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    /// The text after the cursor
+    pub fn after_cursor(&self) -> &str {
+        &self.text[self.cursor..]
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    /// Replace the text, with the cursor at the end (keeping the text's end if
+    /// it's over the limit)
+    pub fn set(&mut self, text: &str) {
+        self.clear();
+        self.insert_str(text);
+    }
+
+    /// Insert `text` at the cursor and move past it, dropping graphemes past the
+    /// limit: the oldest before the insert first, then the last after it, then
+    /// the start of `text` itself
+    /// Returns the columns taken by the graphemes `text` changed or added (a
+    /// combining mark changes the grapheme before it)
+    pub fn insert_str(&mut self, text: &str) -> usize {
+        let start = self.cursor;
+        let end = start + text.len();
+        self.text.insert_str(start, text);
+        let graphemes: Vec<Range<usize>> = self
+            .text
+            .grapheme_indices(true)
+            .map(|(at, grapheme)| at..at + grapheme.len())
+            .collect();
+        let mut excess = graphemes.len().saturating_sub(self.limit);
+        let before = graphemes.iter().take_while(|g| g.end <= start).count();
+        let after = graphemes
+            .iter()
+            .rev()
+            .take_while(|g| g.start >= end)
+            .count();
+        let front = excess.min(before);
+        excess -= front;
+        let back = excess.min(after);
+        excess -= back;
+        // Whatever is still over comes off the start of the inserted text
+        let front = front + excess;
+
+        let keep_from = graphemes.get(front).map_or(self.text.len(), |g| g.start);
+        let keep_to = match back {
+            0 => self.text.len(),
+            back => graphemes[graphemes.len() - back].start,
+        };
+        self.text.truncate(keep_to);
+        self.text.drain(..keep_from);
+        let (start, end) = (
+            start.saturating_sub(keep_from),
+            end.saturating_sub(keep_from).min(self.text.len()),
+        );
+        // The inserted text may have joined the grapheme after it
+        self.cursor = std::iter::once(0)
+            .chain(
+                self.text
+                    .grapheme_indices(true)
+                    .map(|(at, grapheme)| at + grapheme.len()),
+            )
+            .find(|&boundary| boundary >= end)
+            .unwrap_or(self.text.len());
+        self.text
+            .grapheme_indices(true)
+            .filter(|(at, grapheme)| *at < end && at + grapheme.len() > start)
+            .map(|(_, grapheme)| grapheme_width(grapheme))
+            .sum()
+    }
+
+    /// Remove the grapheme before the cursor
+    pub fn delete_back(&mut self) -> Option<String> {
+        let (at, _) = self.before_cursor().grapheme_indices(true).next_back()?;
+        let deleted = self.text.drain(at..self.cursor).collect();
+        self.cursor = at;
+        Some(deleted)
+    }
+
+    /// Remove the grapheme after the cursor
+    pub fn delete_forward(&mut self) -> Option<String> {
+        let grapheme = self.after_cursor().graphemes(true).next()?;
+        let end = self.cursor + grapheme.len();
+        Some(self.text.drain(self.cursor..end).collect())
+    }
+
+    /// Remove the word before the cursor, and any spaces between it and the cursor
+    pub fn delete_word_back(&mut self) -> Option<String> {
+        let mut start = self.cursor;
+        let mut in_word = false;
+        for (at, grapheme) in self.before_cursor().grapheme_indices(true).rev() {
+            let space = grapheme.trim().is_empty();
+            if in_word && space {
+                break;
+            }
+            in_word |= !space;
+            start = at;
+        }
+        if start == self.cursor {
+            return None;
+        }
+        let deleted = self.text.drain(start..self.cursor).collect();
+        self.cursor = start;
+        Some(deleted)
+    }
+
+    /// Move the cursor back one grapheme
+    pub fn left(&mut self) {
+        if let Some((at, _)) = self.before_cursor().grapheme_indices(true).next_back() {
+            self.cursor = at;
+        }
+    }
+
+    /// Move the cursor on one grapheme
+    pub fn right(&mut self) {
+        if let Some(grapheme) = self.after_cursor().graphemes(true).next() {
+            self.cursor += grapheme.len();
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// The longest end of the text that fits in `columns`
+    pub fn tail(&self, columns: usize) -> &str {
+        tail(&self.text, columns)
+    }
+
+    /// Text either side of the cursor that fits in `columns` together, showing the
+    /// end when the cursor is there and at least half the room before it otherwise
+    pub fn around_cursor(&self, columns: usize) -> (&str, &str) {
+        let (before, after) = (self.before_cursor(), self.after_cursor());
+        let after = head(after, columns - width(before).min(columns / 2));
+        let before = tail(before, columns - width(after));
+        (before, after)
+    }
+}
+
+impl PartialEq<str> for TextBuffer {
+    fn eq(&self, other: &str) -> bool {
+        self.text == other
+    }
+}
+
+impl PartialEq<&str> for TextBuffer {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn multi_byte_text_is_cut_between_graphemes() {
+        let mut text = TextBuffer::new(3);
+        assert_eq!(text.insert_str("über"), 3);
+        assert_eq!(text, "ber");
+        text.set("ü👍🏽e\u{301}");
+        assert_eq!(text.len(), 3);
+        assert_eq!(text.width(), 4);
+        assert_eq!(text.tail(3), "👍🏽e\u{301}");
+        assert_eq!(text.tail(1), "e\u{301}");
+        assert_eq!(text.tail(0), "");
+
+        // A combining mark changes the letter before it rather than adding one
+        text.set("ab");
+        assert_eq!(text.insert_str("\u{308}"), 1);
+        assert_eq!(text.len(), 2);
+        assert_eq!(text.delete_back().as_deref(), Some("b\u{308}"));
+        assert_eq!(text.delete_back().as_deref(), Some("a"));
+        assert_eq!(text.delete_back(), None);
+    }
+
+    #[test]
+    fn wide_letters_count_double() {
+        let mut text = TextBuffer::new(10);
+        assert_eq!(text.insert_str("日本"), 4);
+        assert_eq!(text.tail(3), "本");
+    }
+
+    #[test]
+    fn the_cursor_edits_like_a_line_editor() {
+        let mut text = TextBuffer::new(100);
+        text.set("git comit -m");
+        for _ in 0..5 {
+            text.left();
+        }
+        assert_eq!(text.before_cursor(), "git com");
+        assert_eq!(text.insert_str("m"), 1);
+        assert_eq!(text, "git commit -m");
+        assert_eq!(text.delete_word_back().as_deref(), Some("comm"));
+        assert_eq!(text.delete_forward().as_deref(), Some("i"));
+        text.insert_str("push");
+        assert_eq!(text, "git pusht -m");
+        text.home();
+        assert_eq!(text.delete_back(), None);
+        text.right();
+        text.delete_forward();
+        text.end();
+        assert_eq!(text.delete_word_back().as_deref(), Some("-m"));
+        assert_eq!(text, "gt pusht ");
+
+        // Moving steps over whole graphemes
+        text.set("a👍🏽e\u{301}");
+        text.left();
+        assert_eq!(text.after_cursor(), "e\u{301}");
+        text.left();
+        assert_eq!(text.before_cursor(), "a");
+    }
+
+    #[test]
+    fn a_full_buffer_keeps_what_was_just_typed() {
+        let mut text = TextBuffer::new(4);
+        text.set("abcd");
+        text.home();
+        text.right();
+        text.insert_str("x");
+        assert_eq!(text, "xbcd");
+        assert_eq!(text.before_cursor(), "x");
+        // With nothing older before it, the end goes
+        text.home();
+        text.insert_str("y");
+        assert_eq!(text, "yxbc");
+        assert_eq!(text.before_cursor(), "y");
+    }
+
+    #[test]
+    fn the_view_follows_the_cursor() {
+        let mut text = TextBuffer::new(100);
+        text.set("the quick brown fox");
+        assert_eq!(text.around_cursor(8), ("rown fox", ""));
+        text.home();
+        assert_eq!(text.around_cursor(8), ("", "the quic"));
+        for _ in 0..10 {
+            text.right();
+        }
+        assert_eq!(text.around_cursor(8), ("ick ", "brow"));
+    }
+
+    proptest! {
+        #[test]
+        fn any_typing_stays_within_the_limit(
+            limit in 1usize..20,
+            keys in prop::collection::vec((any::<String>(), any::<bool>()), 0..40),
+        ) {
+            let mut text = TextBuffer::new(limit);
+            let mut typed = String::new();
+            for (key, backspace) in &keys {
+                if *backspace {
+                    let before = text.as_str().to_string();
+                    let popped = text.delete_back().unwrap_or_default();
+                    prop_assert_eq!(format!("{}{}", text.as_str(), popped), before);
+                    typed = text.as_str().to_string();
+                } else {
+                    let added = text.insert_str(key);
+                    typed.push_str(key);
+                    prop_assert!(added <= text.width());
+                    prop_assert!(typed.ends_with(text.as_str()));
+                }
+                prop_assert!(text.len() <= limit);
+            }
+        }
+
+        #[test]
+        fn tails_are_whole_graphemes_that_fit(s in any::<String>(), columns in 0usize..30) {
+            let mut text = TextBuffer::new(usize::MAX);
+            text.set(&s);
+            let tail = text.tail(columns);
+            let start = s.len() - tail.len();
+            prop_assert!(s.ends_with(tail));
+            // Cut where a grapheme starts, keeping as many as fit
+            let graphemes: Vec<_> = s.grapheme_indices(true).collect();
+            let first = graphemes.iter().position(|&(at, _)| at >= start).unwrap_or(graphemes.len());
+            prop_assert!(first == graphemes.len() || graphemes[first].0 == start);
+            let used: usize = graphemes[first..].iter().map(|(_, g)| grapheme_width(g)).sum();
+            prop_assert!(used <= columns);
+            if first > 0 {
+                prop_assert!(used + grapheme_width(graphemes[first - 1].1) > columns);
+            }
+        }
+
+        #[test]
+        fn any_editing_keeps_the_cursor_between_graphemes(
+            limit in 1usize..20,
+            keys in prop::collection::vec((0u8..8, any::<String>()), 0..60),
+            columns in 1usize..20,
+        ) {
+            let mut text = TextBuffer::new(limit);
+            for (key, typed) in &keys {
+                match key {
+                    0 => { text.insert_str(typed); }
+                    1 => { text.delete_back(); }
+                    2 => { text.delete_forward(); }
+                    3 => { text.delete_word_back(); }
+                    4 => text.left(),
+                    5 => text.right(),
+                    6 => text.home(),
+                    _ => text.end(),
+                }
+                prop_assert!(text.len() <= limit);
+                let boundaries: Vec<_> = text.as_str().grapheme_indices(true).map(|(at, _)| at).collect();
+                prop_assert!(text.after_cursor().is_empty() || boundaries.contains(&text.before_cursor().len()));
+
+                let (before, after) = text.around_cursor(columns);
+                prop_assert!(text.before_cursor().ends_with(before));
+                prop_assert!(text.after_cursor().starts_with(after));
+                prop_assert!(width(before) + width(after) <= columns);
+                if text.after_cursor().is_empty() {
+                    prop_assert_eq!(before, text.tail(columns));
+                }
+            }
+        }
+    }
+}
@@ -1,21 +1,69 @@
-use super::spritesheet;
-use super::state::{AnimationState, App};
-use super::terminal;
+use super::error::GraphicsError;
+use super::graphics::{self, GraphicsBackend};
+use super::machine::Overlay;
+use super::state::App;
+use super::text;
+use super::widget::DogWidget;
 use ratatui::{
+    buffer::Buffer,
     layout::{Constraint, Layout, Rect},
     style::{Color, Style},
+    widgets::Widget,
     Frame,
 };
 use std::time::Duration;
 use tachyonfx::Shader;
 use tui_big_text::{BigText, PixelSize};
+use unicode_segmentation::UnicodeSegmentation;
 
 // Playful/cute text color - soft and friendly
-const TEXT_MAIN: Color = Color::Rgb(255, 182, 193);    // Soft pink
+const TEXT_MAIN: Color = Color::Rgb(255, 182, 193); // Soft pink
 
-pub fn draw(frame: &mut Frame, app: &mut App, needs_image_redraw: bool, elapsed: Duration) {
-    let area = frame.area();
+// Muted color for the status line so it doesn't compete with the dog
+const STATUS_TEXT: Color = Color::Rgb(150, 150, 150);
 
+// Soft blue for the zZz and question mark
+const OVERLAY_TEXT: Color = Color::Rgb(150, 170, 230);
+/// Letters of the zZz, bottom first, each one a step up and to the right
+const ZZZ: [char; 3] = ['z', 'Z', 'z'];
+/// Time for each letter of the zZz to appear (and for all of them to clear)
+const ZZZ_STEP: Duration = Duration::from_millis(600);
+/// Time the question mark spends up, then down
+const QUESTION_BOB: Duration = Duration::from_millis(400);
+/// Drawn in the big text where the cursor is, when emulating an editor
+const CARET: &str = "|";
+// Warm red for the hearts
+const HEART: Color = Color::Rgb(230, 90, 120);
+/// Hearts floating up beside the sprite
+const HEARTS: u16 = 3;
+/// Time for a heart to rise one row
+const HEART_STEP: Duration = Duration::from_millis(150);
+/// Rows a heart rises before it's gone
+const HEART_RISE: u16 = 4;
+
+/// Draw the full-screen companion view
+pub fn draw(
+    frame: &mut Frame,
+    app: &mut App,
+    graphics: GraphicsBackend,
+    needs_image_redraw: bool,
+    elapsed: Duration,
+) {
+    let widget = DogWidget::new(graphics)
+        .redraw_images(needs_image_redraw)
+        .elapsed(elapsed);
+    frame.render_stateful_widget(widget, frame.area(), app);
+}
+
+/// Render the text display and sprite into `area`
+pub(crate) fn render(
+    area: Rect,
+    buf: &mut Buffer,
+    app: &mut App,
+    graphics: GraphicsBackend,
+    needs_image_redraw: bool,
+    elapsed: Duration,
+) {
     // Content dimensions
     let text_height = 4u16;
     let dog_height = 10u16;
@@ -32,24 +80,28 @@ pub fn draw(frame: &mut Frame, app: &mut App, needs_image_redraw: bool, elapsed:
 
     // Vertical layout: text on top, dog below (both centered together)
     let chunks = Layout::vertical([
-        Constraint::Length(text_height),  // Text display (top)
-        Constraint::Length(dog_height),   // Dog sprite (bottom)
+        Constraint::Length(text_height), // Text display (top)
+        Constraint::Length(dog_height),  // Dog sprite (bottom)
     ])
     .split(content);
 
     // Draw components
     // Draw dog first
-    draw_dog(frame, chunks[1], app, needs_image_redraw);
+    if let Err(err) = draw_dog(buf, chunks[1], app, graphics, needs_image_redraw) {
+        app.report(&err);
+    }
 
     // Draw text if there's any typed text
+    app.caret_column = None;
     if !app.typed_text.is_empty() {
-        let (text_area, new_char_area) = draw_text_display(frame, chunks[0], app);
+        let (text_area, new_char_area, caret_column) = draw_text_display(buf, chunks[0], app);
+        app.caret_column = Some(caret_column);
 
         // Apply typing effect (coalesce) only to new character area
         if let Some(ref mut effect) = app.typing_effect {
             if !effect.done() {
                 if let Some(char_area) = new_char_area {
-                    effect.process(elapsed.into(), frame.buffer_mut(), char_area);
+                    effect.process(elapsed.into(), buf, char_area);
                 }
             }
         }
@@ -57,49 +109,160 @@ pub fn draw(frame: &mut Frame, app: &mut App, needs_image_redraw: bool, elapsed:
         // Apply fade-out effect to full text area
         if let Some(ref mut effect) = app.fade_effect {
             if !effect.done() {
-                effect.process(elapsed.into(), frame.buffer_mut(), text_area);
+                effect.process(elapsed.into(), buf, text_area);
             }
         }
     }
+
+    draw_status_line(buf, area, app);
 }
 
-fn draw_dog(_frame: &mut Frame, area: Rect, app: &App, needs_image_redraw: bool) {
-    // Display PNG sprite if spritesheet is loaded
-    // No fallback - if terminal doesn't support images, just show text only
-    if spritesheet::is_loaded() && needs_image_redraw {
-        display_spritesheet_frame(area, app);
+/// Show the latest error on the bottom row, if any
+fn draw_status_line(buf: &mut Buffer, area: Rect, app: &App) {
+    let Some(status) = &app.status else {
+        return;
+    };
+    if area.height == 0 {
+        return;
     }
+    let line = format!("⚠ {}", status.text);
+    buf.set_stringn(
+        area.x,
+        area.bottom() - 1,
+        line,
+        area.width as usize,
+        Style::default().fg(STATUS_TEXT),
+    );
 }
 
-fn display_spritesheet_frame(area: Rect, app: &App) {
-    let frame_data = match app.animation_state {
-        AnimationState::Typing => spritesheet::get_typing_frame(app.typing_frame),
-        AnimationState::Idle => spritesheet::get_idle_frame(app.idle_frame),
+fn draw_dog(
+    buf: &mut Buffer,
+    area: Rect,
+    app: &mut App,
+    graphics: GraphicsBackend,
+    needs_image_redraw: bool,
+) -> Result<(), GraphicsError> {
+    // Center sprite in area
+    let sprite_width = 20u16;
+    let sprite_height = 10u16;
+    let sprite_area = Rect {
+        x: area.x + (area.width.saturating_sub(sprite_width)) / 2,
+        y: area.y + (area.height.saturating_sub(sprite_height)) / 2,
+        width: sprite_width,
+        height: sprite_height,
     };
+    // Remembered even without images so the dog knows where it stands
+    app.sprite_area = Some(sprite_area);
 
-    if let Some(data) = frame_data {
-        // Center sprite in area
-        let sprite_width = 20u16;
-        let sprite_height = 10u16;
-        let col = area.x + (area.width.saturating_sub(sprite_width)) / 2;
-        let row = area.y + (area.height.saturating_sub(sprite_height)) / 2;
-
-        let _ = terminal::display_image_at_position(
-            data,
-            row,
-            col,
-            Some(sprite_width as u32),
-            Some(sprite_height as u32),
-        );
+    // Display the sprite if the pack has frames for it
+    // No fallback - if terminal doesn't support images, just show text only
+    // Cells are part of the buffer, so they're drawn on every render
+    if needs_image_redraw || graphics.uses_cells() {
+        let pose = app.pose();
+        if let Some(frame) = app
+            .sprites
+            .frame_facing(&pose.animation, pose.frame, pose.facing)
+        {
+            let frame = if pose.dim { frame.dimmed() } else { frame };
+            let drawn_area = shifted(sprite_area, pose.shift, area);
+            if graphics.uses_cells() {
+                graphics::draw_cells(frame, drawn_area, buf);
+            } else {
+                graphics.display(frame, drawn_area)?;
+            }
+        }
+    }
+    if let Some((overlay, elapsed)) = app.overlay() {
+        draw_overlay(buf, area, sprite_area, overlay, elapsed);
     }
+    Ok(())
 }
 
-/// Returns (full_text_area, new_char_area)
-fn draw_text_display(frame: &mut Frame, area: Rect, app: &App) -> (Rect, Option<Rect>) {
-    if app.typed_text.is_empty() {
-        return (area, None);
+/// `rect` moved by `shift` (columns, rows), kept inside `within` where it fits
+fn shifted(rect: Rect, shift: (i16, i16), within: Rect) -> Rect {
+    let x = rect
+        .x
+        .saturating_add_signed(shift.0)
+        .min(within.right().saturating_sub(rect.width))
+        .max(within.x);
+    let y = rect
+        .y
+        .saturating_add_signed(shift.1)
+        .min(within.bottom().saturating_sub(rect.height))
+        .max(within.y);
+    Rect { x, y, ..rect }
+}
+
+/// Draw an overlay beside the sprite's top right corner, clipped to `area`
+fn draw_overlay(
+    buf: &mut Buffer,
+    area: Rect,
+    sprite_area: Rect,
+    overlay: Overlay,
+    elapsed: Duration,
+) {
+    match overlay {
+        Overlay::Zzz => {
+            // Letters appear one at a time, then all clear and it starts over
+            let step = (elapsed.as_millis() / ZZZ_STEP.as_millis()) as usize;
+            let shown = step % (ZZZ.len() + 1);
+            for (i, letter) in ZZZ.iter().take(shown).enumerate() {
+                let x = sprite_area.right() + i as u16;
+                let y = (sprite_area.y + 2).saturating_sub(i as u16);
+                if area.contains((x, y).into()) {
+                    buf[(x, y)].set_char(*letter).set_fg(OVERLAY_TEXT);
+                }
+            }
+        }
+        Overlay::Question => {
+            let up = (elapsed.as_millis() / QUESTION_BOB.as_millis()).is_multiple_of(2);
+            let (x, y) = (sprite_area.right(), sprite_area.y + if up { 0 } else { 1 });
+            if area.contains((x, y).into()) {
+                buf[(x, y)].set_char('?').set_fg(OVERLAY_TEXT);
+            }
+        }
+        Overlay::Woof => {
+            for (i, letter) in "woof!".chars().enumerate() {
+                let (x, y) = (sprite_area.right() + i as u16, sprite_area.y + 1);
+                if area.contains((x, y).into()) {
+                    buf[(x, y)].set_char(letter).set_fg(OVERLAY_TEXT);
+                }
+            }
+        }
+        Overlay::Hearts => {
+            // One after another, each rising a row at a time until it's gone
+            let step = (elapsed.as_millis() / HEART_STEP.as_millis()) as u16;
+            for i in 0..HEARTS {
+                let Some(rise) = step.checked_sub(i * 2).filter(|&rise| rise < HEART_RISE) else {
+                    continue;
+                };
+                let x = sprite_area.right() + i;
+                let Some(y) = (sprite_area.y + HEART_RISE).checked_sub(rise) else {
+                    continue;
+                };
+                if area.contains((x, y).into()) {
+                    buf[(x, y)].set_char('♥').set_fg(HEART);
+                }
+            }
+        }
     }
+}
+
+/// `text` as BigText can draw it: one glyph per column of width. Its font only has
+/// ASCII and it draws a grapheme's first character, so a combining mark is dropped
+/// and anything else is as many blank glyphs as it is wide
+fn as_drawn(text: &str) -> String {
+    text.graphemes(true)
+        .map(|grapheme| match grapheme.chars().next() {
+            Some(c) if c == ' ' || c.is_ascii_graphic() => c.to_string(),
+            _ => " ".repeat(text::width(grapheme)),
+        })
+        .collect()
+}
 
+/// Returns (full_text_area, new_char_area, caret_column)
+/// The caret column is just after the drawn cursor, or the last character without one
+fn draw_text_display(buf: &mut Buffer, area: Rect, app: &App) -> (Rect, Option<Rect>, u16) {
     // Center text horizontally and use full height
     let text_height = 4u16; // Quadrant pixel size = 4 rows
     let text_area = Rect {
@@ -113,63 +276,63 @@ fn draw_text_display(frame: &mut Frame, area: Rect, app: &App) -> (Rect, Option<
     let char_width = 4u16;
     let max_chars = (area.width / char_width) as usize;
 
-    // Count displayed characters
-    let total_chars = app.typed_text.chars().count();
-    let displayed_chars = total_chars.min(max_chars);
+    // The caret takes a character of its own
+    let caret = if app.edit_cursor { CARET } else { "" };
+    let caret_chars = caret.chars().count();
+
+    // Count characters (a wide one counts as two)
+    let total_chars = app.typed_text.width() + caret_chars;
 
     // Check if text fits in the area
     let text_fits = total_chars <= max_chars || max_chars == 0;
 
-    let text = if text_fits {
-        // Text fits - center it
-        BigText::builder()
-            .pixel_size(PixelSize::Quadrant)
-            .style(Style::default().fg(TEXT_MAIN))
-            .centered()
-            .lines(vec![app.typed_text.clone().into()])
-            .build()
+    // Text too long - show what's around the cursor (the end, unless editing)
+    let (before, after) = if text_fits {
+        (
+            app.typed_text.before_cursor(),
+            app.typed_text.after_cursor(),
+        )
     } else {
-        // Text too long - show rightmost characters, right-aligned
-        let start = app.typed_text.len() - max_chars;
-        let display_text = &app.typed_text[start..];
-        BigText::builder()
-            .pixel_size(PixelSize::Quadrant)
-            .style(Style::default().fg(TEXT_MAIN))
-            .right_aligned()
-            .lines(vec![display_text.to_string().into()])
-            .build()
+        app.typed_text
+            .around_cursor(max_chars.saturating_sub(caret_chars))
     };
+    let (before, after) = (as_drawn(before), as_drawn(after));
+    let before_chars = before.chars().count() + caret_chars;
+    let displayed_chars = before_chars + after.chars().count();
 
-    frame.render_widget(text, text_area);
-
-    // Calculate the area for just the new character(s)
-    let new_char_area = if app.new_char_count > 0 && displayed_chars > 0 {
-        let new_chars_width = (app.new_char_count as u16) * char_width;
-
-        if text_fits {
-            // Text is centered - new char is at the end of centered text
-            let total_text_width = (displayed_chars as u16) * char_width;
-            let text_start_x = text_area.x + (text_area.width.saturating_sub(total_text_width)) / 2;
-            let new_char_x = text_start_x + total_text_width - new_chars_width;
-
-            Some(Rect {
-                x: new_char_x,
-                y: text_area.y,
-                width: new_chars_width,
-                height: text_area.height,
-            })
-        } else {
-            // Text is right-aligned - new char is at the right edge
-            Some(Rect {
-                x: text_area.x + text_area.width - new_chars_width,
-                y: text_area.y,
-                width: new_chars_width,
-                height: text_area.height,
-            })
-        }
+    let mut text = BigText::builder();
+    text.pixel_size(PixelSize::Quadrant)
+        .style(Style::default().fg(TEXT_MAIN))
+        .lines(vec![format!("{}{}{}", before, caret, after).into()]);
+    if text_fits {
+        // Text fits - center it
+        text.centered();
     } else {
-        None
+        // Otherwise right-aligned
+        text.right_aligned();
+    }
+    text.build().render(text_area, buf);
+
+    let text_start_x = if text_fits {
+        let total_text_width = (displayed_chars as u16) * char_width;
+        text_area.x + (text_area.width.saturating_sub(total_text_width)) / 2
+    } else {
+        text_area.right() - (displayed_chars as u16) * char_width
     };
+    let caret_column = text_start_x + (before_chars as u16) * char_width;
+
+    // Calculate the area for just the new character(s), which end at the cursor
+    let new_chars = app.new_char_width.min(before_chars - caret_chars);
+    let new_char_area = (new_chars > 0 && max_chars > 0).then(|| {
+        let new_chars_width = (new_chars as u16) * char_width;
+        let caret_width = (caret_chars as u16) * char_width;
+        Rect {
+            x: caret_column - caret_width - new_chars_width,
+            y: text_area.y,
+            width: new_chars_width,
+            height: text_area.height,
+        }
+    });
 
-    (text_area, new_char_area)
+    (text_area, new_char_area, caret_column)
 }
@@ -0,0 +1,66 @@
+use super::graphics::GraphicsBackend;
+use super::state::App;
+use super::ui;
+use ratatui::{buffer::Buffer, layout::Rect, widgets::StatefulWidget};
+use std::time::Duration;
+
+/// The typing dog as a ratatui widget, for embedding in other apps
+///
+/// The host owns the `App` state, feeds it key events with `App::on_key`, calls
+/// `App::tick` once per frame, and renders this widget with the app as its state.
+///
+/// ```no_run
+/// # use tapwatch::{App, DogWidget, GraphicsBackend};
+/// # fn draw(frame: &mut ratatui::Frame, app: &mut App, elapsed: std::time::Duration) {
+/// let widget = DogWidget::new(GraphicsBackend::detect()).elapsed(elapsed);
+/// frame.render_stateful_widget(widget, frame.area(), app);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DogWidget {
+    graphics: GraphicsBackend,
+    redraw_images: Option<bool>,
+    elapsed: Duration,
+}
+
+impl DogWidget {
+    pub fn new(graphics: GraphicsBackend) -> Self {
+        Self {
+            graphics,
+            redraw_images: None,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Time since the previous render, used to advance text effects
+    pub fn elapsed(mut self, elapsed: Duration) -> Self {
+        self.elapsed = elapsed;
+        self
+    }
+
+    /// Force (or suppress) re-emitting the sprite image this frame
+    /// By default the image is redrawn only when the frame or area changed
+    pub fn redraw_images(mut self, redraw: bool) -> Self {
+        self.redraw_images = Some(redraw);
+        self
+    }
+}
+
+impl StatefulWidget for DogWidget {
+    type State = App;
+
+    fn render(self, area: Rect, buf: &mut Buffer, app: &mut App) {
+        let size = (area.width, area.height);
+        let redraw = self
+            .redraw_images
+            .unwrap_or_else(|| app.needs_image_redraw(size));
+
+        ui::render(area, buf, app, self.graphics, redraw, self.elapsed);
+
+        // Only track render state when we decided ourselves; an explicit
+        // override means the caller is doing its own bookkeeping
+        if redraw && self.redraw_images.is_none() {
+            app.mark_rendered(size);
+        }
+    }
+}
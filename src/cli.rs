@@ -0,0 +1,49 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// A typing companion dog for your terminal
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Directory of a sprite pack to use instead of the built-in dog
+    #[arg(long, value_name = "DIR")]
+    pub sprite_pack: Option<PathBuf>,
+
+    /// Color variant of the sprite pack (see the pack's `[variants]`)
+    #[arg(long, value_name = "NAME")]
+    pub variant: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Check the environment and suggest fixes when the dog doesn't move or render
+    Doctor,
+    /// Tools for making sprite packs
+    Sprites {
+        #[command(subcommand)]
+        command: SpritesCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SpritesCommand {
+    /// Report problems with a pack and write a contact sheet of its processed frames
+    Check {
+        /// Pack directory (containing manifest.toml)
+        pack: PathBuf,
+        /// Where to write the contact sheet [default: <pack name>-contact-sheet.png]
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Play a pack's animations, reloading whenever its files change
+    Preview {
+        /// Pack directory (containing manifest.toml)
+        pack: PathBuf,
+        /// Animation to start with
+        #[arg(long, short)]
+        animation: Option<String>,
+    },
+}
@@ -0,0 +1,18 @@
+//! TapWatch - a typing companion dog for the terminal
+//!
+//! The binary is a thin event loop around this library. Other ratatui apps can embed
+//! the dog by owning an [`App`], feeding it keys (see [`app::keys`]) and rendering a
+//! [`DogWidget`] with the app as its state.
+//!
+//! Since the sprite pack's state machine drives the animations, `App::animation_state`
+//! is an [`app::machine::AnimationState`] naming any of the pack's states. The old
+//! two-state [`AnimationState`] is deprecated; convert with
+//! `AnimationState::from(&app.animation_state)` until you move to the state names.
+
+pub mod app;
+
+pub use app::graphics::GraphicsBackend;
+#[allow(deprecated)]
+pub use app::state::AnimationState;
+pub use app::widget::DogWidget;
+pub use app::App;
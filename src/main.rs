@@ -1,100 +1,292 @@
-mod app;
+mod cli;
 
-use app::App;
-use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
+use clap::Parser;
+use cli::{Cli, Command, SpritesCommand};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, MouseEventKind};
 use ratatui::prelude::*;
-use rdev::{listen, EventType, Key}; // Key needed for shift detection
+use rdev::{listen, EventType, Key}; // Key needed for modifier detection
 use std::{
     io::{self, stdout},
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
-        mpsc::{self, Receiver, TryRecvError},
+        mpsc::{self, Receiver, SyncSender, TryRecvError},
         Arc,
     },
     thread,
-    time::Duration,
+    time::{Duration, SystemTime},
+};
+use tapwatch::app::{
+    self,
+    cache::FrameCache,
+    config::Config,
+    doctor,
+    emote::Priority,
+    error::CaptureError,
+    keys::Modifiers,
+    log, mood, preview,
+    recolor::Variant,
+    signals::{self, Signal},
+    sprite_check,
+    spritesheet::SpritePack,
+    terminal::Direction,
 };
+use tapwatch::{App, GraphicsBackend};
 
 /// Target frame rate for smooth animations
 const FRAME_RATE: u64 = 30; // Reduced for stability
 const FRAME_DURATION: Duration = Duration::from_millis(1000 / FRAME_RATE);
 
 fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Doctor) => {
+            let report = doctor::run(cli.sprite_pack.as_deref(), cli.variant.as_deref());
+            println!("{}", report);
+            std::process::exit(if report.has_failures() { 1 } else { 0 });
+        }
+        Some(Command::Sprites { command }) => std::process::exit(run_sprites(command)),
+        None => {}
+    }
+
+    // A broken config shouldn't stop the dog; fall back to defaults and say why
+    let (config, config_error) = match Config::load() {
+        Ok(config) => (config, None),
+        Err(err) => (Config::default(), Some(err)),
+    };
+
+    // Same for a sprite pack that won't load: the built-in dog takes over
+    let mut variant = config.variant();
+    if let Some(name) = cli.variant {
+        variant.name = Some(name);
+    }
+    // Processed frames come back from the disk cache on later starts
+    let frame_cache = FrameCache::user();
+    let (sprites, sprite_error) = match cli.sprite_pack.as_ref().or(config.sprite_pack.as_ref()) {
+        Some(dir) => match SpritePack::load_with(dir, &variant, frame_cache.as_ref()) {
+            Ok(pack) => (Arc::new(pack), None),
+            // A variant the pack doesn't have still shows the pack, in its own colors
+            Err(err) if variant != Variant::default() => {
+                match SpritePack::load_with(dir, &Variant::default(), frame_cache.as_ref()) {
+                    Ok(pack) => (Arc::new(pack), Some(err)),
+                    Err(_) => (SpritePack::embedded(), Some(err)),
+                }
+            }
+            Err(err) => (SpritePack::embedded(), Some(err)),
+        },
+        None => match SpritePack::embedded_variant(&variant, frame_cache.as_ref()) {
+            Ok(pack) => (pack, None),
+            Err(err) => (SpritePack::embedded(), Some(err)),
+        },
+    };
+
+    // A mood that can't be read back starts over
+    let mood_path = mood::path();
+    let (saved_mood, mood_error) = match &mood_path {
+        Some(path) => match mood::load(path, SystemTime::now(), &config.mood) {
+            Ok(mood) => (mood, None),
+            Err(err) => (Default::default(), Some(err)),
+        },
+        None => (Default::default(), None),
+    };
+
+    // Make sure a panic or a signal never leaves the terminal in raw mode
+    signals::install_panic_hook();
+    let signal_rx = signals::spawn_listener()?;
+
     // Set up terminal
-    enable_raw_mode()?;
-    let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
+    app::terminal::enter()?;
+    let backend = CrosstermBackend::new(stdout());
     let mut terminal = Terminal::new(backend)?;
+    if config.follow_mouse {
+        app::terminal::capture_mouse()?;
+    }
 
     // Create app state
-    let mut app = App::new();
+    let mut app = App::from_sprites(sprites)
+        .with_log(log::path())
+        .with_sleep_after(config.sleep_after())
+        .with_tempo(config.tempo)
+        .with_mistake_sensitivity(config.mistake_sensitivity)
+        .with_mood_rates(config.mood)
+        .with_edit_cursor(config.edit_cursor)
+        .with_mood(saved_mood);
+    if let Some(err) = config_error {
+        app.report(&err);
+    }
+    if let Some(err) = sprite_error {
+        app.report(&err);
+    }
+    if let Some(err) = mood_error {
+        app.report(&err);
+    }
+    app.greet();
+    let graphics = config.graphics.resolve();
 
     // Set up bounded channel for global key events (prevents backpressure)
-    let (tx, rx) = mpsc::sync_channel::<String>(32);
+    // Capture failures travel the same channel so they surface in the UI
+    let (tx, rx) = mpsc::sync_channel::<Result<String, CaptureError>>(32);
+
+    // Without global capture, keys typed into this window drive the dog instead
+    if config.global_capture {
+        spawn_global_capture(tx);
+    }
+
+    // Main event loop
+    let result = run_app(
+        &mut terminal,
+        &mut app,
+        graphics,
+        !config.global_capture,
+        config.follow_mouse,
+        rx,
+        signal_rx,
+    );
+
+    // Restore terminal
+    app::terminal::restore()?;
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+    }
+    if let Some(path) = mood_path {
+        if let Err(e) = mood::save(&path, &app.mood, SystemTime::now()) {
+            eprintln!("Error: {}", e);
+        }
+    }
+
+    println!("Thanks for using TapWatch! (ﾉ◕ヮ◕)ﾉ*:･ﾟ✧");
+    Ok(())
+}
+
+/// Run a `sprites` subcommand, returning the exit code
+fn run_sprites(command: SpritesCommand) -> i32 {
+    match command {
+        SpritesCommand::Check { pack, output } => {
+            let (report, animations) = match sprite_check::check(&pack) {
+                Ok(result) => result,
+                Err(err) => {
+                    eprintln!("✗ {}: {}", pack.display(), err);
+                    return 1;
+                }
+            };
+            println!("{}", report);
+
+            let output = output
+                .unwrap_or_else(|| PathBuf::from(format!("{}-contact-sheet.png", report.pack)));
+            match sprite_check::contact_sheet(&animations).save(&output) {
+                Ok(()) => println!("Contact sheet written to {}", output.display()),
+                Err(err) => {
+                    eprintln!("✗ cannot write {}: {}", output.display(), err);
+                    return 1;
+                }
+            }
+            if report.has_errors() {
+                1
+            } else {
+                0
+            }
+        }
+        SpritesCommand::Preview { pack, animation } => {
+            // A broken config falls back to the defaults here too, but says so first
+            let config = Config::load().unwrap_or_else(|err| {
+                eprintln!("Warning: {}; using the default graphics settings", err);
+                Config::default()
+            });
+            let graphics = config.graphics.resolve();
+            match preview::run(&pack, animation.as_deref(), graphics) {
+                Ok(()) => 0,
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    1
+                }
+            }
+        }
+    }
+}
+
+/// Listen for keys system-wide on a background thread
+fn spawn_global_capture(tx: SyncSender<Result<String, CaptureError>>) {
+    let error_tx = tx.clone();
 
-    // Track Shift state across events
+    // Track modifier state across events
     let shift_held = Arc::new(AtomicBool::new(false));
+    let ctrl_held = Arc::new(AtomicBool::new(false));
+    let alt_held = Arc::new(AtomicBool::new(false));
+    let meta_held = Arc::new(AtomicBool::new(false));
 
     // Spawn global key listener thread
     thread::spawn(move || {
-        let shift_state = shift_held;
         let callback = move |event: rdev::Event| {
-            match event.event_type {
-                EventType::KeyPress(key) => {
-                    // Track Shift state
-                    if matches!(key, Key::ShiftLeft | Key::ShiftRight) {
-                        shift_state.store(true, Ordering::SeqCst);
-                        return; // Don't send Shift as a key
-                    }
+            let (key, pressed) = match event.event_type {
+                EventType::KeyPress(key) => (key, true),
+                EventType::KeyRelease(key) => (key, false),
+                _ => return,
+            };
 
-                    let is_shifted = shift_state.load(Ordering::SeqCst);
-                    let key_str = app::keys::key_to_string(key, is_shifted);
-                    // Use try_send to avoid blocking if channel is full
-                    let _ = tx.try_send(key_str.into_owned());
-                }
-                EventType::KeyRelease(key) => {
-                    // Track Shift release
-                    if matches!(key, Key::ShiftLeft | Key::ShiftRight) {
-                        shift_state.store(false, Ordering::SeqCst);
-                    }
-                }
-                _ => {}
+            // Track modifiers; they're sent with the key they modify, not on their own
+            let held = match key {
+                Key::ShiftLeft | Key::ShiftRight => Some(&shift_held),
+                Key::ControlLeft | Key::ControlRight => Some(&ctrl_held),
+                Key::Alt => Some(&alt_held),
+                Key::MetaLeft | Key::MetaRight => Some(&meta_held),
+                _ => None,
+            };
+            if let Some(held) = held {
+                held.store(pressed, Ordering::SeqCst);
+                return;
+            }
+            if !pressed {
+                return;
+            }
+
+            let is_shifted = shift_held.load(Ordering::SeqCst);
+            let modifiers = Modifiers {
+                ctrl: ctrl_held.load(Ordering::SeqCst),
+                alt: alt_held.load(Ordering::SeqCst),
+                meta: meta_held.load(Ordering::SeqCst),
+            };
+            let key_str = app::keys::key_to_string(key, is_shifted);
+            if let Some(key_str) = app::keys::chord(key_str, modifiers) {
+                // Use try_send to avoid blocking if channel is full
+                let _ = tx.try_send(Ok(key_str.into_owned()));
             }
         };
 
         // Note: On macOS, this requires Accessibility permissions
-        if let Err(_e) = listen(callback) {
-            // Silently fail - the app will still work with terminal-only input
+        // The app still works with terminal-only input, so just report why
+        if let Err(err) = listen(callback) {
+            let _ = error_tx.send(Err(CaptureError::from(err)));
         }
     });
-
-    // Main event loop
-    let result = run_app(&mut terminal, &mut app, rx);
-
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
-
-    if let Err(e) = result {
-        eprintln!("Error: {}", e);
-    }
-
-    println!("Thanks for using TapWatch! (ﾉ◕ヮ◕)ﾉ*:･ﾟ✧");
-    Ok(())
 }
 
 fn run_app(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     app: &mut App,
-    rx: Receiver<String>,
+    graphics: GraphicsBackend,
+    feed_terminal_keys: bool,
+    follow_mouse: bool,
+    rx: Receiver<Result<String, CaptureError>>,
+    signal_rx: Receiver<Signal>,
 ) -> io::Result<()> {
     loop {
+        // React to process signals before drawing
+        while let Ok(signal) = signal_rx.try_recv() {
+            match signal {
+                Signal::Quit => app.quit(),
+                Signal::Suspend => suspend(terminal, app, follow_mouse)?,
+                Signal::Emote(emote) => {
+                    app.emote(emote, Priority::High);
+                }
+                Signal::Resume | Signal::Resize => {
+                    // Old images stay on screen until cleared, then get drawn afresh
+                    terminal.clear()?;
+                    app.request_image_redraw();
+                }
+            }
+        }
+
         // Get terminal size for dirty-state tracking
         let term_size = terminal.size()?;
         let terminal_size = (term_size.width, term_size.height);
@@ -106,7 +298,7 @@ fn run_app(
         let elapsed = app.get_elapsed();
 
         // Draw UI
-        terminal.draw(|frame| app::ui::draw(frame, app, needs_image_redraw, elapsed))?;
+        terminal.draw(|frame| app::ui::draw(frame, app, graphics, needs_image_redraw, elapsed))?;
 
         // Mark as rendered if we did redraw images
         if needs_image_redraw {
@@ -116,7 +308,8 @@ fn run_app(
         // Check for global key events (non-blocking, drain up to 10 at a time)
         for _ in 0..10 {
             match rx.try_recv() {
-                Ok(key) => app.on_key(key),
+                Ok(Ok(key)) => app.on_key(key),
+                Ok(Err(err)) => app.report(&err),
                 Err(TryRecvError::Empty) => break,
                 Err(TryRecvError::Disconnected) => break,
             }
@@ -124,7 +317,16 @@ fn run_app(
 
         // Poll for terminal events (with timeout for animation)
         if event::poll(FRAME_DURATION)? {
-            if let Event::Key(key_event) = event::read()? {
+            let event = event::read()?;
+            if let Event::Mouse(mouse) = &event {
+                if matches!(
+                    mouse.kind,
+                    MouseEventKind::Moved | MouseEventKind::Drag(_) | MouseEventKind::Down(_)
+                ) {
+                    app.on_mouse(mouse.column);
+                }
+            }
+            if let Event::Key(key_event) = event {
                 // Only handle key press events (not release)
                 if key_event.kind == KeyEventKind::Press {
                     match key_event.code {
@@ -133,29 +335,41 @@ fn run_app(
                         }
                         KeyCode::Left => {
                             // Fit and move to left edge
-                            let _ = app::terminal::fit_and_move(app::terminal::Direction::Left);
+                            move_window(app, Direction::Left);
                         }
                         KeyCode::Right => {
                             // Fit and move to right edge
-                            let _ = app::terminal::fit_and_move(app::terminal::Direction::Right);
+                            move_window(app, Direction::Right);
                         }
                         KeyCode::Up => {
                             // Fit and move to top edge
-                            let _ = app::terminal::fit_and_move(app::terminal::Direction::Top);
+                            move_window(app, Direction::Top);
                         }
                         KeyCode::Down => {
                             // Fit and move to bottom edge
-                            let _ = app::terminal::fit_and_move(app::terminal::Direction::Bottom);
+                            move_window(app, Direction::Bottom);
                         }
                         KeyCode::Char('c')
                             if key_event.modifiers.contains(event::KeyModifiers::CONTROL) =>
                         {
                             app.quit();
                         }
+                        KeyCode::Char('z')
+                            if key_event.modifiers.contains(event::KeyModifiers::CONTROL) =>
+                        {
+                            // Raw mode swallows SIGTSTP, so Ctrl+Z arrives here as a key
+                            suspend(terminal, app, follow_mouse)?;
+                        }
                         KeyCode::Esc => {
                             app.quit();
                         }
-                        _ => {}
+                        _ => {
+                            if feed_terminal_keys {
+                                if let Some(key) = app::keys::from_crossterm(&key_event) {
+                                    app.on_key(key.into_owned());
+                                }
+                            }
+                        }
                     }
                 }
             }
@@ -172,3 +386,26 @@ fn run_app(
 
     Ok(())
 }
+
+/// Snap the window to a screen edge, reporting failures on the status line
+fn move_window(app: &mut App, direction: Direction) {
+    match app::terminal::fit_and_move(direction) {
+        Ok(()) => app.on_snap(&direction),
+        Err(err) => app.report(&err),
+    }
+}
+
+/// Stop the process with the terminal restored, then repaint everything on resume
+fn suspend(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    follow_mouse: bool,
+) -> io::Result<()> {
+    signals::suspend()?;
+    if follow_mouse {
+        app::terminal::capture_mouse()?;
+    }
+    terminal.clear()?;
+    app.request_image_redraw();
+    Ok(())
+}